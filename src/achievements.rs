@@ -0,0 +1,395 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::{tile, Board, BoardShiftDirection, BoardShiftedEvent},
+    daily::DailyResults,
+    game::{BoardGame, Game, GameMode, NewGameEvent, SoloBoard},
+    merge::MergeRule,
+    settings::Settings,
+    stats::Statistics,
+    storage,
+};
+
+/// Unlocks the [`ACHIEVEMENTS`] as they are reached and saves them.
+pub struct AchievementsPlugin;
+
+pub struct Achievement {
+    /// Stable key used in the save file.
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub condition: Condition,
+}
+
+/// What unlocks an [`Achievement`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    /// A tile of at least this value is on the board, with the classic merge rule.
+    ReachTile(u32),
+    /// Scores at least this many points in one game.
+    Score(u32),
+    /// Reaches [`MergeRule::winning_value`] without taking a move back, in a game that allowed it.
+    WinWithoutUndo,
+    /// Reaches [`MergeRule::winning_value`] in at most this many moves.
+    WinWithinMoves(u32),
+    /// At most `tiles` tiles are left on the board while one of them is worth `min_tile` or more,
+    /// with the classic merge rule.
+    ClearBoard { tiles: usize, min_tile: u32 },
+    /// The largest tile stays in a corner for this many moves in a row, see
+    /// [`crate::board::Grid::is_corner`].
+    CornerStreak(u32),
+    /// Finishes this many games.
+    GamesPlayed(u32),
+    /// Finishes this many scored daily challenges.
+    DailyChallenges(u32),
+}
+
+pub const ACHIEVEMENTS: &[Achievement] = &[
+    Achievement {
+        id: "tile_512",
+        name: "Halfway There",
+        description: "Reach a 512 tile",
+        condition: Condition::ReachTile(512),
+    },
+    Achievement {
+        id: "tile_1024",
+        name: "So Close",
+        description: "Reach a 1024 tile",
+        condition: Condition::ReachTile(1024),
+    },
+    Achievement {
+        id: "tile_2048",
+        name: "2048",
+        description: "Reach a 2048 tile",
+        condition: Condition::ReachTile(2048),
+    },
+    Achievement {
+        id: "tile_4096",
+        name: "Keep Going",
+        description: "Reach a 4096 tile",
+        condition: Condition::ReachTile(4096),
+    },
+    Achievement {
+        id: "score_20000",
+        name: "High Scorer",
+        description: "Score 20000 points in one game",
+        condition: Condition::Score(20000),
+    },
+    Achievement {
+        id: "no_undo",
+        name: "No Regrets",
+        description: "Win without undoing a move",
+        condition: Condition::WinWithoutUndo,
+    },
+    Achievement {
+        id: "quick_win",
+        name: "Speedrunner",
+        description: "Win in 1200 moves or less",
+        condition: Condition::WinWithinMoves(1200),
+    },
+    Achievement {
+        id: "clean_board",
+        name: "Spring Cleaning",
+        description: "Have 3 tiles or less on the board with a 256 tile",
+        condition: Condition::ClearBoard {
+            tiles: 3,
+            min_tile: 256,
+        },
+    },
+    Achievement {
+        id: "corner_streak",
+        name: "Cornered",
+        description: "Keep the largest tile in a corner for 100 moves",
+        condition: Condition::CornerStreak(100),
+    },
+    Achievement {
+        id: "daily_1",
+        name: "Daily Player",
+        description: "Play a daily challenge",
+        condition: Condition::DailyChallenges(1),
+    },
+    Achievement {
+        id: "daily_7",
+        name: "Daily Habit",
+        description: "Play 7 daily challenges",
+        condition: Condition::DailyChallenges(7),
+    },
+    Achievement {
+        id: "games_10",
+        name: "Regular",
+        description: "Play 10 games",
+        condition: Condition::GamesPlayed(10),
+    },
+    Achievement {
+        id: "games_100",
+        name: "Devoted",
+        description: "Play 100 games",
+        condition: Condition::GamesPlayed(100),
+    },
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnlockedAchievement {
+    pub id: String,
+    /// Local date of the unlock, formatted as `YYYY-MM-DD`.
+    pub date: String,
+}
+
+/// The unlocked achievements, stored in [`Achievements::FILE`].
+#[derive(Resource, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Achievements {
+    pub unlocked: Vec<UnlockedAchievement>,
+}
+
+impl Achievements {
+    pub const FILE: &str = "achievements.ron";
+
+    pub fn load() -> Self {
+        return storage::load(Achievements::FILE);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&UnlockedAchievement> {
+        return self.unlocked.iter().find(|unlocked| unlocked.id == id);
+    }
+
+    pub fn is_unlocked(&self, id: &str) -> bool {
+        return self.get(id).is_some();
+    }
+}
+
+/// Sent once when an achievement is unlocked.
+pub struct AchievementUnlockedEvent(pub &'static Achievement);
+
+/// What the conditions need to know about the current game.
+#[derive(Resource, Debug, Default)]
+struct Progress {
+    corner_streak: u32,
+    /// Whether undo was off at some point, winning without it then means nothing.
+    undo_disabled: bool,
+}
+
+impl Condition {
+    fn is_met(
+        &self,
+        game: &Game,
+        board_game: &BoardGame,
+        rule: MergeRule,
+        tiles: &[(tile::Position, tile::Points)],
+        progress: &Progress,
+    ) -> bool {
+        let max_tile = tiles
+            .iter()
+            .filter_map(|(_, points)| points.number())
+            .max()
+            .unwrap_or(0);
+        let won = max_tile >= rule.winning_value();
+        // The tile values of the other rules aren't comparable
+        let classic = rule == MergeRule::Classic;
+
+        return match *self {
+            Condition::ReachTile(value) => classic && max_tile >= value,
+            Condition::Score(score) => board_game.score >= score,
+            Condition::WinWithoutUndo => won && game.undos == 0 && !progress.undo_disabled,
+            Condition::WinWithinMoves(moves) => won && board_game.moves <= moves,
+            Condition::ClearBoard {
+                tiles: count,
+                min_tile,
+            } => classic && tiles.len() <= count && max_tile >= min_tile,
+            Condition::CornerStreak(moves) => progress.corner_streak >= moves,
+            // Checked against the saved records instead
+            Condition::GamesPlayed(_) | Condition::DailyChallenges(_) => false,
+        };
+    }
+}
+
+impl AchievementsPlugin {
+    fn unlock(
+        achievements: &mut Achievements,
+        unlocked_writer: &mut EventWriter<AchievementUnlockedEvent>,
+        achievement: &'static Achievement,
+    ) {
+        achievements.unlocked.push(UnlockedAchievement {
+            id: achievement.id.to_string(),
+            date: chrono::Local::now().format("%Y-%m-%d").to_string(),
+        });
+        unlocked_writer.send(AchievementUnlockedEvent(achievement));
+    }
+
+    fn sys_reset_progress(
+        mut progress: ResMut<Progress>,
+        mut new_game_reader: EventReader<NewGameEvent>,
+    ) {
+        if new_game_reader.iter().count() > 0 {
+            *progress = Progress::default();
+        }
+    }
+
+    /// Checks the board once after every move.
+    ///
+    /// Reads the moves of the previous frame, so the merged tiles of the last one are already
+    /// gone and its new tile is on the board.
+    fn sys_check_board(
+        mut achievements: ResMut<Achievements>,
+        mut progress: ResMut<Progress>,
+        mut unlocked_writer: EventWriter<AchievementUnlockedEvent>,
+        mut shifted_reader: EventReader<BoardShiftedEvent>,
+        game: Res<Game>,
+        game_mode: Res<GameMode>,
+        settings: Res<Settings>,
+        query_board: Query<(Entity, &Board, &BoardGame), SoloBoard>,
+        tiles: Query<(&tile::Position, &tile::Points, &Parent)>,
+    ) {
+        // Only the board of a single player counts
+        let Ok((entity, board, board_game)) = query_board.get_single() else {
+            shifted_reader.clear();
+            return;
+        };
+        if !shifted_reader.iter().any(|event| event.board == entity) {
+            return;
+        }
+        // The handcrafted boards of the puzzles would make most of them trivial
+        if game_mode.is_level() {
+            return;
+        }
+
+        progress.undo_disabled |= game_mode.undo_limit(&settings) == 0;
+
+        let tiles = tiles
+            .iter()
+            .filter(|(_, _, parent)| parent.get() == entity)
+            .map(|(&position, &points, _)| (position, points))
+            .collect::<Vec<_>>();
+
+        let max_tile = tiles
+            .iter()
+            .filter_map(|(_, points)| points.number())
+            .max()
+            .unwrap_or(0);
+        let in_corner = tiles.iter().any(|&(position, points)| {
+            points.number() == Some(max_tile) && board.grid.is_corner(board.size, position)
+        });
+        progress.corner_streak = if in_corner {
+            progress.corner_streak + 1
+        } else {
+            0
+        };
+
+        let mut changed = false;
+        for achievement in ACHIEVEMENTS.iter() {
+            if !achievements.is_unlocked(achievement.id)
+                && achievement.condition.is_met(
+                    &game,
+                    board_game,
+                    game_mode.merge_rule(),
+                    &tiles,
+                    &progress,
+                )
+            {
+                AchievementsPlugin::unlock(&mut achievements, &mut unlocked_writer, achievement);
+                changed = true;
+            }
+        }
+
+        if changed {
+            storage::save(Achievements::FILE, achievements.as_ref());
+        }
+    }
+
+    fn sys_check_records(
+        mut achievements: ResMut<Achievements>,
+        mut unlocked_writer: EventWriter<AchievementUnlockedEvent>,
+        statistics: Res<Statistics>,
+        daily_results: Res<DailyResults>,
+    ) {
+        if !statistics.is_changed() && !daily_results.is_changed() {
+            return;
+        }
+
+        let mut changed = false;
+        for achievement in ACHIEVEMENTS.iter() {
+            let is_met = match achievement.condition {
+                Condition::GamesPlayed(games) => statistics.games_played >= games,
+                Condition::DailyChallenges(count) => daily_results.results.len() >= count as usize,
+                _ => continue,
+            };
+            if !achievements.is_unlocked(achievement.id) && is_met {
+                AchievementsPlugin::unlock(&mut achievements, &mut unlocked_writer, achievement);
+                changed = true;
+            }
+        }
+
+        if changed {
+            storage::save(Achievements::FILE, achievements.as_ref());
+        }
+    }
+}
+
+impl Plugin for AchievementsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Achievements::load())
+            .init_resource::<Progress>()
+            .add_event::<AchievementUnlockedEvent>()
+            // Not limited to `GameState::Playing`, which the last move of a game leaves
+            .add_systems(
+                (
+                    AchievementsPlugin::sys_check_board,
+                    AchievementsPlugin::sys_reset_progress,
+                )
+                    .chain()
+                    .before(BoardShiftDirection::sys_handle_board_shift_on_keypress),
+            )
+            .add_system(AchievementsPlugin::sys_check_records);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiles(values: &[u32]) -> Vec<(tile::Position, tile::Points)> {
+        return values
+            .iter()
+            .enumerate()
+            .map(|(x, &value)| {
+                let position = tile::Position {
+                    x: x as u8,
+                    y: 0,
+                    z: 0,
+                };
+                (position, tile::Points::new(value))
+            })
+            .collect();
+    }
+
+    #[test]
+    fn win_without_undo_needs_undo_to_be_available() {
+        let is_met = |undos: u32, values: &[u32], progress: &Progress| {
+            let game = Game { undos, ..default() };
+            Condition::WinWithoutUndo.is_met(
+                &game,
+                &BoardGame::new(0),
+                MergeRule::Classic,
+                &tiles(values),
+                progress,
+            )
+        };
+
+        assert!(is_met(0, &[2048, 4], &Progress::default()));
+        assert!(!is_met(1, &[2048, 4], &Progress::default()));
+        assert!(!is_met(0, &[1024, 4], &Progress::default()));
+
+        let without_undo = Progress {
+            undo_disabled: true,
+            ..default()
+        };
+        assert!(!is_met(0, &[2048, 4], &without_undo));
+
+        // The modes that turn it off
+        let settings = Settings::default();
+        assert!(GameMode::Classic.undo_limit(&settings) > 0);
+        assert_eq!(GameMode::Daily.undo_limit(&settings), 0);
+        assert_eq!(GameMode::TimeAttack { minutes: 3 }.undo_limit(&settings), 0);
+    }
+}
@@ -16,3 +16,32 @@ impl FromWorld for FontSpec {
         };
     }
 }
+
+#[derive(Resource)]
+pub struct SoundSpec {
+    pub slide: Handle<AudioSource>,
+    pub merge: Handle<AudioSource>,
+    pub spawn: Handle<AudioSource>,
+    pub invalid: Handle<AudioSource>,
+    pub win: Handle<AudioSource>,
+    pub game_over: Handle<AudioSource>,
+    pub music: Handle<AudioSource>,
+}
+
+impl FromWorld for SoundSpec {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world
+            .get_resource_mut::<AssetServer>()
+            .expect("AssetServer to be initialised with the DefaultPlugins");
+
+        return SoundSpec {
+            slide: asset_server.load("sounds/slide.wav"),
+            merge: asset_server.load("sounds/merge.wav"),
+            spawn: asset_server.load("sounds/spawn.wav"),
+            invalid: asset_server.load("sounds/invalid.wav"),
+            win: asset_server.load("sounds/win.wav"),
+            game_over: asset_server.load("sounds/game_over.wav"),
+            music: asset_server.load("sounds/music.wav"),
+        };
+    }
+}
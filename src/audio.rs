@@ -0,0 +1,171 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    assets::SoundSpec,
+    board::{BoardWonEvent, MoveRejectedEvent, TileMovedEvent, TileSpawnedEvent, TilesMergedEvent},
+    game::GameState,
+    settings::{KeyBinding, Settings},
+};
+
+/// Plays the sound effects and music by listening to the gameplay events.
+///
+/// Bevy only warns when there is no audio device, so this runs fine headless.
+pub struct SoundPlugin;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master_volume: f32,
+    pub sfx_volume: f32,
+    pub music_volume: f32,
+    pub muted: bool,
+    pub music: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        return Self {
+            master_volume: 0.8,
+            sfx_volume: 1.0,
+            music_volume: 0.4,
+            muted: false,
+            music: true,
+        };
+    }
+}
+
+impl AudioSettings {
+    pub fn effective_sfx_volume(&self) -> f32 {
+        if self.muted {
+            return 0.;
+        }
+        return self.master_volume * self.sfx_volume;
+    }
+
+    pub fn effective_music_volume(&self) -> f32 {
+        if self.muted || !self.music {
+            return 0.;
+        }
+        return self.master_volume * self.music_volume;
+    }
+}
+
+/// The sink of the looping background music, used to change its volume live.
+#[derive(Resource)]
+struct MusicSink(Handle<AudioSink>);
+
+impl SoundPlugin {
+    /// Every doubling of the merged value raises the merge sound by this many semitones.
+    const MERGE_SEMITONES_PER_LEVEL: f32 = 1.;
+
+    fn play(audio: &Audio, sound: &Handle<AudioSource>, volume: f32, speed: f32) {
+        if volume <= 0. {
+            return;
+        }
+
+        audio.play_with_settings(
+            sound.clone(),
+            PlaybackSettings::ONCE.with_volume(volume).with_speed(speed),
+        );
+    }
+
+    fn start_music(
+        mut commands: Commands,
+        audio: Res<Audio>,
+        sounds: Res<SoundSpec>,
+        settings: Res<Settings>,
+        sinks: Res<Assets<AudioSink>>,
+    ) {
+        let weak_handle = audio.play_with_settings(
+            sounds.music.clone(),
+            PlaybackSettings::LOOP.with_volume(settings.audio.effective_music_volume()),
+        );
+        commands.insert_resource(MusicSink(sinks.get_handle(weak_handle)));
+    }
+
+    fn sys_apply_settings(
+        settings: Res<Settings>,
+        music: Option<Res<MusicSink>>,
+        sinks: Res<Assets<AudioSink>>,
+    ) {
+        if !settings.is_changed() {
+            return;
+        }
+
+        // Without an audio device the sink never gets created
+        if let Some(sink) = music.and_then(|music| sinks.get(&music.0)) {
+            sink.set_volume(settings.audio.effective_music_volume());
+        }
+    }
+
+    fn sys_toggle_mute(input: Res<Input<KeyCode>>, mut settings: ResMut<Settings>) {
+        if settings.key_bindings.just_pressed(KeyBinding::Mute, &input) {
+            settings.audio.muted = !settings.audio.muted;
+        }
+    }
+
+    fn sys_play_gameplay_sounds(
+        audio: Res<Audio>,
+        sounds: Res<SoundSpec>,
+        settings: Res<Settings>,
+        mut moved_reader: EventReader<TileMovedEvent>,
+        mut merged_reader: EventReader<TilesMergedEvent>,
+        mut spawned_reader: EventReader<TileSpawnedEvent>,
+        mut rejected_reader: EventReader<MoveRejectedEvent>,
+        mut won_reader: EventReader<BoardWonEvent>,
+    ) {
+        let volume = settings.audio.effective_sfx_volume();
+
+        // A single shift sends an event per tile, only play one sound of each kind per frame.
+        if moved_reader.iter().count() > 0 {
+            SoundPlugin::play(&audio, &sounds.slide, volume, 1.);
+        }
+
+        let merges = merged_reader.iter().collect::<Vec<_>>();
+        // Merges into special tiles sound like the smallest numbers
+        if let Some(highest) = merges
+            .iter()
+            .map(|merge| merge.points.number().unwrap_or(4))
+            .max()
+        {
+            let level = (highest as f32).log2() - 2.;
+            let speed = 2f32.powf(level * SoundPlugin::MERGE_SEMITONES_PER_LEVEL / 12.);
+            SoundPlugin::play(&audio, &sounds.merge, volume, speed);
+        }
+        // Only the first winning tile of a board
+        if won_reader.iter().count() > 0 {
+            SoundPlugin::play(&audio, &sounds.win, volume, 1.);
+        }
+
+        if spawned_reader.iter().count() > 0 {
+            SoundPlugin::play(&audio, &sounds.spawn, volume * 0.5, 1.);
+        }
+
+        if rejected_reader.iter().count() > 0 {
+            SoundPlugin::play(&audio, &sounds.invalid, volume, 1.);
+        }
+    }
+
+    fn on_game_over(audio: Res<Audio>, sounds: Res<SoundSpec>, settings: Res<Settings>) {
+        SoundPlugin::play(
+            &audio,
+            &sounds.game_over,
+            settings.audio.effective_sfx_volume(),
+            1.,
+        );
+    }
+}
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SoundSpec>()
+            .add_startup_system(SoundPlugin::start_music)
+            .add_system(SoundPlugin::on_game_over.in_schedule(OnEnter(GameState::GameOver)))
+            .add_systems((
+                SoundPlugin::sys_apply_settings,
+                SoundPlugin::sys_toggle_mute,
+                SoundPlugin::sys_play_gameplay_sounds,
+            ));
+    }
+}
@@ -1,171 +1,462 @@
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    window::{PrimaryWindow, WindowResized},
+};
 use bevy_easings::*;
 
 use itertools::Itertools;
-use rand::{self, seq::IteratorRandom};
-use std::{cmp::Ordering, ops};
+use rand::{
+    self,
+    seq::{IteratorRandom, SliceRandom},
+    Rng,
+};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Reverse, collections::HashMap, time::Duration};
 
-use crate::{assets::FontSpec, game::Game};
+use crate::{
+    assets::FontSpec,
+    bot::Bot,
+    game::{BoardGame, Game, GameMode, History, Snapshot},
+    merge::{MergeRule, Merged},
+    net::Remote,
+    settings::{KeyBindings, Settings},
+    theme::{CurrentTheme, RoundedRects},
+    versus::Contender,
+};
 
-#[derive(Component)]
+/// The cells of a board, its tiles and blockers are its children.
+#[derive(Component, Debug, Clone, PartialEq)]
 pub struct Board {
     /// The length of the x & y axis
     pub size: u8,
+    /// The length of a tile side in pixels, see [`Board::fit_to_window`].
+    pub tile_size: f32,
+    /// Boards share the window side by side, this one is in `column` of `columns`.
+    pub column: u8,
+    pub columns: u8,
+    pub grid: Grid,
 }
 
 impl Board {
-    pub const COLOR: Color = Color::Lcha {
-        lightness: 0.06,
-        chroma: 0.088,
-        hue: 281.0,
-        alpha: 1.,
-    };
-
-    pub const TILE_SIZE: f32 = 40.;
-    pub const TILE_SPACING: f32 = 10.;
-    pub const TILE_COLOR: Color = Color::Lcha {
-        lightness: 0.85,
-        chroma: 0.5,
-        hue: 315.0,
-        alpha: 1.0,
-    };
-    pub const TILE_PLACEHOLDER_COLOR: Color = Color::Lcha {
-        lightness: 0.55,
-        chroma: 0.5,
-        hue: 315.0,
-        alpha: 1.0,
-    };
-
-    fn new(size: u8) -> Self {
-        return Self { size };
-    }
+    /// How long a tile takes to slide at an animation speed of `1`.
+    pub const MOVE_DURATION: Duration = Duration::from_millis(100);
 
-    pub fn total_tiles(&self) -> u8 {
-        return self.size * self.size;
+    pub const DEFAULT_TILE_SIZE: f32 = 40.;
+    pub const MIN_TILE_SIZE: f32 = 10.;
+    /// The gap between tiles, relative to the tile size.
+    pub const TILE_SPACING_RATIO: f32 = 0.25;
+    /// The gap between the layers of a [`Grid::Cube`], relative to the tile size.
+    pub const LAYER_SPACING_RATIO: f32 = 1.;
+    /// The room left at the top of the window for the score header.
+    pub const HEADER_HEIGHT: f32 = 140.;
+    /// The room left around the other sides of the board.
+    pub const WINDOW_MARGIN: f32 = 20.;
+
+    pub fn new(size: u8) -> Self {
+        return Self {
+            size,
+            tile_size: Board::DEFAULT_TILE_SIZE,
+            column: 0,
+            columns: 1,
+            grid: Grid::Square,
+        };
     }
 
-    fn dimensions(&self) -> (u8, u8) {
-        return (self.size, self.size);
+    pub fn tile_spacing(&self) -> f32 {
+        return self.tile_size * Board::TILE_SPACING_RATIO;
     }
 
-    fn physical_size(&self) -> Vec2 {
-        let (width, height) = self.dimensions();
-        return Vec2::new(
-            width as f32 * Board::TILE_SIZE + (width + 1) as f32 * Board::TILE_SPACING,
-            height as f32 * Board::TILE_SIZE + (height + 1) as f32 * Board::TILE_SPACING,
+    /// Picks the largest tile size that fits the board in its column of the window below the
+    /// score header.
+    pub fn fit_to_window(&mut self, window: &Window) {
+        let columns = self.columns as f32;
+        let available = Vec2::new(
+            (window.width() - (columns + 1.) * Board::WINDOW_MARGIN) / columns,
+            window.height() - Board::HEADER_HEIGHT - Board::WINDOW_MARGIN,
         );
+        // The size of the board grows with the size of its tiles
+        let per_tile_size = Board {
+            tile_size: 1.,
+            ..self.clone()
+        }
+        .physical_size();
+
+        self.tile_size = (available / per_tile_size)
+            .min_element()
+            .max(Board::MIN_TILE_SIZE);
     }
 
-    fn origin(&self) -> Vec2 {
-        let size = self.physical_size();
+    /// The camera offset that centers the board in the window area below the score header.
+    pub fn camera_offset() -> Vec3 {
+        return Vec3::new(0., (Board::HEADER_HEIGHT - Board::WINDOW_MARGIN) / 2., 0.);
+    }
+
+    pub fn total_tiles(&self) -> u8 {
+        return self.cells().count() as u8;
+    }
 
-        return Vec2::new(
-            -size.x / 2. + Board::TILE_SIZE / 2.,
-            -size.y / 2. + Board::TILE_SIZE / 2.,
+    /// Where the center of the board is in the world, the columns are centered in the window.
+    pub fn translation(&self) -> Vec3 {
+        let offset = self.column as f32 - (self.columns - 1) as f32 / 2.;
+        return Vec3::new(
+            offset * (self.physical_size().x + Board::WINDOW_MARGIN),
+            0.,
+            0.,
         );
     }
 
-    fn cell_position_to_physical(&self, x: u8, y: u8) -> Vec2 {
-        // Offset to the bottom left corner of the board
-        let offset = -self.physical_size() / 2. + Board::TILE_SIZE / 2.;
+    pub fn physical_size(&self) -> Vec2 {
+        let size = self.size as f32;
+        return match self.grid {
+            Grid::Square | Grid::Torus => {
+                Vec2::splat(size * self.tile_size + (size + 1.) * self.tile_spacing())
+            }
+            Grid::Hex => {
+                // From the centers of the outermost cells, see `cell_position_to_physical`
+                let span = (size - 1.) * (self.tile_size + self.tile_spacing());
+                let border = self.tile_size + 2. * self.tile_spacing();
+                Vec2::new(span * 3f32.sqrt() / 2. + border, span + border)
+            }
+            Grid::Cube => {
+                let layer = self.layer().physical_size();
+                Vec2::new(
+                    size * layer.x + (size - 1.) * self.tile_size * Board::LAYER_SPACING_RATIO,
+                    layer.y,
+                )
+            }
+        };
+    }
 
-        return Vec2::new(
-            offset.x + x as f32 * Board::TILE_SIZE + (x + 1) as f32 * Board::TILE_SPACING,
-            offset.y + y as f32 * Board::TILE_SIZE + (y + 1) as f32 * Board::TILE_SPACING,
-        );
+    /// A layer of a [`Grid::Cube`], laid out like a square board.
+    fn layer(&self) -> Board {
+        return Board {
+            grid: Grid::Square,
+            ..self.clone()
+        };
+    }
+
+    fn cell_position_to_physical(&self, position: tile::Position) -> Vec2 {
+        let tile::Position { x, y, z } = position;
+        match self.grid {
+            Grid::Square | Grid::Torus => {
+                // Offset to the bottom left corner of the board
+                let offset = -self.physical_size() / 2. + self.tile_size / 2.;
+
+                return Vec2::new(
+                    offset.x + x as f32 * self.tile_size + (x + 1) as f32 * self.tile_spacing(),
+                    offset.y + y as f32 * self.tile_size + (y + 1) as f32 * self.tile_spacing(),
+                );
+            }
+            Grid::Hex => {
+                // Columns of flat topped cells, the six neighbours of a cell are a step away
+                let step = self.tile_size + self.tile_spacing();
+                let center = (self.size / 2) as f32;
+                let q = x as f32 - center;
+                let r = y as f32 - center;
+
+                return Vec2::new(q * step * 3f32.sqrt() / 2., (r + q / 2.) * step);
+            }
+            Grid::Cube => {
+                // The first layer on the left
+                let layer = self.layer();
+                let step = layer.physical_size().x + self.tile_size * Board::LAYER_SPACING_RATIO;
+                let offset = z as f32 - (self.size - 1) as f32 / 2.;
+
+                return layer.cell_position_to_physical(tile::Position { z: 0, ..position })
+                    + Vec2::new(offset * step, 0.);
+            }
+        }
     }
 
-    fn iter_dimensions(&self) -> itertools::Product<ops::Range<u8>, ops::Range<u8>> {
-        let (width, height) = self.dimensions();
-        return (0..width).cartesian_product(0..height);
+    fn cells(&self) -> impl Iterator<Item = tile::Position> {
+        return self.grid.cells(self.size);
     }
 
-    pub fn spawn(mut commands: Commands) {
-        let board = Board { size: 4 };
+    pub fn spawn(
+        mut commands: Commands,
+        settings: Res<Settings>,
+        windows: Query<&Window, With<PrimaryWindow>>,
+    ) {
+        let mut board = Board::new(settings.board_size);
+        if let Ok(window) = windows.get_single() {
+            board.fit_to_window(window);
+        }
+        board.spawn_board(&mut commands);
+    }
 
-        let offset = -board.physical_size() / 2. + Board::TILE_SIZE / 2.;
+    /// Scales the boards, their cells and tiles to the window whenever it gets resized.
+    pub fn sys_fit_to_window(
+        mut commands: Commands,
+        mut resized_reader: EventReader<WindowResized>,
+        windows: Query<&Window, With<PrimaryWindow>>,
+        mut query_board: Query<(&mut Board, &mut Sprite, &mut Transform, &Children)>,
+        mut cells: Query<(&EmptyCell, &mut Transform, &mut Sprite), Without<Board>>,
+        mut tiles: Query<
+            (
+                Entity,
+                &tile::Position,
+                &mut Transform,
+                &mut Sprite,
+                Option<&Children>,
+            ),
+            (Without<EmptyCell>, Without<Board>),
+        >,
+        mut texts: Query<&mut Text, With<tile::TileText>>,
+    ) {
+        if resized_reader.iter().count() == 0 {
+            return;
+        }
+        let Ok(window) = windows.get_single() else {
+            return;
+        };
 
-        commands
+        for (mut board, mut board_sprite, mut board_transform, board_children) in
+            query_board.iter_mut()
+        {
+            let old_tile_size = board.tile_size;
+            board.fit_to_window(window);
+            if board.tile_size == old_tile_size {
+                continue;
+            }
+            board_sprite.custom_size = Some(board.physical_size());
+            board_transform.translation = board.translation();
+
+            let tile_size = Vec2::splat(board.tile_size);
+            for &child in board_children.iter() {
+                if let Ok((cell, mut transform, mut sprite)) = cells.get_mut(child) {
+                    let position = board.cell_position_to_physical(cell.0);
+                    transform.translation.x = position.x;
+                    transform.translation.y = position.y;
+                    sprite.custom_size = Some(tile_size);
+                }
+
+                let Ok((entity, position, mut transform, mut sprite, children)) =
+                    tiles.get_mut(child)
+                else {
+                    continue;
+                };
+                // Jump to the new layout instead of finishing a slide towards the old one
+                commands
+                    .entity(entity)
+                    .remove::<EasingComponent<Transform>>();
+
+                let physical = board.cell_position_to_physical(*position);
+                transform.translation.x = physical.x;
+                transform.translation.y = physical.y;
+                sprite.custom_size = Some(tile_size);
+
+                let text_entity = children.and_then(|children| children.first());
+                if let Some(mut text) = text_entity.and_then(|&e| texts.get_mut(e).ok()) {
+                    text.sections[0].style.font_size = board.tile_size;
+                }
+            }
+        }
+    }
+
+    /// Keeps the `boards` when there are `columns` of them with this size and grid, otherwise
+    /// replaces them with as many empty boards fit to the window.
+    ///
+    /// Returns the boards in the order of their columns.
+    pub fn lay_out(
+        commands: &mut Commands,
+        boards: impl Iterator<Item = (Entity, Board)>,
+        size: u8,
+        grid: Grid,
+        columns: u8,
+        window: Option<&Window>,
+    ) -> Vec<(Entity, Board)> {
+        let mut boards = boards.collect::<Vec<_>>();
+        boards.sort_by_key(|(_, board)| board.column);
+
+        let fits = boards.len() == columns as usize
+            && boards.iter().all(|(_, board)| {
+                board.size == size && board.grid == grid && board.columns == columns
+            });
+        if fits {
+            return boards;
+        }
+
+        for (entity, _) in boards {
+            commands.entity(entity).despawn_recursive();
+        }
+
+        return (0..columns)
+            .map(|column| {
+                let mut board = Board {
+                    column,
+                    columns,
+                    grid,
+                    ..Board::new(size)
+                };
+                if let Some(window) = window {
+                    board.fit_to_window(window);
+                }
+                (board.clone().spawn_board(commands), board)
+            })
+            .collect();
+    }
+
+    /// The cell under a point in world space, `None` outside of the cells.
+    pub fn cell_at(&self, point: Vec2) -> Option<tile::Position> {
+        let local = point - self.translation().truncate();
+
+        return self.cells().find(|position| {
+            let center = self.cell_position_to_physical(*position);
+            (local - center).abs().max_element() <= self.tile_size / 2.
+        });
+    }
+
+    /// Spawns the background and the empty cells of this board.
+    pub fn spawn_board(self, commands: &mut Commands) -> Entity {
+        let board = self;
+
+        return commands
             .spawn(SpriteBundle {
                 sprite: Sprite {
                     custom_size: Some(board.physical_size()),
-                    color: Board::COLOR,
                     ..default()
                 },
+                transform: Transform::from_translation(board.translation()),
                 ..default()
             })
             .with_children(|builder| {
-                for (x, y) in board.iter_dimensions() {
-                    let pos = board.cell_position_to_physical(x, y);
+                for position in board.cells() {
+                    let pos = board.cell_position_to_physical(position);
 
-                    builder.spawn(SpriteBundle {
-                        sprite: Sprite {
-                            color: Board::TILE_PLACEHOLDER_COLOR,
-                            custom_size: Some(Vec2::new(Board::TILE_SIZE, Board::TILE_SIZE)),
+                    builder.spawn((
+                        SpriteBundle {
+                            sprite: Sprite {
+                                custom_size: Some(Vec2::splat(board.tile_size)),
+                                ..default()
+                            },
+                            transform: Transform::from_xyz(pos.x, pos.y, 1.),
                             ..default()
                         },
-                        transform: Transform::from_xyz(pos.x, pos.y, 1.),
-                        ..default()
-                    });
+                        EmptyCell(position),
+                    ));
                 }
             })
-            .insert(board);
+            .insert(board)
+            .id();
     }
 
+    /// Spawns `amount` tiles on random free cells of the board `entity`, `special_probability`
+    /// is the chance of each one to be of a special [`tile::Kind`].
     pub fn spawn_tiles(
         &self,
         commands: &mut Commands,
-        font_spec: &Res<FontSpec>,
-        maybe_tiles: Option<&Query<&tile::Position>>,
+        entity: Entity,
+        font_spec: &FontSpec,
+        occupied: &[tile::Position],
         amount: usize,
-    ) {
-        let mut rng = rand::thread_rng();
+        rule: MergeRule,
+        four_probability: f32,
+        special_probability: f32,
+        rng: &mut impl Rng,
+    ) -> Vec<TileSpawnedEvent> {
         let new_tiles: Vec<tile::Position> = self
-            .iter_dimensions()
-            .map(|(x, y)| tile::Position { x, y })
-            .filter(|&pos| match maybe_tiles {
-                Some(tiles) => tiles
-                    .iter()
-                    .find(|&&occupied_pos| occupied_pos == pos)
-                    .is_none(),
-                None => true,
+            .cells()
+            .filter(|pos| !occupied.contains(pos))
+            .choose_multiple(rng, amount);
+
+        return new_tiles
+            .into_iter()
+            .map(|position| {
+                let mut points = rule.spawn_points(rng, four_probability);
+                // Only drawn when special tiles can spawn, the seeded games of the other modes
+                // keep their spawns
+                if special_probability > 0. && rng.gen::<f32>() < special_probability {
+                    let (kind, _) = tile::Kind::SPAWN_WEIGHTS
+                        .choose_weighted(rng, |&(_, weight)| weight)
+                        .expect("The spawn weights are positive");
+                    points = tile::Points {
+                        value: if *kind == tile::Kind::Negative {
+                            points.value
+                        } else {
+                            0
+                        },
+                        kind: *kind,
+                    };
+                }
+
+                TileSpawnedEvent {
+                    entity: self.spawn_tile(commands, entity, font_spec, position, points),
+                    board: entity,
+                    position,
+                    points,
+                }
             })
-            .choose_multiple(&mut rng, amount);
+            .collect();
+    }
 
-        for pos in new_tiles.iter() {
-            let render_pos = self.cell_position_to_physical(pos.x, pos.y);
+    pub fn spawn_blocker(
+        &self,
+        commands: &mut Commands,
+        entity: Entity,
+        position: tile::Position,
+    ) -> Entity {
+        let render_pos = self.cell_position_to_physical(position);
 
-            commands
-                .spawn(SpriteBundle {
+        let blocker = commands
+            .spawn((
+                SpriteBundle {
                     sprite: Sprite {
-                        color: Board::TILE_COLOR,
-                        custom_size: Some(Vec2::new(Board::TILE_SIZE, Board::TILE_SIZE)),
+                        custom_size: Some(Vec2::splat(self.tile_size)),
                         ..default()
                     },
                     transform: Transform::from_xyz(render_pos.x, render_pos.y, 2.),
                     ..default()
-                })
-                .with_children(|child_builder| {
-                    child_builder
-                        .spawn(Text2dBundle {
-                            text: Text::from_section(
-                                "",
-                                TextStyle {
-                                    font_size: Board::TILE_SIZE,
-                                    color: Color::BLACK,
-                                    font: font_spec.family.clone(),
-                                    ..default()
-                                },
-                            )
-                            .with_alignment(TextAlignment::Center),
-                            transform: Transform::from_xyz(0., 0., 1.),
-                            ..default()
-                        })
-                        .insert(tile::TileText);
-                })
-                .insert(tile::Points { value: 2 })
-                .insert(pos.clone());
-        }
+                },
+                position,
+                tile::Blocker,
+            ))
+            .id();
+        commands.entity(entity).add_child(blocker);
+        return blocker;
+    }
+
+    /// Spawns a tile as a child of the board `entity`.
+    pub fn spawn_tile(
+        &self,
+        commands: &mut Commands,
+        entity: Entity,
+        font_spec: &FontSpec,
+        position: tile::Position,
+        points: tile::Points,
+    ) -> Entity {
+        let render_pos = self.cell_position_to_physical(position);
+
+        let tile = commands
+            .spawn(SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(Vec2::splat(self.tile_size)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(render_pos.x, render_pos.y, 2.),
+                ..default()
+            })
+            .with_children(|child_builder| {
+                child_builder
+                    .spawn(Text2dBundle {
+                        text: Text::from_section(
+                            "",
+                            TextStyle {
+                                font_size: self.tile_size,
+                                color: Color::NONE,
+                                font: font_spec.family.clone(),
+                            },
+                        )
+                        .with_alignment(TextAlignment::Center),
+                        transform: Transform::from_xyz(0., 0., 1.),
+                        ..default()
+                    })
+                    .insert(tile::TileText);
+            })
+            .insert(points)
+            .insert(position)
+            .id();
+        commands.entity(entity).add_child(tile);
+        return tile;
     }
 
     pub fn render_tile_points(
@@ -182,20 +473,97 @@ impl Board {
                     .sections
                     .first_mut()
                     .expect("expect first section to be accessible as mutable");
-                text_section.value = points.value.to_string();
+                text_section.value = points.label();
+            }
+        }
+    }
+
+    /// Colors the board, its cells and tiles with the [`CurrentTheme`] and rounds their corners.
+    ///
+    /// Runs in the `PostUpdate` set so tiles spawned this frame are themed before being drawn.
+    pub fn render_theme(
+        theme: Res<CurrentTheme>,
+        mut rounded_rects: ResMut<RoundedRects>,
+        mut images: ResMut<Assets<Image>>,
+        mut sprites: Query<
+            (
+                &mut Sprite,
+                &mut Handle<Image>,
+                Option<&tile::Points>,
+                Option<&Board>,
+                Option<&tile::Blocker>,
+                Option<&Children>,
+                Option<&Parent>,
+            ),
+            Or<(
+                With<Board>,
+                With<EmptyCell>,
+                With<tile::Points>,
+                With<tile::Blocker>,
+            )>,
+        >,
+        query_board: Query<&Board>,
+        mut texts: Query<&mut Text, With<tile::TileText>>,
+    ) {
+        for (mut sprite, mut texture, points, board, blocker, children, parent) in
+            sprites.iter_mut()
+        {
+            // Cells and tiles are sized by the board they are on
+            let Some(tile_size) = board
+                .or_else(|| parent.and_then(|parent| query_board.get(parent.get()).ok()))
+                .map(|board| board.tile_size)
+            else {
+                continue;
+            };
+
+            let (color, radius) = match (points, board) {
+                _ if blocker.is_some() => (theme.blocker.0, theme.tile_radius),
+                (Some(points), _) => {
+                    let style = theme.points_style(*points);
+
+                    let text_entity = children.and_then(|children| children.first());
+                    if let Some(mut text) = text_entity.and_then(|&e| texts.get_mut(e).ok()) {
+                        if text.sections[0].style.color != style.text.0 {
+                            text.sections[0].style.color = style.text.0;
+                        }
+                    }
+
+                    (style.background.0, theme.tile_radius)
+                }
+                (None, Some(_)) => (theme.board.0, theme.board_radius),
+                (None, None) => (theme.empty_cell.0, theme.tile_radius),
+            };
+
+            if sprite.color != color {
+                sprite.color = color;
+            }
+
+            let size = sprite.custom_size.unwrap_or(Vec2::ONE);
+            let rounded = rounded_rects.get(&mut images, size, radius * tile_size);
+            if *texture != rounded {
+                *texture = rounded;
             }
         }
+
+        // Every themed sprite asked for its texture above
+        rounded_rects.evict_unused();
     }
 
     pub fn render_tiles(
         mut commands: Commands,
-        mut tiles: Query<(Entity, &mut Transform, &tile::Position), Changed<tile::Position>>,
+        mut tiles: Query<
+            (Entity, &mut Transform, &tile::Position, &Parent),
+            Changed<tile::Position>,
+        >,
         query_board: Query<&Board>,
+        settings: Res<Settings>,
     ) {
-        let board = query_board.single();
+        for (entity, transform, position, parent) in tiles.iter_mut() {
+            let Ok(board) = query_board.get(parent.get()) else {
+                continue;
+            };
 
-        for (entity, transform, position) in tiles.iter_mut() {
-            let physical_position = board.cell_position_to_physical(position.x, position.y);
+            let physical_position = board.cell_position_to_physical(*position);
 
             commands.entity(entity).insert(transform.ease_to(
                 Transform::from_xyz(
@@ -205,7 +573,7 @@ impl Board {
                 ),
                 EaseFunction::QuadraticInOut,
                 EasingType::Once {
-                    duration: std::time::Duration::from_millis(100),
+                    duration: Board::MOVE_DURATION.div_f32(settings.animation_speed),
                 },
             ));
         }
@@ -214,181 +582,1429 @@ impl Board {
     pub fn on_new_tile_handler(
         mut event_reader: EventReader<NewTileEvent>,
         mut commands: Commands,
-        query_board: Query<&Board>,
-        tiles: Query<&tile::Position>,
+        mut query_board: Query<(&Board, &mut BoardGame)>,
+        tiles: Query<(&tile::Position, &Parent)>,
+        font_spec: Res<FontSpec>,
+        settings: Res<Settings>,
+        game_mode: Res<GameMode>,
+        mut spawned_writer: EventWriter<TileSpawnedEvent>,
+    ) {
+        for event in event_reader.iter() {
+            // The levels spawn their own tiles, the spectators the tiles of the watched game
+            if game_mode.is_level() || matches!(*game_mode, GameMode::Spectate { .. }) {
+                continue;
+            }
+            let Ok((board, mut board_game)) = query_board.get_mut(event.board) else {
+                continue;
+            };
+
+            spawned_writer.send_batch(board.spawn_tiles(
+                &mut commands,
+                event.board,
+                &font_spec,
+                &Board::tiles_of(event.board, &tiles),
+                1,
+                game_mode.merge_rule(),
+                game_mode.spawn_four_probability(&settings),
+                game_mode.special_tile_probability(),
+                &mut board_game.rng,
+            ));
+        }
+    }
+
+    /// The positions of the tiles and blockers on the board `entity`.
+    pub fn tiles_of(
+        entity: Entity,
+        tiles: &Query<(&tile::Position, &Parent)>,
+    ) -> Vec<tile::Position> {
+        return tiles
+            .iter()
+            .filter(|(_, parent)| parent.get() == entity)
+            .map(|(&position, _)| position)
+            .collect();
+    }
+
+    /// Shows the caption and the score of each board that has a [`Caption`] above it.
+    pub fn sys_render_captions(
+        mut commands: Commands,
+        query_board: Query<(Entity, &Board, &BoardGame, &Caption, Option<&Children>)>,
+        mut labels: Query<(&mut Text, &mut Transform), With<CaptionLabel>>,
+        label_parents: Query<(Entity, &Parent), With<CaptionLabel>>,
         font_spec: Res<FontSpec>,
+        theme: Res<CurrentTheme>,
     ) {
-        let board = query_board.single();
+        const FONT_SIZE: f32 = 24.;
 
-        for _event in event_reader.iter() {
-            board.spawn_tiles(&mut commands, &font_spec, Some(&tiles), 1);
+        // The next game may keep the board without its caption
+        for (label, parent) in label_parents.iter() {
+            if !query_board.contains(parent.get()) {
+                commands.entity(parent.get()).remove_children(&[label]);
+                commands.entity(label).despawn_recursive();
+            }
+        }
+
+        for (entity, board, board_game, caption, children) in query_board.iter() {
+            let mut value = format!("{}: {}", caption.name, board_game.score);
+            if board_game.over {
+                value.push_str(" - game over");
+            } else if !caption.note.is_empty() {
+                value.push_str(&format!(" - {}", caption.note));
+            }
+            let translation = Vec3::new(0., board.physical_size().y / 2. + FONT_SIZE, 3.);
+
+            let label = children
+                .into_iter()
+                .flatten()
+                .find(|&&child| labels.contains(child));
+            let Some(&label) = label else {
+                let label = commands
+                    .spawn((
+                        Text2dBundle {
+                            text: Text::from_section(
+                                value,
+                                TextStyle {
+                                    font: font_spec.family.clone(),
+                                    font_size: FONT_SIZE,
+                                    color: theme.text.0,
+                                },
+                            )
+                            .with_alignment(TextAlignment::Center),
+                            transform: Transform::from_translation(translation),
+                            ..default()
+                        },
+                        CaptionLabel,
+                    ))
+                    .id();
+                commands.entity(entity).add_child(label);
+                continue;
+            };
+
+            let (mut text, mut transform) = labels.get_mut(label).expect("checked above");
+            if text.sections[0].value != value {
+                text.sections[0].value = value;
+            }
+            text.sections[0].style.color = theme.text.0;
+            transform.translation = translation;
         }
     }
 }
 
+/// Names a board that is played side by side with others.
+#[derive(Component, Debug, Clone, Default)]
+pub struct Caption {
+    pub name: String,
+    /// Shown after the score while the game on the board goes on.
+    pub note: String,
+}
+
+impl Caption {
+    pub fn new(name: String) -> Self {
+        return Self {
+            name,
+            note: String::new(),
+        };
+    }
+}
+
+/// The text of a [`Caption`] above its board.
+#[derive(Component)]
+pub struct CaptionLabel;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The background sprite of a board cell.
+#[derive(Component)]
+pub struct EmptyCell(pub tile::Position);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BoardShiftDirection {
     Left,
     Right,
     Up,
     Down,
+    /// The diagonals of the [`Grid::Hex`] boards.
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+    /// Through the layers of the [`Grid::Cube`] boards, towards the last one.
+    In,
+    Out,
 }
 impl BoardShiftDirection {
-    /// TODO: make it sort direction dependent
-    fn sort_tiles(&self, a: &tile::Position, b: &tile::Position) -> Ordering {
-        match self {
-            BoardShiftDirection::Left => match Ord::cmp(&a.y, &b.y) {
-                Ordering::Equal => Ord::cmp(&a.x, &b.x),
-                ordering => ordering,
-            },
-            BoardShiftDirection::Right => match Ord::cmp(&b.y, &a.y) {
-                Ordering::Equal => Ord::cmp(&b.x, &a.x),
-                ordering => ordering,
-            },
-            BoardShiftDirection::Up => match Ord::cmp(&b.x, &a.x) {
-                Ordering::Equal => Ord::cmp(&b.y, &a.y),
-                ordering => ordering,
-            },
-            BoardShiftDirection::Down => match Ord::cmp(&a.x, &b.x) {
-                Ordering::Equal => Ord::cmp(&a.y, &b.y),
-                ordering => ordering,
-            },
-        }
+    /// The directions of the [`Grid::Square`] boards.
+    pub const ALL: [BoardShiftDirection; 4] = [
+        BoardShiftDirection::Left,
+        BoardShiftDirection::Right,
+        BoardShiftDirection::Up,
+        BoardShiftDirection::Down,
+    ];
+    pub const HEX: [BoardShiftDirection; 6] = [
+        BoardShiftDirection::UpLeft,
+        BoardShiftDirection::Up,
+        BoardShiftDirection::UpRight,
+        BoardShiftDirection::DownLeft,
+        BoardShiftDirection::Down,
+        BoardShiftDirection::DownRight,
+    ];
+    pub const CUBE: [BoardShiftDirection; 6] = [
+        BoardShiftDirection::Left,
+        BoardShiftDirection::Right,
+        BoardShiftDirection::Up,
+        BoardShiftDirection::Down,
+        BoardShiftDirection::In,
+        BoardShiftDirection::Out,
+    ];
+
+    /// The cell next to `(0, 0, 0)` in this direction, see [`Grid::Hex`] for its diagonals.
+    pub fn step(&self) -> (i8, i8, i8) {
+        return match self {
+            BoardShiftDirection::Left | BoardShiftDirection::DownLeft => (-1, 0, 0),
+            BoardShiftDirection::Right | BoardShiftDirection::UpRight => (1, 0, 0),
+            BoardShiftDirection::Up => (0, 1, 0),
+            BoardShiftDirection::Down => (0, -1, 0),
+            BoardShiftDirection::UpLeft => (-1, 1, 0),
+            BoardShiftDirection::DownRight => (1, -1, 0),
+            BoardShiftDirection::In => (0, 0, 1),
+            BoardShiftDirection::Out => (0, 0, -1),
+        };
+    }
+
+    /// The keys of the [`Grid::Hex`] boards, the six around `S` on a QWERTY keyboard.
+    pub fn from_hex_key(key_code: &KeyCode) -> Option<BoardShiftDirection> {
+        return match key_code {
+            KeyCode::Q => Some(BoardShiftDirection::UpLeft),
+            KeyCode::W => Some(BoardShiftDirection::Up),
+            KeyCode::E => Some(BoardShiftDirection::UpRight),
+            KeyCode::A => Some(BoardShiftDirection::DownLeft),
+            KeyCode::S => Some(BoardShiftDirection::Down),
+            KeyCode::D => Some(BoardShiftDirection::DownRight),
+            _ => None,
+        };
     }
 
-    fn set_position_column(
+    /// The last cell a tile at `position` can slide to, before the edge of the board or
+    /// a blocker, with how many cells away it is.
+    ///
+    /// `None` on a ring, a line of a [`Grid::Torus`] without blockers, see [`Self::ring_fronts`].
+    fn segment_front(
         &self,
+        grid: Grid,
         board_size: u8,
-        position: &mut Mut<tile::Position>,
-        new_column: u8,
-    ) {
-        match self {
-            BoardShiftDirection::Left => position.x = new_column,
-            BoardShiftDirection::Right => position.x = board_size - 1 - new_column,
-            BoardShiftDirection::Up => position.y = board_size - 1 - new_column,
-            BoardShiftDirection::Down => position.y = new_column,
+        position: tile::Position,
+        blockers: &[tile::Position],
+    ) -> Option<(tile::Position, u8)> {
+        let mut front = position;
+        let mut distance = 0;
+        while let Some(next) = grid
+            .neighbour(board_size, front, self.step())
+            .filter(|next| !blockers.contains(next))
+        {
+            if next == position {
+                return None;
+            }
+            front = next;
+            distance += 1;
         }
+        return Some((front, distance));
     }
-    fn get_position_row(&self, position: &tile::Position) -> u8 {
-        match self {
-            BoardShiftDirection::Left | BoardShiftDirection::Right => position.y,
-            BoardShiftDirection::Up | BoardShiftDirection::Down => position.x,
+
+    /// The [`Self::segment_front`] of every tile on the ring of `position`.
+    ///
+    /// Nothing stops the tiles of a ring, so it is cut in front of one of them and packed against
+    /// the edge of the board, like a line that doesn't wrap. The cut keeps as many merges as it
+    /// can, across the edge if need be, then the tile closest to the edge in front.
+    fn ring_fronts(
+        &self,
+        grid: Grid,
+        board_size: u8,
+        rule: MergeRule,
+        position: tile::Position,
+        points: &HashMap<tile::Position, tile::Points>,
+    ) -> Vec<(tile::Position, (tile::Position, u8))> {
+        let (dx, dy, dz) = self.step();
+        // The tiles of the ring, each one behind the previous one
+        let cells = std::iter::successors(Some(position), |&cell| {
+            grid.neighbour(board_size, cell, (-dx, -dy, -dz))
+                .filter(|&next| next != position)
+        })
+        .filter(|cell| points.contains_key(cell))
+        .collect::<Vec<_>>();
+        let from_leader = |leader: usize| {
+            return (0..cells.len())
+                .map(|offset| cells[(leader + offset) % cells.len()])
+                .collect::<Vec<_>>();
+        };
+
+        // Groups the tiles the same way as `Self::shift`
+        let merges = |leader: usize| {
+            let points = from_leader(leader)
+                .iter()
+                .map(|cell| points[cell])
+                .collect::<Vec<_>>();
+            let mut merges = 0;
+            let mut index = 0;
+            while index < points.len() {
+                let group = &points[index..(index + rule.group_size()).min(points.len())];
+                if rule.merge_tiles(group).is_some() {
+                    merges += 1;
+                    index += group.len();
+                } else {
+                    index += 1;
+                }
+            }
+            return merges;
+        };
+        let edge = |cell: tile::Position| {
+            return self
+                .segment_front(Grid::Square, board_size, cell, &[])
+                .expect("The lines of a square board end at its edge");
+        };
+
+        let leader = (0..cells.len())
+            .min_by_key(|&leader| (Reverse(merges(leader)), edge(cells[leader]).1))
+            .unwrap_or_default();
+        let (front, _) = edge(position);
+        return from_leader(leader)
+            .into_iter()
+            .enumerate()
+            .map(|(distance, cell)| (cell, (front, distance as u8)))
+            .collect();
+    }
+
+    /// Slides and merges the given tiles towards this direction.
+    ///
+    /// This holds the actual rules of the game and knows nothing about the ECS, `rule` decides
+    /// which tiles merge. `T` identifies a tile (an `Entity` in the game, a cell index in the [`crate::env`]).
+    /// The `blockers` split the lines of the `grid` in segments that tiles can't leave.
+    pub fn shift<T: Copy>(
+        &self,
+        grid: Grid,
+        board_size: u8,
+        rule: MergeRule,
+        tiles: &[(T, tile::Position, tile::Points)],
+        blockers: &[tile::Position],
+    ) -> ShiftResult<T> {
+        let mut result = ShiftResult {
+            moves: Vec::with_capacity(tiles.len()),
+            merges: Vec::new(),
+            destroyed: Vec::new(),
+        };
+        let (dx, dy, dz) = self.step();
+
+        let points = tiles
+            .iter()
+            .map(|&(_, position, points)| (position, points))
+            .collect::<HashMap<_, _>>();
+        let mut fronts = HashMap::new();
+        for &(_, position, _) in tiles {
+            if fronts.contains_key(&position) {
+                continue;
+            }
+            match self.segment_front(grid, board_size, position, blockers) {
+                Some(front) => {
+                    fronts.insert(position, front);
+                }
+                None => fronts.extend(self.ring_fronts(grid, board_size, rule, position, &points)),
+            }
+        }
+
+        // The tiles of a segment next to each other, from its front
+        let ordered_tiles = tiles
+            .iter()
+            .map(|tile| (tile, fronts[&tile.1]))
+            .sorted_by_key(|&(_, (front, distance))| (front.z, front.y, front.x, distance))
+            .collect::<Vec<_>>();
+
+        // Counted from the front of the segment
+        let mut column: u8 = 0;
+        let mut current_segment = None;
+        let mut explosions = Vec::new();
+        let mut index = 0;
+        while let Some(&(&(id, from, _), (front, _))) = ordered_tiles.get(index) {
+            if current_segment != Some(front) {
+                // First tile of a line or behind a blocker
+                current_segment = Some(front);
+                column = 0;
+            }
+
+            let to = (0..column).fold(front, |to, _| {
+                grid.neighbour(board_size, to, (-dx, -dy, -dz))
+                    .expect("A segment has a cell for each of its tiles")
+            });
+
+            // This tile and the ones sliding in right behind it
+            let group = &ordered_tiles[index..(index + rule.group_size()).min(ordered_tiles.len())];
+            let merged = if group
+                .iter()
+                .all(|&(_, (tile_front, _))| tile_front == front)
+            {
+                let points = group.iter().map(|(tile, _)| tile.2).collect::<Vec<_>>();
+                rule.merge_tiles(&points)
+            } else {
+                None
+            };
+
+            match merged {
+                None => {
+                    result.moves.push(TileMove { id, from, to });
+                    column += 1;
+                    index += 1;
+                }
+                Some(Merged::Tile(points)) => {
+                    result.moves.push(TileMove { id, from, to });
+                    result.merges.push(TileMerge {
+                        id,
+                        absorbed: group[1..]
+                            .iter()
+                            .map(|(tile, _)| (tile.0, tile.1))
+                            .collect(),
+                        position: to,
+                        points,
+                    });
+                    column += 1;
+                    index += group.len();
+                }
+                // The cell stays free for the tiles behind
+                Some(merged @ (Merged::Cancelled | Merged::Exploded)) => {
+                    result
+                        .destroyed
+                        .extend(group.iter().map(|(tile, _)| (tile.0, tile.1)));
+                    if merged == Merged::Exploded {
+                        explosions.push(to);
+                    }
+                    index += group.len();
+                }
+            }
         }
+
+        // Once every tile has landed, so that the ones sliding next to a bomb are caught too
+        let blasted = explosions
+            .iter()
+            .flat_map(|&cell| {
+                grid.directions()
+                    .iter()
+                    .filter_map(move |direction| grid.neighbour(board_size, cell, direction.step()))
+            })
+            .collect::<Vec<_>>();
+        let (caught, moves) = result
+            .moves
+            .into_iter()
+            .partition::<Vec<_>, _>(|tile_move| blasted.contains(&tile_move.to));
+        result.moves = moves;
+        for tile_move in caught {
+            result.destroyed.push((tile_move.id, tile_move.from));
+            let merge = result
+                .merges
+                .iter()
+                .position(|merge| merge.position == tile_move.to);
+            if let Some(merge) = merge {
+                result
+                    .destroyed
+                    .extend(result.merges.remove(merge).absorbed);
+            }
+        }
+
+        return result;
     }
 
+    /// Shifts every board that one of the pressed keys is bound to, whose [`Bot`] picked
+    /// a direction or whose [`Remote`] player moved.
     pub fn sys_handle_board_shift_on_keypress(
         mut commands: Commands,
         input: Res<Input<KeyCode>>,
-        mut tiles: Query<(Entity, &mut tile::Position, &mut tile::Points)>,
-        query_board: Query<&Board>,
+        settings: Res<Settings>,
+        mut tiles: Query<(Entity, &mut tile::Position, &mut tile::Points, &Parent)>,
+        blockers: Query<(&tile::Position, &Parent), (With<tile::Blocker>, Without<tile::Points>)>,
+        mut query_board: Query<(
+            Entity,
+            &Board,
+            &mut BoardGame,
+            Option<&Contender>,
+            Option<&mut Bot>,
+            Option<&mut Remote>,
+        )>,
         mut game: ResMut<Game>,
+        game_mode: Res<GameMode>,
+        mut history: ResMut<History>,
         mut event_writer: EventWriter<NewTileEvent>,
+        mut shifted_writer: EventWriter<BoardShiftedEvent>,
+        mut moved_writer: EventWriter<TileMovedEvent>,
+        mut merged_writer: EventWriter<TilesMergedEvent>,
+        mut rejected_writer: EventWriter<MoveRejectedEvent>,
+        mut won_writer: EventWriter<BoardWonEvent>,
     ) {
-        let board = query_board.single();
+        for (board_entity, board, mut board_game, contender, bot, remote) in query_board.iter_mut()
+        {
+            // The history and the best score follow a single player
+            let solo = contender.is_none() && bot.is_none() && remote.is_none();
+            let direction = match (contender, bot, remote) {
+                (_, Some(mut bot), _) => bot.next.take(),
+                (_, _, Some(mut remote)) => remote.moves.pop_front(),
+                (Some(contender), None, None) => input
+                    .get_just_pressed()
+                    .find_map(|key_code| contender.controls.direction(key_code)),
+                (None, None, None) => input.get_just_pressed().find_map(|key_code| {
+                    board.grid.key_direction(key_code, &settings.key_bindings)
+                }),
+            };
+            let Some(direction) = direction else {
+                continue;
+            };
+            if !board.grid.directions().contains(&direction) {
+                continue;
+            }
+            if board_game.over {
+                continue;
+            }
 
-        let Some(direction) = input
-            .get_just_pressed()
-            .find_map(|key_code| BoardShiftDirection::try_from(key_code).ok())
-        else {
-            return;
-        };
+            let snapshot = tiles
+                .iter()
+                .filter(|(_, _, _, parent)| parent.get() == board_entity)
+                .map(|(entity, &position, &points, _)| (entity, position, points))
+                .collect::<Vec<_>>();
+            let blockers = blockers
+                .iter()
+                .filter(|(_, parent)| parent.get() == board_entity)
+                .map(|(&position, _)| position)
+                .collect::<Vec<_>>();
+            let result = direction.shift(
+                board.grid,
+                board.size,
+                game_mode.merge_rule(),
+                &snapshot,
+                &blockers,
+            );
 
-        let original_tile_info = tiles
-            .iter()
-            .map(|(_, &postion, &points)| (postion.clone(), points.clone()))
-            .collect::<Vec<_>>();
+            if result.has_changed() {
+                board_game.moves += 1;
+                if solo {
+                    history.push(
+                        Snapshot {
+                            tiles: snapshot
+                                .iter()
+                                .map(|&(_, position, points)| (position, points))
+                                .collect(),
+                            score: board_game.score,
+                        },
+                        game_mode.undo_limit(&settings),
+                    );
+                }
+            }
 
-        let mut ordered_tiles = tiles
-            .iter_mut()
-            .sorted_by(|a, b| direction.sort_tiles(&a.1, &b.1))
-            .peekable();
+            for tile_move in result.moves.iter() {
+                let (_, mut position, _, _) = tiles
+                    .get_mut(tile_move.id)
+                    .expect("Shifted tiles come from the query");
+                *position = tile_move.to;
 
-        // Column is shift direction dependent
-        let mut column: u8 = 0;
-        while let Some(mut tile) = ordered_tiles.next() {
-            direction.set_position_column(board.size, &mut tile.1, column);
+                if tile_move.from != tile_move.to {
+                    moved_writer.send(TileMovedEvent {
+                        entity: tile_move.id,
+                        from: tile_move.from,
+                        to: tile_move.to,
+                        direction,
+                    });
+                }
+            }
 
-            let Some(next_tile) = ordered_tiles.peek() else {
-                continue;
-            };
+            for &(destroyed, _) in result.destroyed.iter() {
+                commands.entity(destroyed).despawn_recursive();
+            }
 
-            if direction.get_position_row(&tile.1) != direction.get_position_row(&next_tile.1) {
-                // Different rows, don't merge
-                column = 0;
-            } else if tile.2.value != next_tile.2.value {
-                // Different values, don't merge
-                column += 1;
-            } else {
-                // Merge
-                let real_next_tile = ordered_tiles
-                    .next()
-                    .expect("A peekable tile should always exist when calling .next()");
-
-                // Update the values
-                let merged_value = tile.2.value + real_next_tile.2.value;
-                tile.2.value = merged_value;
-                game.score += merged_value;
-                if game.score > game.best_score {
-                    game.best_score = game.score;
+            for merge in result.merges.iter() {
+                let (_, _, mut points, _) = tiles
+                    .get_mut(merge.id)
+                    .expect("Merged tiles come from the query");
+                *points = merge.points;
+
+                for &(absorbed, _) in merge.absorbed.iter() {
+                    commands.entity(absorbed).despawn_recursive();
                 }
 
-                commands.entity(real_next_tile.0).despawn_recursive();
+                merged_writer.send(TilesMergedEvent {
+                    entity: merge.id,
+                    board: board_entity,
+                    absorbed: merge.absorbed.clone(),
+                    position: merge.position,
+                    points: merge.points,
+                    score_gained: merge.points.number().unwrap_or(0),
+                });
+            }
 
-                if let Some(future) = ordered_tiles.peek() {
-                    if direction.get_position_row(&tile.1) != direction.get_position_row(&future.1)
-                    {
-                        // Reset if it's on another row
-                        column = 0;
-                    } else {
-                        column = column + 1;
-                    }
-                }
+            let winning_value = game_mode.merge_rule().winning_value();
+            if !board_game.won
+                && result
+                    .merges
+                    .iter()
+                    .any(|merge| merge.points.number() >= Some(winning_value))
+            {
+                board_game.won = true;
+                won_writer.send(BoardWonEvent {
+                    board: board_entity,
+                });
             }
-        }
 
-        // No point in checking for different length because the tile gets despawned later on.
-        if original_tile_info
-            .iter()
-            .zip(tiles.iter())
-            .any(|(original, updated)| original.0 != *updated.1 || original.1 != *updated.2)
-        {
-            // If a tile has moved / merged create a new tile
-            event_writer.send(NewTileEvent);
+            board_game.score += result.score();
+            if solo {
+                game.record_score(&game_mode.name(), board_game.score);
+            }
+
+            if result.has_changed() {
+                shifted_writer.send(BoardShiftedEvent {
+                    board: board_entity,
+                    direction,
+                });
+                // If a tile has moved / merged create a new tile
+                event_writer.send(NewTileEvent {
+                    board: board_entity,
+                });
+            } else {
+                rejected_writer.send(MoveRejectedEvent {
+                    board: board_entity,
+                    direction,
+                });
+            }
         }
     }
 }
 
-impl TryFrom<&KeyCode> for BoardShiftDirection {
-    type Error = ();
+/// The outcome of [`BoardShiftDirection::shift`].
+#[derive(Debug, Clone)]
+pub struct ShiftResult<T> {
+    /// The new position of every tile that is still on the board after the shift,
+    /// including the ones that didn't move.
+    pub moves: Vec<TileMove<T>>,
+    pub merges: Vec<TileMerge<T>>,
+    /// The tiles that left the board without merging into another one, with the cell they
+    /// slid from, see [`Merged::Cancelled`] and [`Merged::Exploded`].
+    pub destroyed: Vec<(T, tile::Position)>,
+}
 
-    fn try_from(value: &KeyCode) -> Result<Self, Self::Error> {
-        use BoardShiftDirection::*;
+impl<T> ShiftResult<T> {
+    /// The points gained by the merges of this shift.
+    pub fn score(&self) -> u32 {
+        return self
+            .merges
+            .iter()
+            .filter_map(|merge| merge.points.number())
+            .sum();
+    }
 
-        return match value {
-            KeyCode::Left | KeyCode::H | KeyCode::A => Ok(Left),
-            KeyCode::Right | KeyCode::L | KeyCode::D => Ok(Right),
-            KeyCode::Down | KeyCode::J | KeyCode::S => Ok(Down),
-            KeyCode::Up | KeyCode::K | KeyCode::W => Ok(Up),
-            _ => Err(()),
-        };
+    /// A shift that didn't move or merge anything is not a valid move.
+    pub fn has_changed(&self) -> bool {
+        return !self.merges.is_empty()
+            || !self.destroyed.is_empty()
+            || self.moves.iter().any(|m| m.from != m.to);
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct TileMove<T> {
+    pub id: T,
+    pub from: tile::Position,
+    pub to: tile::Position,
+}
+
+/// The `absorbed` tiles slide into `id`, which takes the merged `points`.
+#[derive(Debug, Clone)]
+pub struct TileMerge<T> {
+    pub id: T,
+    /// Every absorbed tile with the cell it slid from.
+    pub absorbed: Vec<(T, tile::Position)>,
+    pub position: tile::Position,
+    pub points: tile::Points,
+}
+
 pub mod tile {
     use bevy::prelude::*;
+    use serde::{Deserialize, Serialize};
 
-    #[derive(Component, Debug, PartialEq, Clone, Copy)]
+    #[derive(Component, Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
     pub struct Points {
+        /// The number on the tile, without the sign of a [`Kind::Negative`] one and `0` for
+        /// the other special kinds.
         pub value: u32,
+        pub kind: Kind,
     }
 
-    #[derive(Component, Debug, PartialEq, Eq, Clone, Copy, Hash)]
+    impl Points {
+        /// A number tile.
+        pub fn new(value: u32) -> Self {
+            return Self {
+                value,
+                kind: Kind::Number,
+            };
+        }
+
+        /// The value of a number tile, the special ones don't score nor count towards
+        /// the goals and records.
+        pub fn number(&self) -> Option<u32> {
+            return (self.kind == Kind::Number).then_some(self.value);
+        }
+
+        pub fn label(&self) -> String {
+            return match self.kind {
+                Kind::Number => self.value.to_string(),
+                Kind::Wildcard => "*".to_string(),
+                Kind::Bomb => "TNT".to_string(),
+                Kind::Multiplier => "\u{d7}2".to_string(),
+                Kind::Negative => format!("\u{2212}{}", self.value),
+            };
+        }
+    }
+
+    /// How a tile merges, the special kinds spawn in [`crate::game::GameMode::PowerUps`].
+    #[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash, Serialize, Deserialize)]
+    pub enum Kind {
+        #[default]
+        Number,
+        /// Merges with a number or a negative tile as the value it needs, see
+        /// [`crate::merge::MergeRule::partner`].
+        Wildcard,
+        /// Merges with any tile, destroying both and the tiles next to them.
+        Bomb,
+        /// Merges with a number or a negative tile, doubling it.
+        Multiplier,
+        /// Cancels the number tile of the same value, merges with the other negative tiles.
+        Negative,
+    }
+
+    impl Kind {
+        /// The special kinds, with how often they spawn compared to each other.
+        pub const SPAWN_WEIGHTS: [(Kind, u32); 4] = [
+            (Kind::Wildcard, 3),
+            (Kind::Bomb, 2),
+            (Kind::Multiplier, 2),
+            (Kind::Negative, 3),
+        ];
+    }
+
+    #[derive(Component, Debug, PartialEq, Eq, Clone, Copy, Hash, Serialize, Deserialize)]
     pub struct Position {
         pub x: u8,
         pub y: u8,
+        /// The layer of a [`super::Grid::Cube`] board, `0` on the others.
+        pub z: u8,
     }
 
     #[derive(Component)]
     pub struct TileText;
+
+    /// A cell tiles can't slide through nor merge into.
+    #[derive(Component, Debug, Clone, Copy)]
+    pub struct Blocker;
 }
 
-pub struct NewTileEvent;
+/// The shape of the cells of a board.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Grid {
+    #[default]
+    Square,
+    /// A hexagon of hexagonal cells, `size` of them across.
+    ///
+    /// A [`tile::Position`] holds axial coordinates from the center cell at
+    /// `(size / 2, size / 2)`, [`BoardShiftDirection::step`] tells where its six neighbours are.
+    Hex,
+    /// `size` square layers stacked along [`tile::Position::z`], drawn side by side.
+    Cube,
+    /// A square board whose lines wrap around, a tile past an edge comes back on the other side.
+    Torus,
+}
+
+impl Grid {
+    /// Odd, so that the hexagon has a center cell.
+    pub const HEX_SIZE: u8 = 5;
+    pub const CUBE_SIZE: u8 = 4;
+
+    /// The directions a board of this grid can shift to.
+    pub fn directions(&self) -> &'static [BoardShiftDirection] {
+        return match self {
+            Grid::Square | Grid::Torus => &BoardShiftDirection::ALL,
+            Grid::Hex => &BoardShiftDirection::HEX,
+            Grid::Cube => &BoardShiftDirection::CUBE,
+        };
+    }
+
+    /// The direction a key shifts a board of this grid to.
+    pub fn key_direction(
+        &self,
+        key_code: &KeyCode,
+        key_bindings: &KeyBindings,
+    ) -> Option<BoardShiftDirection> {
+        return match self {
+            Grid::Square | Grid::Cube | Grid::Torus => key_bindings.direction(key_code),
+            Grid::Hex => BoardShiftDirection::from_hex_key(key_code),
+        };
+    }
+
+    /// One direction of each line of cells, along which the neighbours merge.
+    pub fn axes(&self) -> &'static [BoardShiftDirection] {
+        return match self {
+            Grid::Square | Grid::Torus => &[BoardShiftDirection::Right, BoardShiftDirection::Up],
+            Grid::Hex => &[
+                BoardShiftDirection::Up,
+                BoardShiftDirection::UpRight,
+                BoardShiftDirection::DownRight,
+            ],
+            Grid::Cube => &[
+                BoardShiftDirection::Right,
+                BoardShiftDirection::Up,
+                BoardShiftDirection::In,
+            ],
+        };
+    }
+
+    pub fn contains(&self, size: u8, position: tile::Position) -> bool {
+        if position.x >= size || position.y >= size {
+            return false;
+        }
+        return match self {
+            Grid::Square | Grid::Torus => position.z == 0,
+            // Both coordinates and their sum at most `size / 2` away from the center
+            Grid::Hex => {
+                let radius = size / 2;
+                position.z == 0 && (radius..=3 * radius).contains(&(position.x + position.y))
+            }
+            Grid::Cube => position.z < size,
+        };
+    }
+
+    /// The cell a `step` away from `position`, `None` past the edge of the board, which
+    /// a [`Grid::Torus`] only has across its layers.
+    pub fn neighbour(
+        &self,
+        size: u8,
+        position: tile::Position,
+        (dx, dy, dz): (i8, i8, i8),
+    ) -> Option<tile::Position> {
+        if *self == Grid::Torus {
+            let wrap = |coordinate: u8, delta: i8| {
+                (coordinate as i16 + delta as i16).rem_euclid(size as i16) as u8
+            };
+            let next = tile::Position {
+                x: wrap(position.x, dx),
+                y: wrap(position.y, dy),
+                z: position.z.checked_add_signed(dz)?,
+            };
+            return self.contains(size, next).then_some(next);
+        }
+        let next = tile::Position {
+            x: position.x.checked_add_signed(dx)?,
+            y: position.y.checked_add_signed(dy)?,
+            z: position.z.checked_add_signed(dz)?,
+        };
+        return self.contains(size, next).then_some(next);
+    }
+
+    /// Whether `position` is a corner of the board, a [`Grid::Torus`] has none.
+    pub fn is_corner(&self, size: u8, position: tile::Position) -> bool {
+        let last = size - 1;
+        let is_end = |coordinate: u8| coordinate == 0 || coordinate == last;
+
+        return match self {
+            Grid::Square => is_end(position.x) && is_end(position.y),
+            // Where two sides of the hexagon meet, so two of its bounds are reached
+            Grid::Hex => {
+                let radius = size / 2;
+                let sum = position.x + position.y;
+                self.contains(size, position)
+                    && [
+                        is_end(position.x),
+                        is_end(position.y),
+                        sum == radius || sum == 3 * radius,
+                    ]
+                    .into_iter()
+                    .filter(|&reached| reached)
+                    .count()
+                        >= 2
+            }
+            Grid::Cube => is_end(position.x) && is_end(position.y) && is_end(position.z),
+            Grid::Torus => false,
+        };
+    }
+
+    /// How many layers a board has.
+    pub fn layers(&self, size: u8) -> u8 {
+        return match self {
+            Grid::Square | Grid::Hex | Grid::Torus => 1,
+            Grid::Cube => size,
+        };
+    }
+
+    /// Every cell of a board, layer by layer and column by column.
+    pub fn cells(self, size: u8) -> impl Iterator<Item = tile::Position> {
+        return (0..self.layers(size))
+            .cartesian_product((0..size).cartesian_product(0..size))
+            .map(|(z, (x, y))| tile::Position { x, y, z })
+            .filter(move |&position| self.contains(size, position));
+    }
+}
+
+/// Where the blockers of [`crate::game::GameMode::Blockers`] are placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BlockerLayout {
+    /// This many blockers on random cells.
+    Random(u8),
+    /// One blocker in every corner.
+    Corners,
+}
+
+impl BlockerLayout {
+    pub fn positions(&self, board_size: u8, rng: &mut impl Rng) -> Vec<tile::Position> {
+        let last = board_size - 1;
+
+        return match self {
+            BlockerLayout::Random(count) => Grid::Square
+                .cells(board_size)
+                .choose_multiple(rng, *count as usize),
+            BlockerLayout::Corners => [(0, 0), (0, last), (last, 0), (last, last)]
+                .into_iter()
+                .map(|(x, y)| tile::Position { x, y, z: 0 })
+                .collect(),
+        };
+    }
+}
+
+/// A move changed the board, which gets a new tile.
+pub struct NewTileEvent {
+    pub board: Entity,
+}
+
+/// A move in `direction` changed the board, sent before its new tile.
+#[derive(Debug, Clone, Copy)]
+pub struct BoardShiftedEvent {
+    pub board: Entity,
+    pub direction: BoardShiftDirection,
+}
+
+/// A tile slid to another cell, sent for the surviving tile of a merge as well.
+#[derive(Debug, Clone, Copy)]
+pub struct TileMovedEvent {
+    pub entity: Entity,
+    pub from: tile::Position,
+    pub to: tile::Position,
+    pub direction: BoardShiftDirection,
+}
+
+/// The `absorbed` tiles slid into `entity` and have been despawned.
+#[derive(Debug, Clone)]
+pub struct TilesMergedEvent {
+    pub entity: Entity,
+    pub board: Entity,
+    /// Every absorbed tile with the cell it slid from.
+    pub absorbed: Vec<(Entity, tile::Position)>,
+    pub position: tile::Position,
+    /// The merged tile.
+    pub points: tile::Points,
+    pub score_gained: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TileSpawnedEvent {
+    pub entity: Entity,
+    pub board: Entity,
+    pub position: tile::Position,
+    pub points: tile::Points,
+}
+
+/// A board got its first tile of the winning value of the rule, see [`MergeRule::winning_value`].
+#[derive(Debug, Clone, Copy)]
+pub struct BoardWonEvent {
+    pub board: Entity,
+}
+
+/// A shift was requested but nothing could move or merge in that direction.
+#[derive(Debug, Clone, Copy)]
+pub struct MoveRejectedEvent {
+    pub board: Entity,
+    pub direction: BoardShiftDirection,
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::HashMap;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::game;
+
+    type Cell = (u8, u8, u8);
+
+    fn position((x, y, z): Cell) -> tile::Position {
+        return tile::Position { x, y, z };
+    }
+
+    /// The tiles left after shifting `tiles` with the classic rule, sorted by cell.
+    fn shift_points(
+        grid: Grid,
+        size: u8,
+        direction: BoardShiftDirection,
+        tiles: &[(Cell, tile::Points)],
+        blockers: &[Cell],
+    ) -> (Vec<(Cell, tile::Points)>, ShiftResult<usize>) {
+        let tiles = tiles
+            .iter()
+            .enumerate()
+            .map(|(id, &(cell, points))| (id, position(cell), points))
+            .collect::<Vec<_>>();
+        let blockers = blockers.iter().copied().map(position).collect::<Vec<_>>();
+        let result = direction.shift(grid, size, MergeRule::Classic, &tiles, &blockers);
+
+        let after = result
+            .moves
+            .iter()
+            .map(|tile_move| {
+                let points = result
+                    .merges
+                    .iter()
+                    .find(|merge| merge.id == tile_move.id)
+                    .map_or(tiles[tile_move.id].2, |merge| merge.points);
+                let tile::Position { x, y, z } = tile_move.to;
+                ((x, y, z), points)
+            })
+            .sorted_by_key(|&(cell, _)| cell)
+            .collect();
+        return (after, result);
+    }
+
+    /// Like [`shift_points`] with number tiles.
+    fn shift(
+        grid: Grid,
+        size: u8,
+        direction: BoardShiftDirection,
+        tiles: &[(Cell, u32)],
+        blockers: &[Cell],
+    ) -> Vec<(Cell, u32)> {
+        let tiles = tiles
+            .iter()
+            .map(|&(cell, value)| (cell, tile::Points::new(value)))
+            .collect::<Vec<_>>();
+        return shift_points(grid, size, direction, &tiles, blockers)
+            .0
+            .into_iter()
+            .map(|(cell, points)| (cell, points.value))
+            .collect();
+    }
+
+    /// Fills boards and checks that [`game::has_move`] finds a move exactly when one of the
+    /// directions of the grid changes the board.
+    ///
+    /// The boards start without any merge, then a few tiles take the value of a neighbour.
+    fn assert_has_move_agrees(grid: Grid, size: u8, blockers: &[Cell]) {
+        let mut rng = StdRng::seed_from_u64(u64::from(size));
+        let blockers = blockers.iter().copied().map(position).collect::<Vec<_>>();
+        let mut outcomes = Vec::new();
+
+        for _ in 0..200 {
+            // Neighbours are less than 24 cells apart in the order of `Grid::cells`
+            let mut map = grid
+                .cells(size)
+                .filter(|cell| !blockers.contains(cell))
+                .enumerate()
+                .map(|(index, cell)| (cell, tile::Points::new(2 << (index % 24))))
+                .collect::<HashMap<_, _>>();
+            let cells = map
+                .keys()
+                .copied()
+                .sorted_by_key(|cell| (cell.z, cell.y, cell.x))
+                .collect::<Vec<_>>();
+            for _ in 0..rng.gen_range(0..3) {
+                let cell = *cells.choose(&mut rng).unwrap();
+                let direction = grid.directions().choose(&mut rng).unwrap();
+                if let Some(&points) = grid
+                    .neighbour(size, cell, direction.step())
+                    .and_then(|neighbour| map.get(&neighbour))
+                {
+                    map.insert(cell, points);
+                }
+            }
+            let tiles = cells
+                .iter()
+                .enumerate()
+                .map(|(id, cell)| (id, *cell, map[cell]))
+                .collect::<Vec<_>>();
+
+            let changes = grid.directions().iter().any(|direction| {
+                direction
+                    .shift(grid, size, MergeRule::Classic, &tiles, &blockers)
+                    .has_changed()
+            });
+            let has_move = game::has_move(grid, size, MergeRule::Classic, &map);
+            assert_eq!(has_move, changes, "{grid:?} {tiles:?}");
+            outcomes.push(has_move);
+        }
+
+        assert!(outcomes.contains(&true) && outcomes.contains(&false));
+    }
+
+    #[test]
+    fn corners_of_each_grid() {
+        let corners = |grid: Grid, size: u8| {
+            grid.cells(size)
+                .filter(|&position| grid.is_corner(size, position))
+                .map(|tile::Position { x, y, z }| (x, y, z))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            corners(Grid::Square, 4),
+            [(0, 0, 0), (0, 3, 0), (3, 0, 0), (3, 3, 0)]
+        );
+        assert_eq!(
+            corners(Grid::Hex, 5),
+            [
+                (0, 2, 0),
+                (0, 4, 0),
+                (2, 0, 0),
+                (2, 4, 0),
+                (4, 0, 0),
+                (4, 2, 0)
+            ]
+        );
+        assert_eq!(corners(Grid::Cube, 4).len(), 8);
+        assert!(corners(Grid::Torus, 4).is_empty());
+    }
+
+    #[test]
+    fn hex_shifts_along_its_axes() {
+        use BoardShiftDirection::*;
+
+        let cases: [(BoardShiftDirection, &[(Cell, u32)], &[Cell], &[(Cell, u32)]); 5] = [
+            // The column through the center
+            (
+                Up,
+                &[((2, 0, 0), 2), ((2, 1, 0), 2), ((2, 3, 0), 4)],
+                &[],
+                &[((2, 3, 0), 4), ((2, 4, 0), 4)],
+            ),
+            (
+                Up,
+                &[((2, 0, 0), 2), ((2, 1, 0), 2), ((2, 4, 0), 4)],
+                &[(2, 2, 0)],
+                &[((2, 1, 0), 4), ((2, 4, 0), 4)],
+            ),
+            // The diagonal through the center, from corner to corner
+            (
+                DownRight,
+                &[((0, 4, 0), 2), ((2, 2, 0), 2)],
+                &[],
+                &[((4, 0, 0), 4)],
+            ),
+            (
+                DownRight,
+                &[((0, 4, 0), 2), ((2, 2, 0), 2)],
+                &[(3, 1, 0)],
+                &[((2, 2, 0), 4)],
+            ),
+            // A short line along the edge
+            (UpLeft, &[((4, 2, 0), 2)], &[], &[((2, 4, 0), 2)]),
+        ];
+
+        for (direction, tiles, blockers, after) in cases {
+            assert_eq!(
+                shift(Grid::Hex, Grid::HEX_SIZE, direction, tiles, blockers),
+                after,
+                "{direction:?} {tiles:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn hex_has_move_agrees_with_shift() {
+        assert_has_move_agrees(Grid::Hex, Grid::HEX_SIZE, &[]);
+        assert_has_move_agrees(Grid::Hex, Grid::HEX_SIZE, &[(2, 2, 0), (0, 4, 0)]);
+    }
+
+    #[test]
+    fn cube_shifts_through_its_layers() {
+        use BoardShiftDirection::*;
+
+        let cases: [(BoardShiftDirection, &[(Cell, u32)], &[Cell], &[(Cell, u32)]); 4] = [
+            (
+                In,
+                &[((1, 1, 0), 2), ((1, 1, 2), 2)],
+                &[],
+                &[((1, 1, 3), 4)],
+            ),
+            (
+                Out,
+                &[((1, 1, 0), 2), ((1, 1, 2), 2), ((1, 1, 3), 2)],
+                &[(1, 1, 1)],
+                &[((1, 1, 0), 2), ((1, 1, 2), 4)],
+            ),
+            // The other cells of the layers don't take part
+            (
+                In,
+                &[((0, 0, 1), 4), ((3, 3, 0), 8)],
+                &[(0, 0, 3)],
+                &[((0, 0, 2), 4), ((3, 3, 3), 8)],
+            ),
+            // Each layer shifts like a square board
+            (
+                Left,
+                &[((3, 0, 2), 8), ((2, 0, 2), 8), ((1, 0, 1), 8)],
+                &[],
+                &[((0, 0, 1), 8), ((0, 0, 2), 16)],
+            ),
+        ];
+
+        for (direction, tiles, blockers, after) in cases {
+            assert_eq!(
+                shift(Grid::Cube, Grid::CUBE_SIZE, direction, tiles, blockers),
+                after,
+                "{direction:?} {tiles:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn cube_has_move_agrees_with_shift() {
+        assert_has_move_agrees(Grid::Cube, Grid::CUBE_SIZE, &[]);
+        assert_has_move_agrees(Grid::Cube, Grid::CUBE_SIZE, &[(1, 1, 1), (2, 2, 2)]);
+    }
+
+    #[test]
+    fn torus_shifts_around_its_rings() {
+        use BoardShiftDirection::*;
+
+        let cases: [(BoardShiftDirection, &[(Cell, u32)], &[Cell], &[(Cell, u32)]); 6] = [
+            // The 2s merge across the edge, behind the 8 that is the closest to it
+            (
+                Right,
+                &[
+                    ((0, 0, 0), 2),
+                    ((1, 0, 0), 4),
+                    ((2, 0, 0), 8),
+                    ((3, 0, 0), 2),
+                ],
+                &[],
+                &[((1, 0, 0), 4), ((2, 0, 0), 4), ((3, 0, 0), 8)],
+            ),
+            (
+                Up,
+                &[
+                    ((1, 0, 0), 2),
+                    ((1, 1, 0), 4),
+                    ((1, 2, 0), 8),
+                    ((1, 3, 0), 2),
+                ],
+                &[],
+                &[((1, 1, 0), 4), ((1, 2, 0), 4), ((1, 3, 0), 8)],
+            ),
+            // Both cuts merge twice, the one closest to the edge leads
+            (
+                Right,
+                &[
+                    ((0, 0, 0), 2),
+                    ((1, 0, 0), 4),
+                    ((2, 0, 0), 4),
+                    ((3, 0, 0), 2),
+                ],
+                &[],
+                &[((2, 0, 0), 4), ((3, 0, 0), 8)],
+            ),
+            // Without merges the ring packs like a square row
+            (
+                Left,
+                &[((1, 0, 0), 4), ((3, 0, 0), 2)],
+                &[],
+                &[((0, 0, 0), 4), ((1, 0, 0), 2)],
+            ),
+            // A blocker cuts the ring, the segment wraps up to it
+            (
+                Right,
+                &[((0, 0, 0), 2), ((2, 0, 0), 2)],
+                &[(1, 0, 0)],
+                &[((0, 0, 0), 4)],
+            ),
+            (
+                Down,
+                &[((2, 3, 0), 8), ((2, 1, 0), 4)],
+                &[(2, 2, 0)],
+                &[((2, 0, 0), 4), ((2, 3, 0), 8)],
+            ),
+        ];
+
+        for (direction, tiles, blockers, after) in cases {
+            assert_eq!(
+                shift(Grid::Torus, 4, direction, tiles, blockers),
+                after,
+                "{direction:?} {tiles:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn full_ring_without_merges_doesnt_move() {
+        let tiles = [2, 4, 8, 16].map(tile::Points::new);
+        let tiles = (0..4)
+            .map(|x| ((x, 0, 0), tiles[x as usize]))
+            .collect::<Vec<_>>();
+
+        for direction in [BoardShiftDirection::Left, BoardShiftDirection::Right] {
+            let (after, result) = shift_points(Grid::Torus, 4, direction, &tiles, &[]);
+            assert_eq!(after, tiles);
+            assert!(!result.has_changed());
+        }
+    }
+
+    #[test]
+    fn torus_has_move_agrees_with_shift() {
+        assert_has_move_agrees(Grid::Torus, 4, &[]);
+        assert_has_move_agrees(Grid::Torus, 5, &[(0, 0, 0), (2, 3, 0)]);
+    }
+
+    fn special(kind: tile::Kind, value: u32) -> tile::Points {
+        return tile::Points { value, kind };
+    }
+
+    #[test]
+    fn bomb_destroys_its_pair_and_the_tiles_next_to_it() {
+        let wildcard = special(tile::Kind::Wildcard, 0);
+        let bomb = special(tile::Kind::Bomb, 0);
+        let tiles = [
+            ((0, 0, 0), wildcard),
+            ((1, 0, 0), bomb),
+            ((3, 0, 0), tile::Points::new(4)),
+            ((0, 1, 0), tile::Points::new(8)),
+            ((2, 2, 0), tile::Points::new(16)),
+        ];
+
+        let (after, result) = shift_points(Grid::Square, 4, BoardShiftDirection::Left, &tiles, &[]);
+
+        // The 4 slides into the cell of the blast, the 8 above it is caught
+        assert_eq!(
+            after,
+            [
+                ((0, 0, 0), tile::Points::new(4)),
+                ((0, 2, 0), tile::Points::new(16))
+            ]
+        );
+        assert_eq!(
+            result
+                .destroyed
+                .iter()
+                .sorted_by_key(|(id, _)| id)
+                .collect::<Vec<_>>(),
+            [
+                &(0, position((0, 0, 0))),
+                &(1, position((1, 0, 0))),
+                &(3, position((0, 1, 0)))
+            ]
+        );
+        assert!(result.merges.is_empty());
+        assert!(result.has_changed());
+    }
+
+    #[test]
+    fn bomb_blast_undoes_a_merge_next_to_it() {
+        let tiles = [
+            ((0, 0, 0), special(tile::Kind::Bomb, 0)),
+            ((1, 0, 0), tile::Points::new(2)),
+            ((0, 1, 0), tile::Points::new(2)),
+            ((1, 1, 0), tile::Points::new(2)),
+        ];
+
+        let (after, result) = shift_points(Grid::Square, 4, BoardShiftDirection::Left, &tiles, &[]);
+
+        assert!(after.is_empty());
+        assert!(result.merges.is_empty());
+        assert_eq!(result.destroyed.len(), 4);
+        assert_eq!(result.score(), 0);
+    }
+
+    #[test]
+    fn negative_cancels_its_counterpart() {
+        let tiles = [
+            ((0, 0, 0), special(tile::Kind::Negative, 4)),
+            ((1, 0, 0), tile::Points::new(4)),
+            ((2, 0, 0), tile::Points::new(2)),
+            // Only the same value cancels, negative tiles merge together
+            ((0, 1, 0), special(tile::Kind::Negative, 4)),
+            ((1, 1, 0), tile::Points::new(8)),
+            ((0, 2, 0), special(tile::Kind::Negative, 2)),
+            ((1, 2, 0), special(tile::Kind::Negative, 2)),
+        ];
+
+        let (after, result) = shift_points(Grid::Square, 4, BoardShiftDirection::Left, &tiles, &[]);
+
+        assert_eq!(
+            after,
+            [
+                ((0, 0, 0), tile::Points::new(2)),
+                ((0, 1, 0), special(tile::Kind::Negative, 4)),
+                ((0, 2, 0), special(tile::Kind::Negative, 4)),
+                ((1, 1, 0), tile::Points::new(8)),
+            ]
+        );
+        assert_eq!(
+            result.destroyed,
+            [(0, position((0, 0, 0))), (1, position((1, 0, 0)))]
+        );
+        // Neither the cancelled pair nor the negative merge scores
+        assert_eq!(result.score(), 0);
+    }
+
+    #[test]
+    fn multipliers_stack_over_moves() {
+        let multiplier = special(tile::Kind::Multiplier, 0);
+        let tiles = [
+            ((0, 0, 0), tile::Points::new(8)),
+            ((1, 0, 0), multiplier),
+            ((2, 0, 0), multiplier),
+            // Multipliers don't merge together
+            ((0, 1, 0), multiplier),
+            ((1, 1, 0), multiplier),
+        ];
+
+        let (after, result) = shift_points(Grid::Square, 4, BoardShiftDirection::Left, &tiles, &[]);
+        assert_eq!(
+            after,
+            [
+                ((0, 0, 0), tile::Points::new(16)),
+                ((0, 1, 0), multiplier),
+                ((1, 0, 0), multiplier),
+                ((1, 1, 0), multiplier),
+            ]
+        );
+        assert_eq!(result.score(), 16);
+
+        let (after, result) = shift_points(Grid::Square, 4, BoardShiftDirection::Left, &after, &[]);
+        assert_eq!(
+            after,
+            [
+                ((0, 0, 0), tile::Points::new(32)),
+                ((0, 1, 0), multiplier),
+                ((1, 1, 0), multiplier),
+            ]
+        );
+        assert_eq!(result.score(), 32);
+    }
+
+    #[test]
+    fn wildcard_takes_the_value_of_its_partner() {
+        let wildcard = special(tile::Kind::Wildcard, 0);
+        let tiles = [
+            ((0, 0, 0), tile::Points::new(2)),
+            ((1, 0, 0), wildcard),
+            ((0, 1, 0), wildcard),
+            ((1, 1, 0), special(tile::Kind::Negative, 8)),
+            // Wildcards don't merge together
+            ((0, 2, 0), wildcard),
+            ((1, 2, 0), wildcard),
+        ];
+
+        let (after, result) = shift_points(Grid::Square, 4, BoardShiftDirection::Left, &tiles, &[]);
+
+        assert_eq!(
+            after,
+            [
+                ((0, 0, 0), tile::Points::new(4)),
+                ((0, 1, 0), special(tile::Kind::Negative, 16)),
+                ((0, 2, 0), wildcard),
+                ((1, 2, 0), wildcard),
+            ]
+        );
+        assert_eq!(result.score(), 4);
+    }
+}
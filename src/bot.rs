@@ -0,0 +1,231 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use rand::{seq::IteratorRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::{tile, Board, BoardShiftDirection, Grid},
+    game::{BoardGame, GameMode, GameState},
+    merge::MergeRule,
+};
+
+/// Plays the boards with a [`Bot`], like the ones of [`GameMode::Gallery`].
+pub struct BotPlugin;
+
+/// How well a [`Bot`] plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        };
+    }
+
+    /// How many moves ahead the bot looks, ignoring the spawns in between.
+    pub fn depth(&self) -> u8 {
+        return match self {
+            Difficulty::Easy => 1,
+            Difficulty::Normal => 2,
+            Difficulty::Hard => 3,
+        };
+    }
+
+    /// The chance of playing a random move instead of the best one.
+    pub fn mistake_probability(&self) -> f64 {
+        return match self {
+            Difficulty::Easy => 0.3,
+            Difficulty::Normal => 0.1,
+            Difficulty::Hard => 0.,
+        };
+    }
+
+    /// Harder bots also think faster.
+    pub fn move_interval(&self) -> Duration {
+        return match self {
+            Difficulty::Easy => Duration::from_millis(400),
+            Difficulty::Normal => Duration::from_millis(250),
+            Difficulty::Hard => Duration::from_millis(150),
+        };
+    }
+}
+
+/// Picks the moves of its board entity, one every [`Difficulty::move_interval`].
+#[derive(Component, Debug, Clone)]
+pub struct Bot {
+    /// The move for [`BoardShiftDirection::sys_handle_board_shift_on_keypress`] to play.
+    pub next: Option<BoardShiftDirection>,
+    pub difficulty: Difficulty,
+    timer: Timer,
+}
+
+impl Default for Bot {
+    fn default() -> Self {
+        return Self::new(Difficulty::default());
+    }
+}
+
+impl Bot {
+    /// How much an empty cell is worth next to the points of a merge.
+    const EMPTY_CELL_WEIGHT: u32 = 16;
+    /// The pace of a bot left playing alone, see [`Bot::hurry`].
+    const HURRY_INTERVAL: Duration = Duration::from_millis(50);
+
+    pub fn new(difficulty: Difficulty) -> Self {
+        return Self {
+            next: None,
+            difficulty,
+            timer: Timer::new(difficulty.move_interval(), TimerMode::Repeating),
+        };
+    }
+
+    /// Plays the remaining moves at a quick pace.
+    pub fn hurry(&mut self) {
+        if self.timer.duration() != Bot::HURRY_INTERVAL {
+            self.timer.set_duration(Bot::HURRY_INTERVAL);
+        }
+    }
+
+    /// The move of the [`Difficulty`], `None` when nothing moves.
+    pub fn pick<T: Copy>(
+        &self,
+        grid: Grid,
+        board_size: u8,
+        rule: MergeRule,
+        tiles: &[(T, tile::Position, tile::Points)],
+        blockers: &[tile::Position],
+        rng: &mut impl Rng,
+    ) -> Option<BoardShiftDirection> {
+        if rng.gen_bool(self.difficulty.mistake_probability()) {
+            return grid
+                .directions()
+                .iter()
+                .copied()
+                .filter(|direction| {
+                    direction
+                        .shift(grid, board_size, rule, tiles, blockers)
+                        .has_changed()
+                })
+                .choose(rng);
+        }
+
+        return Bot::best_move(
+            grid,
+            board_size,
+            rule,
+            tiles,
+            blockers,
+            self.difficulty.depth(),
+        )
+        .map(|(_, direction)| direction);
+    }
+
+    /// The move that leaves the most room and gains the most points within `depth` moves,
+    /// with its value.
+    pub fn best_move<T: Copy>(
+        grid: Grid,
+        board_size: u8,
+        rule: MergeRule,
+        tiles: &[(T, tile::Position, tile::Points)],
+        blockers: &[tile::Position],
+        depth: u8,
+    ) -> Option<(u32, BoardShiftDirection)> {
+        let cells = grid.cells(board_size).count() as u32 - blockers.len() as u32;
+        // Indexes stand in for the ids, so the next moves can find the merged values
+        let tiles = tiles
+            .iter()
+            .enumerate()
+            .map(|(index, &(_, position, points))| (index, position, points))
+            .collect::<Vec<_>>();
+
+        return grid
+            .directions()
+            .iter()
+            .filter_map(|&direction| {
+                let result = direction.shift(grid, board_size, rule, &tiles, blockers);
+                if !result.has_changed() {
+                    return None;
+                }
+
+                let after = result
+                    .moves
+                    .iter()
+                    .map(|tile_move| {
+                        let points = result
+                            .merges
+                            .iter()
+                            .find(|merge| merge.id == tile_move.id)
+                            .map_or(tiles[tile_move.id].2, |merge| merge.points);
+                        (tile_move.id, tile_move.to, points)
+                    })
+                    .collect::<Vec<_>>();
+                let rest = match depth {
+                    0 | 1 => None,
+                    _ => Bot::best_move(grid, board_size, rule, &after, blockers, depth - 1),
+                };
+                let empty = cells - after.len() as u32;
+                let value = rest.map_or(empty * Bot::EMPTY_CELL_WEIGHT, |(value, _)| value);
+
+                Some((result.score() + value, direction))
+            })
+            // The first of equal moves, so a replay picks the same ones
+            .rev()
+            .max_by_key(|&(value, _)| value);
+    }
+}
+
+impl BotPlugin {
+    pub fn sys_think(
+        time: Res<Time>,
+        mut query_board: Query<(Entity, &Board, &BoardGame, &mut Bot)>,
+        tiles: Query<(Entity, &tile::Position, &tile::Points, &Parent)>,
+        blockers: Query<(&tile::Position, &Parent), (With<tile::Blocker>, Without<tile::Points>)>,
+        game_mode: Res<GameMode>,
+    ) {
+        for (board_entity, board, board_game, mut bot) in query_board.iter_mut() {
+            if !bot.timer.tick(time.delta()).just_finished() || board_game.over {
+                continue;
+            }
+
+            let tiles = tiles
+                .iter()
+                .filter(|(_, _, _, parent)| parent.get() == board_entity)
+                .map(|(entity, &position, &points, _)| (entity, position, points))
+                .collect::<Vec<_>>();
+            let blockers = blockers
+                .iter()
+                .filter(|(_, parent)| parent.get() == board_entity)
+                .map(|(&position, _)| position)
+                .collect::<Vec<_>>();
+            bot.next = bot.pick(
+                board.grid,
+                board.size,
+                game_mode.merge_rule(),
+                &tiles,
+                &blockers,
+                &mut rand::thread_rng(),
+            );
+        }
+    }
+}
+
+impl Plugin for BotPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            BotPlugin::sys_think
+                .before(BoardShiftDirection::sys_handle_board_shift_on_keypress)
+                .in_set(OnUpdate(GameState::Playing)),
+        );
+    }
+}
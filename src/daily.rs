@@ -0,0 +1,251 @@
+use std::{fmt::Write, fs};
+
+use bevy::prelude::*;
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::{tile, Board},
+    game::{BoardGame, Game, GameMode, GameState, NewGameEvent, SoloBoard},
+    storage,
+};
+
+/// Records the first daily challenge of every day and fills the [`DailyCard`] at its end.
+pub struct DailyPlugin;
+
+/// The rules everyone plays the daily challenge with, whatever their settings.
+pub const BOARD_SIZE: u8 = 4;
+pub const SPAWN_FOUR_PROBABILITY: f32 = 0.1;
+
+pub fn today() -> NaiveDate {
+    return chrono::Local::now().date_naive();
+}
+
+/// The seed of the daily challenge of `date`, the same on every machine.
+pub fn seed(date: NaiveDate) -> u64 {
+    // SplitMix64 spreads consecutive days over the whole seed range
+    let mut z = (date.num_days_from_ce() as u64).wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    return z ^ (z >> 31);
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailyResult {
+    /// Formatted as `YYYY-MM-DD`.
+    pub date: String,
+    pub score: u32,
+    pub max_tile: u32,
+    pub moves: u32,
+    pub duration_secs: f32,
+    /// The final board, top row first, `0` for empty cells.
+    pub board: Vec<Vec<u32>>,
+}
+
+impl DailyResult {
+    /// One emoji square per cell, darker to brighter as the tiles grow.
+    pub fn emoji(value: u32) -> char {
+        return match value {
+            0 => '⬛',
+            1..=4 => '⬜',
+            5..=16 => '🟫',
+            17..=64 => '🟦',
+            65..=256 => '🟩',
+            257..=512 => '🟨',
+            513..=1024 => '🟧',
+            _ => '🟥',
+        };
+    }
+
+    /// A text summary with an emoji grid of the final board, to paste in a chat.
+    pub fn share_text(&self) -> String {
+        let mut text = format!(
+            "2048 Daily {}\nScore {} | Best tile {} | {} moves\n",
+            self.date, self.score, self.max_tile, self.moves
+        );
+        for row in self.board.iter() {
+            let _ = writeln!(
+                text,
+                "{}",
+                row.iter()
+                    .map(|&value| DailyResult::emoji(value))
+                    .collect::<String>()
+            );
+        }
+        return text;
+    }
+}
+
+/// The scored daily challenges, stored in [`DailyResults::FILE`].
+#[derive(Resource, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DailyResults {
+    /// Oldest first.
+    pub results: Vec<DailyResult>,
+    /// The date of the last scored attempt, even if it was left unfinished.
+    pub last_attempt: Option<String>,
+}
+
+impl DailyResults {
+    pub const FILE: &str = "daily.ron";
+    /// The share text of the last scored challenge is also written there.
+    pub const SHARE_FILE: &str = "daily_share.txt";
+
+    pub fn load() -> Self {
+        return storage::load(DailyResults::FILE);
+    }
+
+    pub fn get(&self, date: NaiveDate) -> Option<&DailyResult> {
+        let date = date.to_string();
+        return self.results.iter().find(|result| result.date == date);
+    }
+}
+
+/// The daily challenge being played, only the first one of a day is scored.
+#[derive(Resource, Debug, Default)]
+pub struct DailyAttempt(pub Option<Attempt>);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Attempt {
+    pub date: NaiveDate,
+    /// `false` for the practice attempts after the scored one.
+    pub ranked: bool,
+}
+
+/// The result card shown at the end of a daily challenge.
+#[derive(Resource, Debug, Default)]
+pub struct DailyCard(pub Option<(DailyResult, Attempt)>);
+
+impl DailyPlugin {
+    fn sys_start_attempt(
+        mut new_game_reader: EventReader<NewGameEvent>,
+        game_mode: Res<GameMode>,
+        mut results: ResMut<DailyResults>,
+        mut attempt: ResMut<DailyAttempt>,
+    ) {
+        let Some(event) = new_game_reader.iter().last() else {
+            return;
+        };
+
+        // Same as the seed picked by `game::reset`
+        if *game_mode != GameMode::Daily || event.seed.is_some() {
+            attempt.0 = None;
+            return;
+        }
+
+        // Restarting doesn't give a second scored try with the same spawns
+        let date = today();
+        let ranked = results.last_attempt != Some(date.to_string());
+        if ranked {
+            results.last_attempt = Some(date.to_string());
+            storage::save(DailyResults::FILE, results.as_ref());
+        }
+
+        attempt.0 = Some(Attempt { date, ranked });
+    }
+
+    fn on_game_over(
+        attempt: Res<DailyAttempt>,
+        mut results: ResMut<DailyResults>,
+        mut card: ResMut<DailyCard>,
+        game: Res<Game>,
+        query_board: Query<(&Board, &BoardGame), SoloBoard>,
+        tiles: Query<(&tile::Position, &tile::Points)>,
+    ) {
+        let Some(attempt) = attempt.0 else {
+            return;
+        };
+        let Ok((board, board_game)) = query_board.get_single() else {
+            return;
+        };
+        let size = board.size;
+
+        let mut board = vec![vec![0; size as usize]; size as usize];
+        for (position, points) in tiles.iter() {
+            board[(size - 1 - position.y) as usize][position.x as usize] = points.value;
+        }
+
+        let result = DailyResult {
+            date: attempt.date.to_string(),
+            score: board_game.score,
+            max_tile: tiles
+                .iter()
+                .map(|(_, points)| points.value)
+                .max()
+                .unwrap_or(0),
+            moves: board_game.moves,
+            duration_secs: game.elapsed.as_secs_f32(),
+            board,
+        };
+
+        if attempt.ranked {
+            results.results.push(result.clone());
+            storage::save(DailyResults::FILE, results.as_ref());
+
+            let path = storage::data_dir().join(DailyResults::SHARE_FILE);
+            if let Err(err) = fs::write(&path, result.share_text()) {
+                warn!("Couldn't write {}: {err}", path.display());
+            }
+        }
+
+        card.0 = Some((result, attempt));
+    }
+
+    fn on_exit_game_over(mut card: ResMut<DailyCard>) {
+        card.0 = None;
+    }
+}
+
+impl Plugin for DailyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DailyResults::load())
+            .init_resource::<DailyAttempt>()
+            .init_resource::<DailyCard>()
+            .add_system(DailyPlugin::sys_start_attempt)
+            .add_system(DailyPlugin::on_game_over.in_schedule(OnEnter(GameState::GameOver)))
+            .add_system(DailyPlugin::on_exit_game_over.in_schedule(OnExit(GameState::GameOver)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        return NaiveDate::from_ymd_opt(2024, 3, day).expect("A valid date");
+    }
+
+    #[test]
+    fn seed_depends_only_on_the_date() {
+        assert_eq!(seed(date(1)), seed(date(1)));
+        // Pinned, changing it would change the challenge of every day
+        assert_eq!(seed(date(1)), 11446773249474946846);
+        assert_ne!(seed(date(1)), seed(date(2)));
+        assert_ne!(seed(date(2)), seed(date(3)));
+    }
+
+    #[test]
+    fn share_text_has_the_result_and_the_board() {
+        let result = DailyResult {
+            date: "2024-03-01".to_string(),
+            score: 2316,
+            max_tile: 256,
+            moves: 187,
+            duration_secs: 240.,
+            board: vec![
+                vec![0, 2, 4, 8],
+                vec![16, 32, 64, 128],
+                vec![256, 512, 1024, 2048],
+            ],
+        };
+
+        assert_eq!(
+            result.share_text(),
+            "2048 Daily 2024-03-01\n\
+             Score 2316 | Best tile 256 | 187 moves\n\
+             ⬛⬜⬜🟫\n\
+             🟫🟦🟦🟩\n\
+             🟩🟨🟧🟥\n"
+        );
+    }
+}
@@ -0,0 +1,396 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    assets::FontSpec,
+    board::{tile, Board, Grid},
+    game::{Game, GameState},
+    puzzle::{Goal, Level},
+    settings::Settings,
+    storage,
+};
+
+/// Edits a [`Level`] by clicking the cells of the board while in [`GameState::Editor`].
+///
+/// Left click cycles the value of a tile or toggles a blocker, depending on the [`EditorTool`],
+/// right click empties the cell.
+pub struct EditorPlugin;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditorTool {
+    Tiles,
+    Blockers,
+}
+
+/// The level being edited, stored in [`Editor::FILE`] when saved.
+#[derive(Resource, Debug, Clone)]
+pub struct Editor {
+    pub level: Level,
+    pub tool: EditorTool,
+}
+
+impl Editor {
+    /// Has the same format as the files in `assets/levels`, so it can be copied there.
+    pub const FILE: &str = "editor.level.ron";
+    /// Clicking a tile of this value empties its cell.
+    pub const MAX_TILE: u32 = 1 << 17;
+
+    pub fn load() -> Self {
+        let mut level: Level = storage::load(Editor::FILE);
+        // Evens out rows of a hand edited file
+        let size = level
+            .board_size()
+            .clamp(*Settings::BOARD_SIZES.start(), *Settings::BOARD_SIZES.end());
+        Editor::resize(&mut level, size);
+
+        return Self {
+            level,
+            tool: EditorTool::Tiles,
+        };
+    }
+
+    pub fn save(&self) -> std::path::PathBuf {
+        storage::save(Editor::FILE, &self.level);
+        return storage::data_dir().join(Editor::FILE);
+    }
+
+    /// Keeps the top left corner of the board.
+    fn resize(level: &mut Level, size: u8) {
+        level.board.resize(size as usize, Vec::new());
+        for row in level.board.iter_mut() {
+            row.resize(size as usize, 0);
+        }
+        level.blockers.retain(|&(x, row)| x < size && row < size);
+    }
+
+    /// The cell of `position` in [`Level::board`] and [`Level::blockers`].
+    fn cell(&self, position: tile::Position) -> (usize, usize) {
+        let row = self.level.board_size() - 1 - position.y;
+        return (position.x as usize, row as usize);
+    }
+
+    fn remove_blocker(&mut self, position: tile::Position) -> bool {
+        let (x, row) = self.cell(position);
+        let count = self.level.blockers.len();
+        self.level
+            .blockers
+            .retain(|&blocker| blocker != (x as u8, row as u8));
+        return self.level.blockers.len() != count;
+    }
+
+    /// Applies the [`EditorTool`] to a cell.
+    pub fn click(&mut self, position: tile::Position) {
+        let (x, row) = self.cell(position);
+
+        match self.tool {
+            EditorTool::Tiles => {
+                self.remove_blocker(position);
+                let value = &mut self.level.board[row][x];
+                *value = match *value {
+                    0 => 2,
+                    value if value >= Editor::MAX_TILE => 0,
+                    value => value * 2,
+                };
+            }
+            EditorTool::Blockers => {
+                self.level.board[row][x] = 0;
+                if !self.remove_blocker(position) {
+                    self.level.blockers.push((x as u8, row as u8));
+                }
+            }
+        }
+    }
+
+    pub fn clear_cell(&mut self, position: tile::Position) {
+        let (x, row) = self.cell(position);
+        self.level.board[row][x] = 0;
+        self.remove_blocker(position);
+    }
+
+    /// Empties the board and the spawn script, keeping the size and the goal.
+    pub fn clear(&mut self) {
+        for row in self.level.board.iter_mut() {
+            row.fill(0);
+        }
+        self.level.blockers.clear();
+        self.level.spawns.clear();
+    }
+}
+
+/// A line of the editor panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditorField {
+    BoardSize,
+    Tool,
+    Goal,
+    Target,
+    MaxMoves,
+    ThreeStars,
+    TwoStars,
+    /// Adds or removes the last spawn of the script.
+    Spawns,
+    /// Halves or doubles the last spawn of the script.
+    LastSpawn,
+}
+
+impl EditorField {
+    pub const ALL: [EditorField; 9] = [
+        EditorField::BoardSize,
+        EditorField::Tool,
+        EditorField::Goal,
+        EditorField::Target,
+        EditorField::MaxMoves,
+        EditorField::ThreeStars,
+        EditorField::TwoStars,
+        EditorField::Spawns,
+        EditorField::LastSpawn,
+    ];
+
+    /// How many of the last spawns the panel lists.
+    const SHOWN_SPAWNS: usize = 6;
+    const SCORE_STEP: u32 = 100;
+    const MAX_MOVES: u32 = 999;
+
+    pub fn label(&self) -> &'static str {
+        return match self {
+            EditorField::BoardSize => "Size",
+            EditorField::Tool => "Tool",
+            EditorField::Goal => "Goal",
+            EditorField::Target => "Target",
+            EditorField::MaxMoves => "Moves",
+            EditorField::ThreeStars => "3 stars",
+            EditorField::TwoStars => "2 stars",
+            EditorField::Spawns => "Spawns",
+            EditorField::LastSpawn => "Last spawn",
+        };
+    }
+
+    pub fn display(&self, editor: &Editor) -> String {
+        let level = &editor.level;
+
+        return match self {
+            EditorField::BoardSize => format!("{0}x{0}", level.board_size()),
+            EditorField::Tool => format!("{:?}", editor.tool),
+            EditorField::Goal => match level.goal {
+                Goal::Tile(_) => "Tile",
+                Goal::ClearTo(_) => "Clear",
+                Goal::Score(_) => "Score",
+            }
+            .to_string(),
+            EditorField::Target => match level.goal {
+                Goal::Tile(value) | Goal::Score(value) => value.to_string(),
+                Goal::ClearTo(count) => count.to_string(),
+            },
+            EditorField::MaxMoves => level.max_moves.to_string(),
+            EditorField::ThreeStars => format!("<= {}", level.three_stars),
+            EditorField::TwoStars => format!("<= {}", level.two_stars),
+            EditorField::Spawns => {
+                let skipped = level.spawns.len().saturating_sub(Self::SHOWN_SPAWNS);
+                let shown = level.spawns[skipped..]
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                match (level.spawns.len(), skipped) {
+                    (0, _) => "None".to_string(),
+                    (_, 0) => shown,
+                    _ => format!(".. {shown}"),
+                }
+            }
+            EditorField::LastSpawn => level
+                .spawns
+                .last()
+                .map_or("-".to_string(), |value| value.to_string()),
+        };
+    }
+
+    /// Steps the field up or down, the tool ignores the sign of `step`.
+    pub fn adjust(&self, editor: &mut Editor, step: i8) {
+        let level = &mut editor.level;
+        let scale = |value: u32, min: u32| {
+            if step > 0 {
+                (value * 2).min(Editor::MAX_TILE)
+            } else {
+                (value / 2).max(min)
+            }
+        };
+
+        match self {
+            EditorField::BoardSize => {
+                let size = level
+                    .board_size()
+                    .saturating_add_signed(step)
+                    .clamp(*Settings::BOARD_SIZES.start(), *Settings::BOARD_SIZES.end());
+                Editor::resize(level, size);
+            }
+            EditorField::Tool => {
+                editor.tool = match editor.tool {
+                    EditorTool::Tiles => EditorTool::Blockers,
+                    EditorTool::Blockers => EditorTool::Tiles,
+                };
+            }
+            EditorField::Goal => {
+                let goals = [Goal::Tile(16), Goal::ClearTo(1), Goal::Score(1000)];
+                let index = goals
+                    .iter()
+                    .position(|goal| {
+                        std::mem::discriminant(goal) == std::mem::discriminant(&level.goal)
+                    })
+                    .map_or(0, |index| index as isize + step as isize);
+                level.goal = goals[index.rem_euclid(goals.len() as isize) as usize];
+            }
+            EditorField::Target => {
+                level.goal = match level.goal {
+                    Goal::Tile(value) => Goal::Tile(scale(value, 4)),
+                    Goal::ClearTo(count) => Goal::ClearTo(
+                        count
+                            .saturating_add_signed(step as isize)
+                            .clamp(1, level.board.len().pow(2)),
+                    ),
+                    Goal::Score(score) => Goal::Score(
+                        score
+                            .saturating_add_signed(step as i32 * Self::SCORE_STEP as i32)
+                            .max(Self::SCORE_STEP),
+                    ),
+                };
+            }
+            EditorField::MaxMoves => {
+                level.max_moves = level
+                    .max_moves
+                    .saturating_add_signed(step as i32)
+                    .clamp(1, Self::MAX_MOVES);
+            }
+            EditorField::ThreeStars => {
+                level.three_stars = level.three_stars.saturating_add_signed(step as i32);
+            }
+            EditorField::TwoStars => {
+                level.two_stars = level.two_stars.saturating_add_signed(step as i32);
+            }
+            EditorField::Spawns => {
+                if step > 0 {
+                    level.spawns.push(2);
+                } else {
+                    level.spawns.pop();
+                }
+            }
+            EditorField::LastSpawn => {
+                if let Some(value) = level.spawns.last_mut() {
+                    *value = scale(*value, 2);
+                }
+            }
+        }
+
+        // Fewer moves always earn more stars
+        level.two_stars = level.two_stars.clamp(1, level.max_moves);
+        level.three_stars = level.three_stars.clamp(1, level.two_stars);
+    }
+}
+
+impl EditorPlugin {
+    fn on_enter_editor(mut editor: ResMut<Editor>, mut game: ResMut<Game>) {
+        // Editing replaces the board of the running game
+        game.in_progress = false;
+        editor.set_changed();
+    }
+
+    /// Shows the edited level on the board whenever it changes.
+    fn sys_render_level(
+        mut commands: Commands,
+        editor: Res<Editor>,
+        query_board: Query<(Entity, &Board)>,
+        tiles: Query<Entity, With<tile::Position>>,
+        font_spec: Res<FontSpec>,
+        windows: Query<&Window, With<PrimaryWindow>>,
+    ) {
+        if !editor.is_changed() {
+            return;
+        }
+
+        for entity in tiles.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+
+        let level = &editor.level;
+        let boards = Board::lay_out(
+            &mut commands,
+            query_board
+                .iter()
+                .map(|(entity, board)| (entity, board.clone())),
+            level.board_size(),
+            Grid::Square,
+            1,
+            windows.get_single().ok(),
+        );
+
+        for (board_entity, board) in boards {
+            for position in level.blockers() {
+                board.spawn_blocker(&mut commands, board_entity, position);
+            }
+            for (position, points) in level.tiles() {
+                board.spawn_tile(&mut commands, board_entity, &font_spec, position, points);
+            }
+        }
+    }
+
+    fn sys_edit_on_click(
+        mut editor: ResMut<Editor>,
+        mouse: Res<Input<MouseButton>>,
+        windows: Query<&Window, With<PrimaryWindow>>,
+        cameras: Query<(&Camera, &GlobalTransform)>,
+        query_board: Query<&Board>,
+        interactions: Query<&Interaction>,
+    ) {
+        let left = mouse.just_pressed(MouseButton::Left);
+        let right = mouse.just_pressed(MouseButton::Right);
+        if !left && !right {
+            return;
+        }
+
+        // Clicks on the editor panel
+        if interactions
+            .iter()
+            .any(|&interaction| interaction != Interaction::None)
+        {
+            return;
+        }
+
+        let Some(cursor) = windows
+            .get_single()
+            .ok()
+            .and_then(|window| window.cursor_position())
+        else {
+            return;
+        };
+        let Some(point) = cameras.iter().find_map(|(camera, transform)| {
+            camera
+                .viewport_to_world(transform, cursor)
+                .map(|ray| ray.origin.truncate())
+        }) else {
+            return;
+        };
+        let Some(position) = query_board.iter().find_map(|board| board.cell_at(point)) else {
+            return;
+        };
+
+        if left {
+            editor.click(position);
+        } else {
+            editor.clear_cell(position);
+        }
+    }
+}
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Editor::load())
+            .add_system(EditorPlugin::on_enter_editor.in_schedule(OnEnter(GameState::Editor)))
+            .add_systems(
+                (
+                    EditorPlugin::sys_edit_on_click,
+                    EditorPlugin::sys_render_level,
+                    Board::render_tile_points,
+                )
+                    .chain()
+                    .in_set(OnUpdate(GameState::Editor)),
+            );
+    }
+}
@@ -0,0 +1,661 @@
+//! A headless, Gym-style environment for reinforcement learning.
+//!
+//! It doesn't need an `App`: the board lives in plain vectors and every step goes through
+//! [`BoardShiftDirection::shift`], so the agent plays with the exact same merge rules as
+//! `sys_handle_board_shift_on_keypress`.
+//!
+//! ```
+//! use bevy_2048::{
+//!     board::BoardShiftDirection,
+//!     env::{Env, EnvConfig},
+//! };
+//!
+//! let mut env = Env::new(EnvConfig::default());
+//! let mut observation = env.reset(42);
+//! assert_eq!(observation.shape, [1, 4, 4]);
+//!
+//! // Every valid move adds a tile, so cycling through the directions ends the game
+//! for action in BoardShiftDirection::ALL.into_iter().cycle() {
+//!     let (next, _reward, done, info) = env.step(action);
+//!     observation = next;
+//!     if done {
+//!         assert!(info.score > 0);
+//!         break;
+//!     }
+//! }
+//! ```
+use bevy::utils::HashMap;
+use rand::{rngs::StdRng, seq::IteratorRandom, SeedableRng};
+
+use crate::{
+    board::{tile, Board, BoardShiftDirection},
+    game,
+    merge::MergeRule,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct EnvConfig {
+    pub board_size: u8,
+    pub merge_rule: MergeRule,
+    /// The chance of a new tile being a 4 instead of a 2.
+    pub spawn_four_probability: f32,
+    pub reward: RewardConfig,
+    pub observation: ObservationEncoding,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        return Self {
+            board_size: 4,
+            merge_rule: MergeRule::Classic,
+            spawn_four_probability: 0.,
+            reward: RewardConfig::default(),
+            observation: ObservationEncoding::Log2,
+        };
+    }
+}
+
+/// Reward shaping, the reward of a step is the sum of every term.
+#[derive(Debug, Clone, Copy)]
+pub struct RewardConfig {
+    /// Multiplier of the points scored by the step.
+    pub score_delta: f32,
+    /// Multiplier of the [`MergeRule::rank`] of every merged tile, its `log2` with the classic
+    /// rule.
+    pub log_merge: f32,
+    /// Reward of a step that didn't change the board, use a negative value as a penalty.
+    pub invalid_move: f32,
+    /// Reward of the step that ends the game.
+    pub game_over: f32,
+}
+
+impl Default for RewardConfig {
+    fn default() -> Self {
+        return Self {
+            score_delta: 1.,
+            log_merge: 0.,
+            invalid_move: 0.,
+            game_over: 0.,
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObservationEncoding {
+    /// One channel with the tile values, `0.` for empty cells.
+    Raw,
+    /// One channel with the [`MergeRule::rank`] of the tile values, their `log2` with the
+    /// classic rule and `0.` for empty cells.
+    Log2,
+    /// One binary plane per rank: plane `0` marks empty cells, plane `n` the tiles of rank `n`,
+    /// so `2^n` with the classic rule.
+    /// Tiles above the last plane are clamped into it, [`Env::new`] panics without any plane.
+    OneHot { planes: usize },
+}
+
+impl ObservationEncoding {
+    pub fn channels(&self) -> usize {
+        return match self {
+            ObservationEncoding::Raw | ObservationEncoding::Log2 => 1,
+            ObservationEncoding::OneHot { planes } => *planes,
+        };
+    }
+}
+
+/// A `[channels, height, width]` tensor stored in row major order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub shape: [usize; 3],
+    pub data: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Info {
+    pub score: u32,
+    pub max_tile: u32,
+    pub moves: u32,
+    /// Whether the action changed the board.
+    pub valid_move: bool,
+    /// The last observation of a game that [`VecEnv::step`] ended and reset, the other fields
+    /// are still about that game.
+    pub final_observation: Option<Observation>,
+}
+
+pub struct Env {
+    config: EnvConfig,
+    board: Board,
+    /// Indexed by `y * size + x`.
+    cells: Vec<Option<tile::Points>>,
+    rng: StdRng,
+    score: u32,
+    moves: u32,
+    done: bool,
+}
+
+impl Env {
+    pub fn new(config: EnvConfig) -> Self {
+        assert!(
+            config.observation.channels() > 0,
+            "The observation needs at least one plane"
+        );
+        let board = Board::new(config.board_size);
+
+        return Self {
+            config,
+            cells: vec![None; board.total_tiles() as usize],
+            board,
+            rng: StdRng::seed_from_u64(0),
+            score: 0,
+            moves: 0,
+            done: false,
+        };
+    }
+
+    pub fn config(&self) -> &EnvConfig {
+        return &self.config;
+    }
+
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.cells.iter_mut().for_each(|cell| *cell = None);
+        self.score = 0;
+        self.moves = 0;
+        self.done = false;
+
+        self.spawn_tiles(2);
+
+        return self.observe();
+    }
+
+    pub fn step(&mut self, action: BoardShiftDirection) -> (Observation, f32, bool, Info) {
+        if self.done {
+            return (self.observe(), 0., true, self.info(false));
+        }
+
+        let result = action.shift(
+            self.board.grid,
+            self.board.size,
+            self.config.merge_rule,
+            &self.tiles(),
+            &[],
+        );
+        if !result.has_changed() {
+            return (
+                self.observe(),
+                self.config.reward.invalid_move,
+                false,
+                self.info(false),
+            );
+        }
+
+        let mut cells = vec![None; self.cells.len()];
+        for tile_move in result.moves.iter() {
+            cells[self.index(&tile_move.to)] = self.cells[tile_move.id];
+        }
+        for merge in result.merges.iter() {
+            cells[self.index(&merge.position)] = Some(merge.points);
+        }
+        self.cells = cells;
+
+        let gained = result.score();
+        self.score += gained;
+        self.moves += 1;
+        self.spawn_tiles(1);
+        self.done = !self.has_move();
+
+        let reward = &self.config.reward;
+        let mut value = reward.score_delta * gained as f32
+            + reward.log_merge
+                * result
+                    .merges
+                    .iter()
+                    .map(|merge| self.config.merge_rule.rank(merge.points.value) as f32)
+                    .sum::<f32>();
+        if self.done {
+            value += reward.game_over;
+        }
+
+        return (self.observe(), value, self.done, self.info(true));
+    }
+
+    /// The actions that would change the board, in the order of [`BoardShiftDirection::ALL`].
+    pub fn valid_actions(&self) -> [bool; 4] {
+        let tiles = self.tiles();
+        return BoardShiftDirection::ALL.map(|direction| {
+            direction
+                .shift(
+                    self.board.grid,
+                    self.board.size,
+                    self.config.merge_rule,
+                    &tiles,
+                    &[],
+                )
+                .has_changed()
+        });
+    }
+
+    pub fn observe(&self) -> Observation {
+        let size = self.board.size as usize;
+        let channels = self.config.observation.channels();
+        let mut data = vec![0.; channels * self.cells.len()];
+
+        for (index, cell) in self.cells.iter().enumerate() {
+            let rank = cell.map_or(0, |points| self.config.merge_rule.rank(points.value));
+            match self.config.observation {
+                ObservationEncoding::Raw => {
+                    data[index] = cell.map_or(0., |points| points.value as f32);
+                }
+                ObservationEncoding::Log2 => {
+                    data[index] = rank as f32;
+                }
+                ObservationEncoding::OneHot { planes } => {
+                    let plane = rank as usize;
+                    data[plane.min(planes - 1) * self.cells.len() + index] = 1.;
+                }
+            }
+        }
+
+        return Observation {
+            shape: [channels, size, size],
+            data,
+        };
+    }
+
+    pub fn info(&self, valid_move: bool) -> Info {
+        return Info {
+            score: self.score,
+            max_tile: self
+                .cells
+                .iter()
+                .flatten()
+                .map(|points| points.value)
+                .max()
+                .unwrap_or(0),
+            moves: self.moves,
+            valid_move,
+            final_observation: None,
+        };
+    }
+
+    pub fn is_done(&self) -> bool {
+        return self.done;
+    }
+
+    fn index(&self, position: &tile::Position) -> usize {
+        return position.y as usize * self.board.size as usize + position.x as usize;
+    }
+
+    fn tiles(&self) -> Vec<(usize, tile::Position, tile::Points)> {
+        let size = self.board.size as usize;
+        return self
+            .cells
+            .iter()
+            .enumerate()
+            .filter_map(|(index, cell)| {
+                let position = tile::Position {
+                    x: (index % size) as u8,
+                    y: (index / size) as u8,
+                    z: 0,
+                };
+                cell.map(|points| (index, position, points))
+            })
+            .collect();
+    }
+
+    fn spawn_tiles(&mut self, amount: usize) {
+        let empty = self
+            .cells
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| cell.is_none())
+            .map(|(index, _)| index)
+            .choose_multiple(&mut self.rng, amount);
+
+        for index in empty {
+            self.cells[index] = Some(
+                self.config
+                    .merge_rule
+                    .spawn_points(&mut self.rng, self.config.spawn_four_probability),
+            );
+        }
+    }
+
+    fn has_move(&self) -> bool {
+        if self.cells.iter().any(Option::is_none) {
+            return true;
+        }
+
+        let map: HashMap<tile::Position, tile::Points> = self
+            .tiles()
+            .into_iter()
+            .map(|(_, position, points)| (position, points))
+            .collect();
+
+        return game::has_move(
+            self.board.grid,
+            self.board.size,
+            self.config.merge_rule,
+            &map,
+        );
+    }
+}
+
+/// Runs many [`Env`]s in lockstep with batched observations.
+///
+/// Finished environments are reset in the step that ends their game: it returns `done` and the
+/// first observation of the next game, the [`Info`] keeps the end of the finished one.
+pub struct VecEnv {
+    envs: Vec<Env>,
+    next_seed: u64,
+}
+
+/// A `[envs, channels, height, width]` tensor stored in row major order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchObservation {
+    pub shape: [usize; 4],
+    pub data: Vec<f32>,
+}
+
+impl VecEnv {
+    pub fn new(config: EnvConfig, count: usize) -> Self {
+        return Self {
+            envs: (0..count).map(|_| Env::new(config)).collect(),
+            next_seed: 0,
+        };
+    }
+
+    pub fn len(&self) -> usize {
+        return self.envs.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.envs.is_empty();
+    }
+
+    pub fn envs(&self) -> &[Env] {
+        return &self.envs;
+    }
+
+    /// Resets every environment, the `i`th one with `seed + i`.
+    pub fn reset(&mut self, seed: u64) -> BatchObservation {
+        self.next_seed = seed;
+        let observations = self
+            .envs
+            .iter_mut()
+            .map(|env| {
+                let observation = env.reset(self.next_seed);
+                self.next_seed += 1;
+                observation
+            })
+            .collect::<Vec<_>>();
+
+        return self.batch(observations);
+    }
+
+    pub fn step(
+        &mut self,
+        actions: &[BoardShiftDirection],
+    ) -> (BatchObservation, Vec<f32>, Vec<bool>, Vec<Info>) {
+        assert_eq!(actions.len(), self.envs.len(), "One action per environment");
+
+        let mut observations = Vec::with_capacity(self.envs.len());
+        let mut rewards = Vec::with_capacity(self.envs.len());
+        let mut dones = Vec::with_capacity(self.envs.len());
+        let mut infos = Vec::with_capacity(self.envs.len());
+
+        for (env, &action) in self.envs.iter_mut().zip(actions) {
+            let (mut observation, reward, done, mut info) = env.step(action);
+            if done {
+                info.final_observation = Some(observation);
+                observation = env.reset(self.next_seed);
+                self.next_seed += 1;
+            }
+
+            observations.push(observation);
+            rewards.push(reward);
+            dones.push(done);
+            infos.push(info);
+        }
+
+        return (self.batch(observations), rewards, dones, infos);
+    }
+
+    fn batch(&self, observations: Vec<Observation>) -> BatchObservation {
+        let [channels, height, width] = observations
+            .first()
+            .map_or([0, 0, 0], |observation| observation.shape);
+
+        return BatchObservation {
+            shape: [observations.len(), channels, height, width],
+            data: observations.into_iter().flat_map(|o| o.data).collect(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::prelude::default;
+
+    /// An environment with `cells[y][x]` on its board, from the bottom row up and `0` for empty cells.
+    fn env_with(config: EnvConfig, cells: [[u32; 4]; 4]) -> Env {
+        let mut env = Env::new(config);
+        env.cells = cells
+            .into_iter()
+            .flatten()
+            .map(|value| (value > 0).then(|| tile::Points::new(value)))
+            .collect();
+        return env;
+    }
+
+    fn values(env: &Env) -> Vec<u32> {
+        return env
+            .cells
+            .iter()
+            .map(|cell| cell.map_or(0, |points| points.value))
+            .collect();
+    }
+
+    #[test]
+    fn reset_spawns_two_tiles() {
+        let mut env = Env::new(EnvConfig::default());
+        let observation = env.reset(3);
+
+        assert_eq!(observation.shape, [1, 4, 4]);
+        assert_eq!(
+            observation
+                .data
+                .iter()
+                .filter(|&&value| value == 1.)
+                .count(),
+            2
+        );
+        assert_eq!(env.info(false).score, 0);
+        assert!(!env.is_done());
+        assert_eq!(observation, env.reset(3));
+    }
+
+    #[test]
+    fn step_merges_and_rewards_the_score() {
+        let config = EnvConfig {
+            reward: RewardConfig {
+                score_delta: 1.,
+                log_merge: 10.,
+                ..default()
+            },
+            ..default()
+        };
+        let mut env = env_with(config, [[2, 2, 0, 0], [0; 4], [0; 4], [0; 4]]);
+
+        let (_, reward, done, info) = env.step(BoardShiftDirection::Left);
+
+        assert_eq!(reward, 4. + 10. * 2.);
+        assert!(!done);
+        assert_eq!(info.score, 4);
+        assert_eq!(info.moves, 1);
+        assert!(info.valid_move);
+        assert_eq!(values(&env)[0], 4);
+        // The merged tile and a new one
+        assert_eq!(env.cells.iter().flatten().count(), 2);
+    }
+
+    #[test]
+    fn invalid_move_keeps_the_board() {
+        let config = EnvConfig {
+            reward: RewardConfig {
+                invalid_move: -1.,
+                ..default()
+            },
+            ..default()
+        };
+        let mut env = env_with(config, [[2, 0, 0, 0], [0; 4], [0; 4], [0; 4]]);
+
+        let (_, reward, done, info) = env.step(BoardShiftDirection::Left);
+
+        assert_eq!(reward, -1.);
+        assert!(!done);
+        assert!(!info.valid_move);
+        assert_eq!(info.moves, 0);
+        assert_eq!(env.valid_actions(), [false, true, true, false]);
+    }
+
+    #[test]
+    fn last_move_ends_the_game() {
+        let config = EnvConfig {
+            reward: RewardConfig {
+                game_over: -100.,
+                ..default()
+            },
+            ..default()
+        };
+        let mut env = env_with(
+            config,
+            [[2, 2, 8, 16], [2, 4, 8, 4], [4, 2, 4, 2], [2, 4, 2, 4]],
+        );
+
+        let (_, reward, done, _) = env.step(BoardShiftDirection::Left);
+
+        // The new 2 fills the only empty cell
+        assert_eq!(values(&env)[..4], [4, 8, 16, 2]);
+        assert_eq!(reward, 4. - 100.);
+        assert!(done);
+        assert_eq!(
+            env.step(BoardShiftDirection::Right),
+            (env.observe(), 0., true, env.info(false))
+        );
+    }
+
+    #[test]
+    fn one_hot_clamps_into_the_last_plane() {
+        let config = EnvConfig {
+            observation: ObservationEncoding::OneHot { planes: 3 },
+            ..default()
+        };
+        let env = env_with(config, [[2, 4, 8, 0], [0; 4], [0; 4], [0; 4]]);
+        let observation = env.observe();
+
+        assert_eq!(observation.shape, [3, 4, 4]);
+        let plane = |plane: usize| &observation.data[plane * 16..plane * 16 + 4];
+        assert_eq!(plane(0), [0., 0., 0., 1.]);
+        assert_eq!(plane(1), [1., 0., 0., 0.]);
+        assert_eq!(plane(2), [0., 1., 1., 0.]);
+    }
+
+    #[test]
+    fn fibonacci_tiles_get_a_plane_each() {
+        let config = EnvConfig {
+            merge_rule: MergeRule::Fibonacci,
+            observation: ObservationEncoding::OneHot { planes: 5 },
+            reward: RewardConfig {
+                score_delta: 0.,
+                log_merge: 1.,
+                ..default()
+            },
+            ..default()
+        };
+        let mut env = env_with(config, [[1, 2, 3, 5], [0; 4], [0; 4], [0; 4]]);
+        let observation = env.observe();
+
+        let plane = |plane: usize| &observation.data[plane * 16..plane * 16 + 4];
+        assert_eq!(plane(0), [0.; 4]);
+        assert_eq!(plane(1), [1., 0., 0., 0.]);
+        assert_eq!(plane(2), [0., 1., 0., 0.]);
+        assert_eq!(plane(3), [0., 0., 1., 0.]);
+        assert_eq!(plane(4), [0., 0., 0., 1.]);
+
+        // 1 and 2 make a 3, 3 and 5 an 8
+        let (_, reward, _, _) = env.step(BoardShiftDirection::Left);
+        assert_eq!(values(&env)[..2], [3, 8]);
+        assert_eq!(reward, 3. + 5.);
+    }
+
+    #[test]
+    #[should_panic]
+    fn one_hot_needs_a_plane() {
+        Env::new(EnvConfig {
+            observation: ObservationEncoding::OneHot { planes: 0 },
+            ..default()
+        });
+    }
+
+    #[test]
+    fn vec_env_is_seeded() {
+        let actions = [BoardShiftDirection::Left, BoardShiftDirection::Up];
+        let play = || {
+            let mut envs = VecEnv::new(EnvConfig::default(), 2);
+            let mut steps = vec![envs.reset(7)];
+            for action in BoardShiftDirection::ALL.into_iter().cycle().take(200) {
+                steps.push(envs.step(&[action, actions[steps.len() % 2]]).0);
+            }
+            return (steps, envs.envs().iter().map(values).collect::<Vec<_>>());
+        };
+
+        assert_eq!(play(), play());
+
+        // The second environment is seeded with the next seed
+        let mut single = Env::new(EnvConfig::default());
+        let mut envs = VecEnv::new(EnvConfig::default(), 2);
+        let batch = envs.reset(7);
+        assert_eq!(batch.shape, [2, 1, 4, 4]);
+        assert_eq!(batch.data[16..], single.reset(8).data);
+    }
+
+    #[test]
+    fn vec_env_applies_every_action() {
+        let config = EnvConfig {
+            board_size: 3,
+            ..default()
+        };
+        let mut envs = VecEnv::new(config, 2);
+        envs.reset(5);
+        let mut singles = [Env::new(config), Env::new(config)];
+        singles[0].reset(5);
+        singles[1].reset(6);
+        let mut next_seed = 7;
+
+        for (step, action) in BoardShiftDirection::ALL
+            .into_iter()
+            .cycle()
+            .take(400)
+            .enumerate()
+        {
+            let actions = [action, BoardShiftDirection::ALL[step % 3]];
+            let (batch, rewards, dones, infos) = envs.step(&actions);
+
+            for (index, single) in singles.iter_mut().enumerate() {
+                let (mut observation, reward, done, mut info) = single.step(actions[index]);
+                if done {
+                    info.final_observation = Some(observation);
+                    observation = single.reset(next_seed);
+                    next_seed += 1;
+                }
+                assert_eq!((rewards[index], dones[index]), (reward, done));
+                assert_eq!(infos[index], info);
+                assert_eq!(batch.data[index * 9..(index + 1) * 9], observation.data);
+            }
+        }
+
+        // Games were played to the end and restarted
+        assert!(next_seed > 9);
+    }
+}
@@ -1,74 +1,712 @@
-use bevy::{prelude::*, utils::HashMap};
+use std::{collections::VecDeque, time::Duration};
 
-use crate::{assets, board};
+use bevy::{prelude::*, utils::HashMap, window::PrimaryWindow};
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
+use crate::{
+    assets, board,
+    bot::{Bot, Difficulty},
+    daily,
+    merge::MergeRule,
+    net, puzzle, race,
+    settings::{KeyBinding, Settings},
+    versus::{self, Contender},
+};
+
+/// The session shared by the boards, each keeps its own score in a [`BoardGame`].
 #[derive(Default, Resource)]
 pub struct Game {
+    /// The best score of each mode by its [`GameMode::name`], as in the [`crate::stats::GameRecord`]s.
+    pub best_scores: HashMap<String, u32>,
+    /// Time spent in [`GameState::Playing`], so pausing freezes it.
+    pub elapsed: Duration,
+    /// Whether there is a started game that can be continued from the main menu.
+    pub in_progress: bool,
+    /// How many moves have been taken back in this game.
+    pub undos: u32,
+    pub seed: u64,
+}
+
+impl Game {
+    /// Reaching a tile of this value wins a game with the [`MergeRule::Classic`] rule.
+    pub const WINNING_VALUE: u32 = 2048;
+
+    pub fn best_score(&self, mode: GameMode) -> u32 {
+        return self.best_scores.get(&mode.name()).copied().unwrap_or(0);
+    }
+
+    pub fn record_score(&mut self, mode: &str, score: u32) {
+        let best = self.best_scores.entry(mode.to_string()).or_default();
+        *best = (*best).max(score);
+    }
+}
+
+/// The game played on a board entity.
+#[derive(Component, Debug, Clone)]
+pub struct BoardGame {
     pub score: u32,
-    pub best_score: u32,
+    /// How many moves changed the board.
+    pub moves: u32,
+    /// Seeded with [`Game::seed`], so a game can be replayed and boards of the same
+    /// seed get the same spawns.
+    pub rng: StdRng,
+    /// Whether the board is full without any merge left.
+    pub over: bool,
+    /// Whether a tile reached the winning value of the rule, see [`board::BoardWonEvent`].
+    pub won: bool,
+}
+
+impl BoardGame {
+    pub fn new(seed: u64) -> Self {
+        return Self {
+            score: 0,
+            moves: 0,
+            rng: StdRng::seed_from_u64(seed),
+            over: false,
+            won: false,
+        };
+    }
 }
 
+/// The board of a single player, which the undo history, the records and the score
+/// header follow. Versus, bot and remote boards don't match it.
+pub type SoloBoard = (
+    With<BoardGame>,
+    Without<Contender>,
+    Without<Bot>,
+    Without<net::Remote>,
+);
+
 #[derive(Default, Debug, Clone, Eq, PartialEq, Hash, bevy::prelude::States)]
 pub enum GameState {
     #[default]
+    MainMenu,
+    Modes,
+    Settings,
+    Statistics,
+    Leaderboard,
+    Achievements,
+    DailyResults,
+    Levels,
+    Editor,
+    /// Waiting for the other player of a [`GameMode::Lan`] game.
+    Lobby,
     Playing,
+    Paused,
     GameOver,
 }
 
-pub fn check_game_over(
-    tiles: Query<(&board::tile::Position, &board::tile::Points)>,
-    query_board: Query<&board::Board>,
-    mut game_state: ResMut<NextState<GameState>>,
+/// The board before a move.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub tiles: Vec<(board::tile::Position, board::tile::Points)>,
+    pub score: u32,
+}
+
+/// The last moves of the current game, most recent last, for undoing them.
+#[derive(Resource, Default)]
+pub struct History {
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl History {
+    /// Records a snapshot, forgetting the oldest ones beyond `limit`.
+    pub fn push(&mut self, snapshot: Snapshot, limit: usize) {
+        self.snapshots.push_back(snapshot);
+        while self.snapshots.len() > limit {
+            self.snapshots.pop_front();
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<Snapshot> {
+        return self.snapshots.pop_back();
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.snapshots.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.snapshots.is_empty();
+    }
+}
+
+pub fn sys_undo_on_keypress(
+    mut commands: Commands,
+    input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    mut history: ResMut<History>,
+    mut game: ResMut<Game>,
+    tile_entities: Query<(Entity, &Parent), With<board::tile::Points>>,
+    mut query_board: Query<(Entity, &board::Board, &mut BoardGame), SoloBoard>,
+    font_spec: Res<assets::FontSpec>,
 ) {
-    let board = query_board.single();
+    if !settings.key_bindings.just_pressed(KeyBinding::Undo, &input) {
+        return;
+    }
 
-    if tiles.iter().len() != board.total_tiles() as usize {
+    // The history is kept for the board of a single player
+    let Ok((board_entity, board, mut board_game)) = query_board.get_single_mut() else {
+        return;
+    };
+    let Some(snapshot) = history.pop() else {
         return;
+    };
+
+    for (entity, parent) in tile_entities.iter() {
+        if parent.get() == board_entity {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    for &(position, points) in snapshot.tiles.iter() {
+        board.spawn_tile(&mut commands, board_entity, &font_spec, position, points);
     }
 
-    let map: HashMap<&board::tile::Position, &board::tile::Points> = tiles.iter().collect();
+    board_game.score = snapshot.score;
+    game.undos += 1;
+}
+
+/// The rule set of the next game, picked on the modes screen.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GameMode {
+    #[default]
+    Classic,
+    /// Everyone gets the same spawns on the same day, see [`daily`].
+    Daily,
+    /// The best score before the time runs out.
+    TimeAttack { minutes: u8 },
+    /// The fastest time to reach the target tile.
+    Sprint { target: u32 },
+    /// The best score within a number of moves.
+    MoveLimit { moves: u32 },
+    /// Cells that tiles can't enter are placed on the board at the start.
+    Blockers { layout: board::BlockerLayout },
+    /// Other tiles merge together, see [`MergeRule`].
+    Merge { rule: MergeRule },
+    /// A handcrafted level, by its index in [`puzzle::Level::BUILT_IN`].
+    Puzzle { level: u8 },
+    /// Play-tests the level of the [`crate::editor::Editor`].
+    Custom,
+    /// Two players on the same keyboard race on boards side by side, see [`versus`].
+    ///
+    /// They play with WASD and the arrow keys, see [`versus::Controls`].
+    Versus,
+    /// Bots play [`GameMode::GALLERY_BOARDS`] games side by side, see [`crate::bot`].
+    Gallery,
+    /// The player races a bot on the same seed, see [`crate::race`].
+    Race { difficulty: Difficulty },
+    /// The player races another one over the network, see [`net`].
+    Lan,
+    /// A hexagon of hexagonal cells that shift in six directions, see [`board::Grid::Hex`].
+    Hex,
+    /// Layers of a cube that also shift in and out, see [`board::Grid::Cube`].
+    Cube,
+    /// Tiles slide past an edge and come back on the other side, see [`board::Grid::Torus`].
+    Wrap,
+    /// Special tiles spawn among the numbers, see [`board::tile::Kind`].
+    PowerUps,
+    /// Watches the game of another instance, see [`crate::spectate`].
+    Spectate { rule: MergeRule },
+}
 
-    const NEIGHBOR_POINTS: [(i8, i8); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+impl GameMode {
+    pub const GALLERY_BOARDS: u8 = 4;
+    pub const POWER_UP_PROBABILITY: f32 = 0.1;
 
-    let board_axis_range = 0..(board.size as i8);
+    pub const ALL: [GameMode; 18] = [
+        GameMode::Classic,
+        GameMode::Daily,
+        GameMode::TimeAttack { minutes: 3 },
+        GameMode::TimeAttack { minutes: 5 },
+        GameMode::Sprint { target: 512 },
+        GameMode::Sprint { target: 1024 },
+        GameMode::Sprint { target: 2048 },
+        GameMode::MoveLimit { moves: 100 },
+        GameMode::MoveLimit { moves: 250 },
+        GameMode::Blockers {
+            layout: board::BlockerLayout::Random(2),
+        },
+        GameMode::Blockers {
+            layout: board::BlockerLayout::Corners,
+        },
+        GameMode::Merge {
+            rule: MergeRule::Fibonacci,
+        },
+        GameMode::Merge {
+            rule: MergeRule::PowersOfThree,
+        },
+        GameMode::Merge {
+            rule: MergeRule::Threes,
+        },
+        GameMode::Hex,
+        GameMode::Cube,
+        GameMode::Wrap,
+        GameMode::PowerUps,
+    ];
 
-    let has_move = tiles.iter().any(|(pos, value)| {
-        NEIGHBOR_POINTS
-            .iter()
-            .filter_map(|(x2, y2)| {
-                let new_x = pos.x as i8 - x2;
-                let new_y = pos.y as i8 - y2;
-
-                if !board_axis_range.contains(&new_x) || !board_axis_range.contains(&new_y) {
-                    return None;
-                };
-
-                map.get(&board::tile::Position {
-                    x: new_x.try_into().unwrap(),
-                    y: new_y.try_into().unwrap(),
-                })
-            })
-            .any(|&v| v == value)
-    });
+    pub fn name(&self) -> String {
+        return match self {
+            GameMode::Classic => "Classic".to_string(),
+            GameMode::Daily => "Daily".to_string(),
+            GameMode::TimeAttack { minutes } => format!("Time {minutes} min"),
+            GameMode::Sprint { target } => format!("Sprint {target}"),
+            GameMode::MoveLimit { moves } => format!("{moves} Moves"),
+            GameMode::Blockers {
+                layout: board::BlockerLayout::Random(count),
+            } => format!("{count} Blockers"),
+            GameMode::Blockers {
+                layout: board::BlockerLayout::Corners,
+            } => "Corners".to_string(),
+            GameMode::Merge { rule } => rule.name().to_string(),
+            GameMode::Puzzle { level } => format!("Puzzle {}", level + 1),
+            GameMode::Custom => "Custom".to_string(),
+            GameMode::Versus => "Versus".to_string(),
+            GameMode::Gallery => "Bot Gallery".to_string(),
+            GameMode::Race { difficulty } => format!("Race {} AI", difficulty.name()),
+            GameMode::Lan => "LAN".to_string(),
+            GameMode::Hex => "Hex".to_string(),
+            GameMode::Cube => "Cube 4x4x4".to_string(),
+            GameMode::Wrap => "Wrap".to_string(),
+            GameMode::PowerUps => "Power-ups".to_string(),
+            GameMode::Spectate { .. } => "Spectator".to_string(),
+        };
+    }
+
+    /// The time a [`GameMode::TimeAttack`] lasts.
+    pub fn time_limit(&self) -> Option<Duration> {
+        return match self {
+            GameMode::TimeAttack { minutes } => Some(Duration::from_secs(*minutes as u64 * 60)),
+            GameMode::Versus => Some(versus::ROUND_DURATION),
+            _ => None,
+        };
+    }
+
+    /// Whether the leaderboard ranks the fastest games instead of the best scores.
+    pub fn ranks_by_time(&self) -> bool {
+        return matches!(self, GameMode::Sprint { .. });
+    }
+
+    /// Whether a game reached the goal of its mode, only [`GameMode::Sprint`] has one.
+    pub fn is_completed(&self, max_tile: u32) -> bool {
+        return match self {
+            GameMode::Sprint { target } => max_tile >= *target,
+            _ => true,
+        };
+    }
+
+    /// Whether the mode plays a [`puzzle::Level`] instead of a random board.
+    pub fn is_level(&self) -> bool {
+        return matches!(self, GameMode::Puzzle { .. } | GameMode::Custom);
+    }
+
+    /// How many boards are played side by side.
+    pub fn boards(&self) -> u8 {
+        return match self {
+            GameMode::Versus | GameMode::Race { .. } | GameMode::Lan => 2,
+            GameMode::Gallery => GameMode::GALLERY_BOARDS,
+            _ => 1,
+        };
+    }
+
+    pub fn grid(&self) -> board::Grid {
+        return match self {
+            GameMode::Hex => board::Grid::Hex,
+            GameMode::Cube => board::Grid::Cube,
+            GameMode::Wrap => board::Grid::Torus,
+            _ => board::Grid::Square,
+        };
+    }
+
+    /// The modes with their own rule keep the classic one.
+    pub fn merge_rule(&self) -> MergeRule {
+        return match self {
+            GameMode::Merge { rule } | GameMode::Spectate { rule } => *rule,
+            _ => MergeRule::Classic,
+        };
+    }
 
-    if has_move == false {
+    /// Modes with a shared seed ignore the settings that change the spawns.
+    pub fn board_size(&self, settings: &Settings) -> u8 {
+        return match self {
+            GameMode::Daily => daily::BOARD_SIZE,
+            GameMode::Lan => net::BOARD_SIZE,
+            GameMode::Hex => board::Grid::HEX_SIZE,
+            GameMode::Cube => board::Grid::CUBE_SIZE,
+            _ => settings.board_size,
+        };
+    }
+
+    pub fn spawn_four_probability(&self, settings: &Settings) -> f32 {
+        return match self {
+            GameMode::Daily => daily::SPAWN_FOUR_PROBABILITY,
+            GameMode::Lan => net::SPAWN_FOUR_PROBABILITY,
+            _ => settings.spawn_four_probability,
+        };
+    }
+
+    /// The chance of a new tile to be of a special [`board::tile::Kind`].
+    pub fn special_tile_probability(&self) -> f32 {
+        return match self {
+            GameMode::PowerUps => GameMode::POWER_UP_PROBABILITY,
+            _ => 0.,
+        };
+    }
+
+    pub fn undo_limit(&self, settings: &Settings) -> usize {
+        return match self {
+            // The scripted spawns of the levels and the attacks can't be taken back, nor can the
+            // time and moves of the modes that limit them
+            GameMode::Daily
+            | GameMode::TimeAttack { .. }
+            | GameMode::MoveLimit { .. }
+            | GameMode::Puzzle { .. }
+            | GameMode::Custom
+            | GameMode::Versus
+            | GameMode::Gallery
+            | GameMode::Race { .. }
+            | GameMode::Lan
+            | GameMode::Spectate { .. } => 0,
+            _ => settings.undo_limit as usize,
+        };
+    }
+
+    /// Whether the mode has its own tables on the leaderboard screen.
+    pub fn has_leaderboard(&self) -> bool {
+        return match self {
+            // The daily and puzzle results are kept apart
+            GameMode::Daily
+            | GameMode::Puzzle { .. }
+            | GameMode::Custom
+            | GameMode::Versus
+            | GameMode::Gallery
+            | GameMode::Race { .. }
+            | GameMode::Lan
+            | GameMode::Spectate { .. } => false,
+            _ => true,
+        };
+    }
+}
+
+/// Clears the board and starts a new game, entering [`GameState::Playing`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NewGameEvent {
+    /// Replays the spawn sequence of an earlier game, a random seed is picked when `None`.
+    pub seed: Option<u64>,
+}
+
+pub fn tick_timer(time: Res<Time>, mut game: ResMut<Game>) {
+    game.elapsed += time.delta();
+}
+
+pub fn on_game_over(mut game: ResMut<Game>) {
+    game.in_progress = false;
+}
+
+pub fn sys_toggle_pause_on_keypress(
+    input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    run_state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !settings
+        .key_bindings
+        .just_pressed(KeyBinding::Pause, &input)
+    {
+        return;
+    }
+
+    match run_state.0 {
+        GameState::Playing => next_state.set(GameState::Paused),
+        GameState::Paused => next_state.set(GameState::Playing),
+        GameState::GameOver => next_state.set(GameState::MainMenu),
+        // Menus handle escape themselves
+        GameState::MainMenu
+        | GameState::Modes
+        | GameState::Settings
+        | GameState::Statistics
+        | GameState::Leaderboard
+        | GameState::Achievements
+        | GameState::DailyResults
+        | GameState::Levels
+        | GameState::Editor
+        | GameState::Lobby => {}
+    }
+}
+
+/// Marks the stuck boards as over and ends the game once all of them are,
+/// see [`versus`] for the rounds that end sooner.
+pub fn check_game_over(
+    tiles: Query<(&board::tile::Position, &board::tile::Points, &Parent)>,
+    blockers: Query<&Parent, With<board::tile::Blocker>>,
+    mut query_board: Query<(Entity, &board::Board, &mut BoardGame)>,
+    game_mode: Res<GameMode>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    let mut all_over = true;
+    for (entity, board, mut board_game) in query_board.iter_mut() {
+        if !board_game.over && is_stuck(entity, board, game_mode.merge_rule(), &tiles, &blockers) {
+            board_game.over = true;
+        }
+        all_over &= board_game.over;
+    }
+
+    // The watched game goes on after an undo or a new game
+    let spectating = matches!(*game_mode, GameMode::Spectate { .. });
+    if all_over && !query_board.is_empty() && !spectating {
+        game_state.set(GameState::GameOver);
+    }
+}
+
+/// Whether the board `entity` is full and nothing on it can merge anymore.
+pub fn is_stuck(
+    entity: Entity,
+    board: &board::Board,
+    rule: MergeRule,
+    tiles: &Query<(&board::tile::Position, &board::tile::Points, &Parent)>,
+    blockers: &Query<&Parent, With<board::tile::Blocker>>,
+) -> bool {
+    let map: HashMap<board::tile::Position, board::tile::Points> = tiles
+        .iter()
+        .filter(|(_, _, parent)| parent.get() == entity)
+        .map(|(&pos, &points, _)| (pos, points))
+        .collect();
+    let blocked = blockers
+        .iter()
+        .filter(|parent| parent.get() == entity)
+        .count();
+
+    return map.len() + blocked == board.total_tiles() as usize
+        && !has_move(board.grid, board.size, rule, &map);
+}
+
+/// Ends the game once the time, target tile or move count of the [`GameMode`] is reached.
+pub fn check_mode_end(
+    mut game: ResMut<Game>,
+    game_mode: Res<GameMode>,
+    query_board: Query<&BoardGame, SoloBoard>,
+    tiles: Query<&board::tile::Points>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    let ended = match *game_mode {
+        GameMode::Classic
+        | GameMode::Daily
+        | GameMode::Blockers { .. }
+        | GameMode::Merge { .. }
+        | GameMode::Hex
+        | GameMode::Cube
+        | GameMode::Wrap
+        | GameMode::PowerUps => false,
+        // See `PuzzlePlugin::sys_check_goal` and `VersusPlugin::sys_check_round_end`
+        GameMode::Puzzle { .. } | GameMode::Custom | GameMode::Versus => false,
+        // Ends once every board is stuck
+        GameMode::Gallery | GameMode::Race { .. } | GameMode::Lan => false,
+        // Follows the watched game, which ends on its own
+        GameMode::Spectate { .. } => false,
+        GameMode::TimeAttack { .. } => {
+            let limit = game_mode.time_limit().unwrap_or_default();
+            if game.elapsed >= limit {
+                // Doesn't report the frame that went past the limit
+                game.elapsed = limit;
+                true
+            } else {
+                false
+            }
+        }
+        GameMode::Sprint { .. } => {
+            let max_tile = tiles
+                .iter()
+                .filter_map(|points| points.number())
+                .max()
+                .unwrap_or(0);
+            game_mode.is_completed(max_tile)
+        }
+        GameMode::MoveLimit { moves } => query_board
+            .get_single()
+            .is_ok_and(|board_game| board_game.moves >= moves),
+    };
+
+    if ended {
         game_state.set(GameState::GameOver);
     }
 }
 
+/// Whether a full board still has a line of neighbours that `rule` can merge, along one of
+/// the axes of the `grid`.
+pub fn has_move(
+    grid: board::Grid,
+    board_size: u8,
+    rule: MergeRule,
+    tiles: &HashMap<board::tile::Position, board::tile::Points>,
+) -> bool {
+    return tiles.keys().any(|&pos| {
+        grid.axes().iter().any(|axis| {
+            // The tiles from this one towards the axis, stopping at the edge of the board
+            // or, on a board that wraps, before coming back to this one
+            let group = std::iter::successors(Some(pos), |&pos| {
+                grid.neighbour(board_size, pos, axis.step())
+            })
+            .take(rule.group_size().min(board_size as usize))
+            .map_while(|pos| tiles.get(&pos).copied())
+            .collect::<Vec<_>>();
+
+            rule.merge_tiles(&group).is_some()
+        })
+    });
+}
+
 pub fn reset(
+    mut events: EventReader<NewGameEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
     mut commands: Commands,
     tile_entities: Query<Entity, With<board::tile::Position>>,
     mut game: ResMut<Game>,
-    query_board: Query<&board::Board>,
+    mut history: ResMut<History>,
+    query_board: Query<(Entity, &board::Board)>,
     font_spec: Res<assets::FontSpec>,
+    settings: Res<Settings>,
+    game_mode: Res<GameMode>,
+    levels: puzzle::Levels,
+    lan: Res<net::Lan>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut spawned_writer: EventWriter<board::TileSpawnedEvent>,
 ) {
+    let Some(event) = events.iter().last() else {
+        return;
+    };
+    // The board comes from the watched game, see `SpectatePlugin::sys_follow`
+    if matches!(*game_mode, GameMode::Spectate { .. }) {
+        next_state.set(GameState::Playing);
+        return;
+    }
+    // Only the host picks the seed of a LAN game, see `NetPlugin::sys_sync_new_games`
+    if *game_mode == GameMode::Lan && lan.role == Some(net::Role::Guest) && event.seed.is_none() {
+        next_state.set(GameState::Lobby);
+        return;
+    }
+
     for entity in tile_entities.iter() {
         commands.entity(entity).despawn_recursive();
     }
 
-    let board = query_board.single();
-    board.spawn_tiles(&mut commands, &font_spec, None, 2);
+    game.seed = event.seed.unwrap_or_else(|| match *game_mode {
+        GameMode::Daily => daily::seed(daily::today()),
+        _ => rand::random(),
+    });
+
+    let level = levels.of_mode(*game_mode);
+
+    let board_size = level.map_or(game_mode.board_size(&settings), puzzle::Level::board_size);
+    // The board size setting only applies to new games
+    let boards = board::Board::lay_out(
+        &mut commands,
+        query_board
+            .iter()
+            .map(|(entity, board)| (entity, board.clone())),
+        board_size,
+        game_mode.grid(),
+        game_mode.boards(),
+        windows.get_single().ok(),
+    );
+
+    let blockers = match (*game_mode, level) {
+        (GameMode::Blockers { layout }, _) => {
+            layout.positions(board_size, &mut StdRng::seed_from_u64(game.seed))
+        }
+        (_, Some(level)) => level.blockers(),
+        _ => Vec::new(),
+    };
+
+    for (column, (board_entity, board)) in boards.into_iter().enumerate() {
+        for &position in blockers.iter() {
+            board.spawn_blocker(&mut commands, board_entity, position);
+        }
+
+        // Versus, race and LAN boards start from the same seed, the bots of the gallery play
+        // different games
+        let mut board_game = BoardGame::new(match *game_mode {
+            GameMode::Gallery => game.seed.wrapping_add(column as u64),
+            _ => game.seed,
+        });
+        let rng = &mut board_game.rng;
+
+        match level {
+            Some(level) => {
+                for (position, points) in level.tiles() {
+                    spawned_writer.send(board::TileSpawnedEvent {
+                        entity: board.spawn_tile(
+                            &mut commands,
+                            board_entity,
+                            &font_spec,
+                            position,
+                            points,
+                        ),
+                        board: board_entity,
+                        position,
+                        points,
+                    });
+                }
+            }
+            None => spawned_writer.send_batch(board.spawn_tiles(
+                &mut commands,
+                board_entity,
+                &font_spec,
+                &blockers,
+                2,
+                game_mode.merge_rule(),
+                game_mode.spawn_four_probability(&settings),
+                game_mode.special_tile_probability(),
+                rng,
+            )),
+        }
+
+        let mut board_commands = commands.entity(board_entity);
+        board_commands
+            .insert(board_game)
+            .remove::<(Contender, Bot, net::Remote, board::Caption)>();
+        match *game_mode {
+            GameMode::Versus => {
+                let contender = Contender::new(column);
+                let name = format!("{} ({})", contender.name(), contender.controls.name());
+                board_commands.insert((board::Caption::new(name), contender));
+            }
+            GameMode::Gallery => {
+                board_commands.insert((
+                    board::Caption::new(format!("Bot {}", column + 1)),
+                    Bot::new(Difficulty::Normal),
+                ));
+            }
+            GameMode::Race { difficulty } if column > 0 => {
+                board_commands.insert((
+                    board::Caption::new(format!("AI ({})", difficulty.name())),
+                    Bot::new(difficulty),
+                ));
+            }
+            GameMode::Race { .. } => {
+                board_commands.insert(board::Caption::new(race::PLAYER_NAME.to_string()));
+            }
+            GameMode::Lan if column > 0 => {
+                board_commands.insert((
+                    board::Caption::new(lan.peer_name.clone()),
+                    net::Remote::default(),
+                ));
+            }
+            GameMode::Lan => {
+                board_commands.insert(board::Caption::new(race::PLAYER_NAME.to_string()));
+            }
+            _ => {}
+        }
+    }
+
+    game.elapsed = Duration::ZERO;
+    game.in_progress = true;
+    game.undos = 0;
+    history.clear();
 
-    game.score = 0;
+    next_state.set(GameState::Playing);
 }
@@ -0,0 +1,351 @@
+use bevy::{input::InputSystem, prelude::*, window::ReceivedCharacter};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::{tile, Board},
+    game::{BoardGame, Game, GameMode, GameState, SoloBoard},
+    storage,
+};
+
+/// Keeps a top [`Leaderboards::SIZE`] table per rule set and asks for a name when a finished
+/// game makes it into one.
+pub struct LeaderboardPlugin;
+
+/// The rules a score was made with, only scores made with the same rules are ranked together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RuleSet {
+    pub board_size: u8,
+    pub mode: GameMode,
+}
+
+impl RuleSet {
+    pub fn name(&self) -> String {
+        return format!("{0}x{0} {1}", self.board_size, self.mode.name());
+    }
+
+    /// Whether `entry` ranks ahead of `other`, ties keep the earlier entry ahead.
+    pub fn ranks_ahead(&self, entry: &LeaderboardEntry, other: &LeaderboardEntry) -> bool {
+        if self.mode.ranks_by_time() {
+            return entry.duration_secs < other.duration_secs;
+        }
+        return entry.score > other.score;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub name: String,
+    pub score: u32,
+    pub max_tile: u32,
+    pub moves: u32,
+    pub duration_secs: f32,
+    /// Local date at the end of the game, formatted as `YYYY-MM-DD`.
+    pub date: String,
+    /// Starting a game with this seed replays the same tile spawns.
+    pub seed: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Leaderboard {
+    pub rules: RuleSet,
+    /// Best first, see [`RuleSet::ranks_ahead`].
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+/// Every leaderboard, stored in [`Leaderboards::FILE`].
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Leaderboards {
+    pub tables: Vec<Leaderboard>,
+    /// Prefilled in the name prompt.
+    pub last_name: String,
+}
+
+impl Default for Leaderboards {
+    fn default() -> Self {
+        return Self {
+            tables: Vec::new(),
+            last_name: "Player".to_string(),
+        };
+    }
+}
+
+impl Leaderboards {
+    pub const FILE: &str = "leaderboards.ron";
+    /// How many entries a leaderboard keeps.
+    pub const SIZE: usize = 10;
+    pub const MAX_NAME_LENGTH: usize = 12;
+
+    pub fn load() -> Self {
+        return storage::load(Leaderboards::FILE);
+    }
+
+    pub fn entries(&self, rules: RuleSet) -> &[LeaderboardEntry] {
+        return self
+            .tables
+            .iter()
+            .find(|table| table.rules == rules)
+            .map_or(&[], |table| table.entries.as_slice());
+    }
+
+    /// Whether a game would make it into the leaderboard of `rules`.
+    pub fn qualifies(&self, rules: RuleSet, entry: &LeaderboardEntry) -> bool {
+        if entry.score == 0 || !rules.mode.is_completed(entry.max_tile) {
+            return false;
+        }
+
+        let entries = self.entries(rules);
+        return entries.len() < Leaderboards::SIZE
+            || entries
+                .last()
+                .is_none_or(|last| rules.ranks_ahead(entry, last));
+    }
+
+    /// The index the entry would be inserted at.
+    pub fn rank(&self, rules: RuleSet, entry: &LeaderboardEntry) -> usize {
+        return self
+            .entries(rules)
+            .partition_point(|other| !rules.ranks_ahead(entry, other));
+    }
+
+    /// Inserts the entry at its rank and returns it, or `None` when it doesn't qualify.
+    pub fn insert(&mut self, rules: RuleSet, entry: LeaderboardEntry) -> Option<usize> {
+        if !self.qualifies(rules, &entry) {
+            return None;
+        }
+        let rank = self.rank(rules, &entry);
+
+        let index = match self.tables.iter().position(|table| table.rules == rules) {
+            Some(index) => index,
+            None => {
+                self.tables.push(Leaderboard {
+                    rules,
+                    entries: Vec::new(),
+                });
+                self.tables.len() - 1
+            }
+        };
+
+        let entries = &mut self.tables[index].entries;
+        entries.insert(rank, entry);
+        entries.truncate(Leaderboards::SIZE);
+
+        return Some(rank);
+    }
+}
+
+/// A qualifying score waiting for the player to type their name.
+#[derive(Resource, Debug, Default)]
+pub struct PendingEntry(pub Option<(RuleSet, LeaderboardEntry)>);
+
+impl LeaderboardPlugin {
+    fn on_game_over(
+        leaderboards: Res<Leaderboards>,
+        mut pending: ResMut<PendingEntry>,
+        game: Res<Game>,
+        game_mode: Res<GameMode>,
+        query_board: Query<(&Board, &BoardGame), SoloBoard>,
+        tiles: Query<&tile::Points>,
+    ) {
+        if !game_mode.has_leaderboard() {
+            return;
+        }
+        let Ok((board, board_game)) = query_board.get_single() else {
+            return;
+        };
+
+        let rules = RuleSet {
+            board_size: board.size,
+            mode: *game_mode,
+        };
+        let entry = LeaderboardEntry {
+            name: leaderboards.last_name.clone(),
+            score: board_game.score,
+            max_tile: tiles
+                .iter()
+                .filter_map(|points| points.number())
+                .max()
+                .unwrap_or(0),
+            moves: board_game.moves,
+            duration_secs: game.elapsed.as_secs_f32(),
+            date: chrono::Local::now().format("%Y-%m-%d").to_string(),
+            seed: game.seed,
+        };
+
+        if leaderboards.qualifies(rules, &entry) {
+            pending.0 = Some((rules, entry));
+        }
+    }
+
+    /// Edits the pending name, enter or escape saves it.
+    ///
+    /// Runs right after the input is collected and clears it, so typing doesn't trigger the
+    /// key bindings.
+    fn sys_type_name(
+        mut pending: ResMut<PendingEntry>,
+        mut leaderboards: ResMut<Leaderboards>,
+        mut characters: EventReader<ReceivedCharacter>,
+        mut input: ResMut<Input<KeyCode>>,
+    ) {
+        let Some((_, entry)) = pending.0.as_mut() else {
+            characters.clear();
+            return;
+        };
+
+        for event in characters.iter() {
+            if (event.char.is_alphanumeric() || event.char == ' ')
+                && entry.name.chars().count() < Leaderboards::MAX_NAME_LENGTH
+            {
+                entry.name.push(event.char);
+            }
+        }
+        if input.just_pressed(KeyCode::Back) {
+            entry.name.pop();
+        }
+
+        let submit =
+            input.any_just_pressed([KeyCode::Return, KeyCode::NumpadEnter, KeyCode::Escape]);
+        input.clear();
+
+        if submit {
+            LeaderboardPlugin::submit(&mut pending, &mut leaderboards);
+        }
+    }
+
+    /// Saves the pending entry when a new game starts before the name was confirmed.
+    fn on_exit_game_over(
+        mut pending: ResMut<PendingEntry>,
+        mut leaderboards: ResMut<Leaderboards>,
+    ) {
+        LeaderboardPlugin::submit(&mut pending, &mut leaderboards);
+    }
+
+    fn submit(pending: &mut PendingEntry, leaderboards: &mut Leaderboards) {
+        let Some((rules, mut entry)) = pending.0.take() else {
+            return;
+        };
+
+        entry.name = entry.name.trim().to_string();
+        if entry.name.is_empty() {
+            entry.name = Leaderboards::default().last_name;
+        }
+        leaderboards.last_name = entry.name.clone();
+        leaderboards.insert(rules, entry);
+
+        storage::save(Leaderboards::FILE, leaderboards);
+    }
+}
+
+impl Plugin for LeaderboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Leaderboards::load())
+            .init_resource::<PendingEntry>()
+            .add_system(
+                LeaderboardPlugin::sys_type_name
+                    .in_base_set(CoreSet::PreUpdate)
+                    .after(InputSystem),
+            )
+            .add_system(LeaderboardPlugin::on_game_over.in_schedule(OnEnter(GameState::GameOver)))
+            .add_system(
+                LeaderboardPlugin::on_exit_game_over.in_schedule(OnExit(GameState::GameOver)),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLASSIC: RuleSet = RuleSet {
+        board_size: 4,
+        mode: GameMode::Classic,
+    };
+    const SPRINT: RuleSet = RuleSet {
+        board_size: 4,
+        mode: GameMode::Sprint { target: 512 },
+    };
+
+    fn entry(name: &str, score: u32, max_tile: u32, duration_secs: f32) -> LeaderboardEntry {
+        return LeaderboardEntry {
+            name: name.to_string(),
+            score,
+            max_tile,
+            moves: 100,
+            duration_secs,
+            date: "2024-03-01".to_string(),
+            seed: 7,
+        };
+    }
+
+    fn names(leaderboards: &Leaderboards, rules: RuleSet) -> Vec<&str> {
+        return leaderboards
+            .entries(rules)
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect();
+    }
+
+    #[test]
+    fn insert_keeps_the_best_first() {
+        let mut leaderboards = Leaderboards::default();
+        assert_eq!(
+            leaderboards.insert(CLASSIC, entry("b", 500, 64, 60.)),
+            Some(0)
+        );
+        assert_eq!(
+            leaderboards.insert(CLASSIC, entry("a", 900, 64, 60.)),
+            Some(0)
+        );
+        assert_eq!(
+            leaderboards.insert(CLASSIC, entry("c", 100, 16, 60.)),
+            Some(2)
+        );
+        // Ties stay behind the earlier entry
+        assert_eq!(
+            leaderboards.insert(CLASSIC, entry("d", 500, 64, 60.)),
+            Some(2)
+        );
+
+        assert_eq!(names(&leaderboards, CLASSIC), ["a", "b", "d", "c"]);
+        assert!(leaderboards.entries(SPRINT).is_empty());
+    }
+
+    #[test]
+    fn full_leaderboards_drop_the_last_entry() {
+        let mut leaderboards = Leaderboards::default();
+        for score in 1..=Leaderboards::SIZE as u32 {
+            leaderboards.insert(CLASSIC, entry(&score.to_string(), score * 100, 64, 60.));
+        }
+        assert_eq!(leaderboards.entries(CLASSIC).len(), Leaderboards::SIZE);
+
+        let worst = entry("worst", 100, 64, 60.);
+        assert!(!leaderboards.qualifies(CLASSIC, &worst));
+        assert_eq!(leaderboards.insert(CLASSIC, worst), None);
+
+        assert_eq!(
+            leaderboards.insert(CLASSIC, entry("new", 150, 64, 60.)),
+            Some(9)
+        );
+        let entries = leaderboards.entries(CLASSIC);
+        assert_eq!(entries.len(), Leaderboards::SIZE);
+        assert_eq!(entries.last().map(|entry| entry.score), Some(150));
+    }
+
+    #[test]
+    fn empty_and_unfinished_games_dont_qualify() {
+        let leaderboards = Leaderboards::default();
+        assert!(!leaderboards.qualifies(CLASSIC, &entry("a", 0, 2, 60.)));
+        assert!(leaderboards.qualifies(CLASSIC, &entry("a", 4, 4, 60.)));
+        assert!(!leaderboards.qualifies(SPRINT, &entry("a", 3000, 256, 60.)));
+        assert!(leaderboards.qualifies(SPRINT, &entry("a", 3000, 512, 60.)));
+    }
+
+    #[test]
+    fn sprints_rank_by_time() {
+        let mut leaderboards = Leaderboards::default();
+        leaderboards.insert(SPRINT, entry("slow", 9000, 512, 300.));
+        leaderboards.insert(SPRINT, entry("fast", 4000, 512, 120.));
+
+        assert_eq!(names(&leaderboards, SPRINT), ["fast", "slow"]);
+    }
+}
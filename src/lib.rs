@@ -0,0 +1,25 @@
+#![allow(clippy::needless_return)]
+#![allow(clippy::type_complexity)]
+#![allow(clippy::too_many_arguments)]
+
+pub mod achievements;
+pub mod assets;
+pub mod audio;
+pub mod board;
+pub mod bot;
+pub mod daily;
+pub mod editor;
+pub mod env;
+pub mod game;
+pub mod leaderboard;
+pub mod merge;
+pub mod net;
+pub mod puzzle;
+pub mod race;
+pub mod settings;
+pub mod spectate;
+pub mod stats;
+pub mod storage;
+pub mod theme;
+pub mod ui;
+pub mod versus;
@@ -1,32 +1,60 @@
-#![allow(dead_code)]
-#![allow(unused_variables)]
+#![allow(clippy::needless_return)]
 
-mod assets;
-mod board;
-mod game;
-mod ui;
-
-use assets::FontSpec;
-///! Followed from the [2048 bevy course](https://www.rustadventure.dev/2048-with-bevy-ecs/bevy-0.10/updating-tile-display-when-point-values-change)
+//! Followed from the [2048 bevy course](https://www.rustadventure.dev/2048-with-bevy-ecs/bevy-0.10/updating-tile-display-when-point-values-change)
 use bevy::prelude::*;
-use board::{Board, BoardShiftDirection, NewTileEvent};
-use game::{Game, GameState};
+use bevy_2048::{
+    achievements,
+    assets::FontSpec,
+    audio,
+    board::{
+        Board, BoardShiftDirection, BoardShiftedEvent, BoardWonEvent, MoveRejectedEvent,
+        NewTileEvent, TileMovedEvent, TileSpawnedEvent, TilesMergedEvent,
+    },
+    bot, daily, editor,
+    game::{self, Game, GameMode, GameState, History, NewGameEvent},
+    leaderboard, net, puzzle, race,
+    settings::Settings,
+    spectate, stats, theme, ui, versus,
+};
 
 fn main() {
     App::new()
-        .insert_resource(ClearColor(Color::hex("#1f2638").unwrap()))
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                title: "2048".into(),
-                ..default()
-            }),
-            ..default()
-        }))
+        .add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: Some(Window {
+                        title: "2048".into(),
+                        ..default()
+                    }),
+                    ..default()
+                })
+                .set(AssetPlugin {
+                    // Hot reloads the theme files
+                    watch_for_changes: true,
+                    ..default()
+                }),
+        )
         .init_resource::<FontSpec>()
+        .insert_resource(Settings::load())
         .init_resource::<Game>()
+        .init_resource::<History>()
+        .init_resource::<GameMode>()
         .add_state::<GameState>()
         .add_plugin(bevy_easings::EasingsPlugin)
+        .add_plugin(theme::ThemePlugin)
         .add_plugin(ui::UIPlugin)
+        .add_plugin(audio::SoundPlugin)
+        .add_plugin(stats::StatisticsPlugin)
+        .add_plugin(leaderboard::LeaderboardPlugin)
+        .add_plugin(achievements::AchievementsPlugin)
+        .add_plugin(daily::DailyPlugin)
+        .add_plugin(puzzle::PuzzlePlugin)
+        .add_plugin(editor::EditorPlugin)
+        .add_plugin(versus::VersusPlugin)
+        .add_plugin(bot::BotPlugin)
+        .add_plugin(race::RacePlugin)
+        .add_plugin(net::NetPlugin)
+        .add_plugin(spectate::SpectatePlugin)
         .add_startup_systems(
             (
                 setup_camera,
@@ -36,14 +64,39 @@ fn main() {
                 .chain(),
         )
         .add_event::<NewTileEvent>()
-        .add_system((game::reset).in_schedule(OnEnter(GameState::Playing)))
+        .add_event::<BoardShiftedEvent>()
+        .add_event::<TileMovedEvent>()
+        .add_event::<TilesMergedEvent>()
+        .add_event::<TileSpawnedEvent>()
+        .add_event::<MoveRejectedEvent>()
+        .add_event::<BoardWonEvent>()
+        .add_system(Board::render_theme.in_base_set(CoreSet::PostUpdate))
+        .add_system(Board::sys_fit_to_window)
+        .add_system(Board::sys_render_captions)
+        .add_event::<NewGameEvent>()
+        .add_systems((
+            game::reset,
+            game::sys_toggle_pause_on_keypress,
+            Settings::sys_save_on_change,
+        ))
+        .add_system(game::on_game_over.in_schedule(OnEnter(GameState::GameOver)))
         .add_systems(
             (
-                Board::render_tiles,
+                // Before the shift, so they never see a tile absorbed in the same frame
+                Board::render_tiles.before(BoardShiftDirection::sys_handle_board_shift_on_keypress),
                 Board::render_tile_points,
-                game::check_game_over,
+                game::check_game_over
+                    .before(BoardShiftDirection::sys_handle_board_shift_on_keypress),
+                game::check_mode_end
+                    .after(game::tick_timer)
+                    .before(BoardShiftDirection::sys_handle_board_shift_on_keypress),
                 BoardShiftDirection::sys_handle_board_shift_on_keypress,
-                Board::on_new_tile_handler,
+                // The bots move every frame, a tile spawned before the shift could land on a
+                // cell the shift fills
+                Board::on_new_tile_handler
+                    .after(BoardShiftDirection::sys_handle_board_shift_on_keypress),
+                game::sys_undo_on_keypress,
+                game::tick_timer,
             )
                 .in_set(OnUpdate(GameState::Playing)),
         )
@@ -51,7 +104,9 @@ fn main() {
 }
 
 fn setup_camera(mut commands: Commands) {
-    commands.spawn(Camera2dBundle::default());
+    let mut camera = Camera2dBundle::default();
+    camera.transform.translation += Board::camera_offset();
+    commands.spawn(camera);
 }
 
 // fn game_reset(mut commands: Commands, tiles: Query<Entity, With<Position>>, mut game ResMut<Game>)
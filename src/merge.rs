@@ -0,0 +1,375 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{board::tile, game::Game};
+
+/// What a group of tiles turns into, see [`MergeRule::merge_tiles`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Merged {
+    Tile(tile::Points),
+    /// Nothing is left, a negative tile met its positive counterpart.
+    Cancelled,
+    /// Nothing is left, and the bomb destroys the tiles next to it as well.
+    Exploded,
+}
+
+/// Which tiles merge together and what they turn into.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MergeRule {
+    /// Two equal powers of two add up.
+    #[default]
+    Classic,
+    /// Two consecutive Fibonacci numbers add up to the next one.
+    Fibonacci,
+    /// Three equal powers of three add up.
+    PowersOfThree,
+    /// A 1 and a 2 make a 3, then equal tiles from 3 up add up.
+    Threes,
+}
+
+impl MergeRule {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            MergeRule::Classic => "Classic",
+            MergeRule::Fibonacci => "Fibonacci",
+            MergeRule::PowersOfThree => "Powers of 3",
+            MergeRule::Threes => "Threes",
+        };
+    }
+
+    /// How many tiles in a row merge at once.
+    pub fn group_size(&self) -> usize {
+        return match self {
+            MergeRule::PowersOfThree => 3,
+            _ => 2,
+        };
+    }
+
+    /// Reaching a tile of this value wins the game.
+    pub fn winning_value(&self) -> u32 {
+        return match self {
+            MergeRule::Classic => Game::WINNING_VALUE,
+            // The first Fibonacci number past 2048
+            MergeRule::Fibonacci => 2584,
+            // 3^7
+            MergeRule::PowersOfThree => 2187,
+            // 3 * 2^8
+            MergeRule::Threes => 768,
+        };
+    }
+
+    /// Where `value` comes in the values of the rule, from `1` for the smallest one and `0`
+    /// for an empty cell. It's the `log2` of the value with [`MergeRule::Classic`], values
+    /// between two of the rule get the rank of the lower one.
+    pub fn rank(&self, value: u32) -> u32 {
+        if value == 0 {
+            return 0;
+        }
+        return match self {
+            MergeRule::Classic => value.ilog2(),
+            MergeRule::PowersOfThree => value.ilog(3),
+            // 1, 2, 3, 5, 8...
+            MergeRule::Fibonacci => {
+                // Counted in u64, the number after the largest u32 one doesn't fit
+                let (mut rank, mut current, mut next) = (0, 1u64, 2u64);
+                while current <= value as u64 {
+                    rank += 1;
+                    (current, next) = (next, current + next);
+                }
+                rank
+            }
+            // 1, 2, 3, 6, 12...
+            MergeRule::Threes => match value {
+                1 | 2 => value,
+                _ => (value / 3).ilog2() + 3,
+            },
+        };
+    }
+
+    /// A new tile, `large_probability` is the chance of the larger of the spawn values.
+    pub fn spawn_points(&self, rng: &mut impl Rng, large_probability: f32) -> tile::Points {
+        let large = rng.gen::<f32>() < large_probability;
+
+        let value = match self {
+            MergeRule::Classic => {
+                if large {
+                    4
+                } else {
+                    2
+                }
+            }
+            MergeRule::Fibonacci => {
+                if large {
+                    2
+                } else {
+                    1
+                }
+            }
+            MergeRule::PowersOfThree => {
+                if large {
+                    9
+                } else {
+                    3
+                }
+            }
+            // Ones and twos need each other, so they come in equal shares
+            MergeRule::Threes => {
+                if large {
+                    3
+                } else if rng.gen::<bool>() {
+                    2
+                } else {
+                    1
+                }
+            }
+        };
+
+        return tile::Points::new(value);
+    }
+
+    /// The value of the tile merged from `values`, in the order they slide in, or `None` when
+    /// they don't merge or their sum doesn't fit. Expects [`MergeRule::group_size`] values.
+    pub fn merge(&self, values: &[u32]) -> Option<u32> {
+        if values.len() != self.group_size() {
+            return None;
+        }
+
+        let merges = match self {
+            MergeRule::Classic | MergeRule::PowersOfThree => {
+                values.iter().all(|&value| value == values[0])
+            }
+            MergeRule::Fibonacci => MergeRule::are_consecutive_fibonacci(values[0], values[1]),
+            MergeRule::Threes => {
+                let (a, b) = (values[0].min(values[1]), values[0].max(values[1]));
+                (a == 1 && b == 2) || (a >= 3 && a == b)
+            }
+        };
+
+        if !merges {
+            return None;
+        }
+        return values
+            .iter()
+            .try_fold(0u32, |sum, &value| sum.checked_add(value));
+    }
+
+    /// Like [`MergeRule::merge`] for tiles of any [`tile::Kind`]. Negative tiles merge
+    /// together like numbers, the other special kinds only with the rules that merge pairs.
+    pub fn merge_tiles(&self, tiles: &[tile::Points]) -> Option<Merged> {
+        if tiles.len() != self.group_size() {
+            return None;
+        }
+        let values = tiles.iter().map(|points| points.value).collect::<Vec<_>>();
+        for kind in [tile::Kind::Number, tile::Kind::Negative] {
+            if tiles.iter().all(|points| points.kind == kind) {
+                return self
+                    .merge(&values)
+                    .map(|value| Merged::Tile(tile::Points { value, kind }));
+            }
+        }
+
+        let &[first, second] = tiles else {
+            return None;
+        };
+        let pair = |special: tile::Points, other: tile::Points| {
+            return match (special.kind, other.kind) {
+                (tile::Kind::Bomb, _) => Some(Merged::Exploded),
+                (tile::Kind::Wildcard, tile::Kind::Number | tile::Kind::Negative) => self
+                    .merge(&[other.value, self.partner(other.value)?])
+                    .map(|value| Merged::Tile(tile::Points { value, ..other })),
+                (tile::Kind::Multiplier, tile::Kind::Number | tile::Kind::Negative) => {
+                    Some(Merged::Tile(tile::Points {
+                        value: other.value.checked_mul(2)?,
+                        ..other
+                    }))
+                }
+                (tile::Kind::Negative, tile::Kind::Number) if special.value == other.value => {
+                    Some(Merged::Cancelled)
+                }
+                _ => None,
+            };
+        };
+        return pair(first, second).or_else(|| pair(second, first));
+    }
+
+    /// The value a [`tile::Kind::Wildcard`] takes to merge with a tile of `value`.
+    pub fn partner(&self, value: u32) -> Option<u32> {
+        return match self {
+            MergeRule::Classic | MergeRule::PowersOfThree => Some(value),
+            // The next number of the sequence, a 1 pairs with the other 1
+            MergeRule::Fibonacci => {
+                let (mut current, mut next) = (1u32, 1u32);
+                while current < value {
+                    (current, next) = (next, current.checked_add(next)?);
+                }
+                (current == value).then_some(if value == 1 { 1 } else { next })
+            }
+            MergeRule::Threes => match value {
+                1 => Some(2),
+                2 => Some(1),
+                _ => Some(value),
+            },
+        };
+    }
+
+    fn are_consecutive_fibonacci(a: u32, b: u32) -> bool {
+        let (low, high) = (a.min(b), a.max(b));
+
+        // 1 and 1 are the first two numbers of the sequence
+        let (mut current, mut next) = (1u32, 1u32);
+        while current <= low {
+            if current == low && next == high {
+                return true;
+            }
+            let Some(sum) = current.checked_add(next) else {
+                return false;
+            };
+            (current, next) = (next, sum);
+        }
+        return false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn classic_merges_equal_pairs() {
+        let rule = MergeRule::Classic;
+        assert_eq!(rule.merge(&[2, 2]), Some(4));
+        assert_eq!(rule.merge(&[2, 4]), None);
+        assert_eq!(rule.merge(&[2, 2, 2]), None);
+        assert_eq!(rule.merge(&[1 << 31, 1 << 31]), None);
+    }
+
+    #[test]
+    fn fibonacci_merges_consecutive_numbers() {
+        let rule = MergeRule::Fibonacci;
+        assert_eq!(rule.merge(&[1, 1]), Some(2));
+        assert_eq!(rule.merge(&[1, 2]), Some(3));
+        assert_eq!(rule.merge(&[5, 3]), Some(8));
+        assert_eq!(rule.merge(&[2, 5]), None);
+        assert_eq!(rule.merge(&[5, 5]), None);
+        assert_eq!(rule.merge(&[4, 4]), None);
+
+        assert!(MergeRule::are_consecutive_fibonacci(1597, 2584));
+        assert!(MergeRule::are_consecutive_fibonacci(2584, 1597));
+        assert!(!MergeRule::are_consecutive_fibonacci(1, 3));
+        assert!(!MergeRule::are_consecutive_fibonacci(u32::MAX, u32::MAX));
+    }
+
+    #[test]
+    fn powers_of_three_merge_in_groups_of_three() {
+        let rule = MergeRule::PowersOfThree;
+        assert_eq!(rule.group_size(), 3);
+        assert_eq!(rule.merge(&[3, 3, 3]), Some(9));
+        assert_eq!(rule.merge(&[3, 3]), None);
+        assert_eq!(rule.merge(&[3, 3, 9]), None);
+    }
+
+    #[test]
+    fn threes_needs_a_one_and_a_two() {
+        let rule = MergeRule::Threes;
+        assert_eq!(rule.merge(&[1, 2]), Some(3));
+        assert_eq!(rule.merge(&[2, 1]), Some(3));
+        assert_eq!(rule.merge(&[1, 1]), None);
+        assert_eq!(rule.merge(&[2, 2]), None);
+        assert_eq!(rule.merge(&[3, 3]), Some(6));
+        assert_eq!(rule.merge(&[3, 6]), None);
+    }
+
+    #[test]
+    fn merge_tiles_follows_merge_for_numbers() {
+        let points = |values: &[u32]| values.iter().map(|&v| tile::Points::new(v)).collect();
+        let cases: [(MergeRule, Vec<tile::Points>, Option<u32>); 4] = [
+            (MergeRule::Classic, points(&[8, 8]), Some(16)),
+            (MergeRule::Fibonacci, points(&[8, 13]), Some(21)),
+            (MergeRule::PowersOfThree, points(&[9, 9, 9]), Some(27)),
+            (MergeRule::Threes, points(&[2, 1]), Some(3)),
+        ];
+
+        for (rule, tiles, value) in cases {
+            let merged = value.map(|value| Merged::Tile(tile::Points::new(value)));
+            assert_eq!(rule.merge_tiles(&tiles), merged, "{rule:?}");
+            assert_eq!(rule.merge_tiles(&tiles[1..]), None, "{rule:?}");
+        }
+    }
+
+    #[test]
+    fn wildcard_partners() {
+        assert_eq!(MergeRule::Classic.partner(8), Some(8));
+        assert_eq!(MergeRule::PowersOfThree.partner(27), Some(27));
+        assert_eq!(MergeRule::Fibonacci.partner(1), Some(1));
+        assert_eq!(MergeRule::Fibonacci.partner(2), Some(3));
+        assert_eq!(MergeRule::Fibonacci.partner(13), Some(21));
+        assert_eq!(MergeRule::Fibonacci.partner(4), None);
+        assert_eq!(MergeRule::Threes.partner(1), Some(2));
+        assert_eq!(MergeRule::Threes.partner(2), Some(1));
+        assert_eq!(MergeRule::Threes.partner(6), Some(6));
+    }
+
+    #[test]
+    fn winning_values() {
+        assert_eq!(MergeRule::Classic.winning_value(), 2048);
+        assert_eq!(MergeRule::Fibonacci.winning_value(), 2584);
+        assert_eq!(MergeRule::PowersOfThree.winning_value(), 3u32.pow(7));
+        assert_eq!(MergeRule::Threes.winning_value(), 768);
+
+        // Each of them can be merged to
+        assert!(MergeRule::are_consecutive_fibonacci(987, 1597));
+        assert_eq!(MergeRule::PowersOfThree.merge(&[729, 729, 729]), Some(2187));
+        assert_eq!(MergeRule::Threes.merge(&[384, 384]), Some(768));
+    }
+
+    #[test]
+    fn ranks_follow_the_values_of_each_rule() {
+        let ranks = |rule: MergeRule, values: &[u32]| {
+            values
+                .iter()
+                .map(|&value| rule.rank(value))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            ranks(MergeRule::Classic, &[0, 2, 4, 8, 2048]),
+            [0, 1, 2, 3, 11]
+        );
+        assert_eq!(
+            ranks(MergeRule::Fibonacci, &[0, 1, 2, 3, 5, 8, 2584]),
+            [0, 1, 2, 3, 4, 5, 17]
+        );
+        assert_eq!(
+            ranks(MergeRule::PowersOfThree, &[0, 3, 9, 2187]),
+            [0, 1, 2, 7]
+        );
+        assert_eq!(
+            ranks(MergeRule::Threes, &[0, 1, 2, 3, 6, 12, 768]),
+            [0, 1, 2, 3, 4, 5, 11]
+        );
+        assert_eq!(MergeRule::Fibonacci.rank(u32::MAX), 46);
+    }
+
+    #[test]
+    fn spawn_values() {
+        let spawns = |rule: MergeRule, large_probability: f32| {
+            let mut rng = StdRng::seed_from_u64(1);
+            let mut values = (0..100)
+                .map(|_| rule.spawn_points(&mut rng, large_probability).value)
+                .collect::<Vec<_>>();
+            values.sort();
+            values.dedup();
+            values
+        };
+
+        assert_eq!(spawns(MergeRule::Classic, 0.), [2]);
+        assert_eq!(spawns(MergeRule::Classic, 1.), [4]);
+        assert_eq!(spawns(MergeRule::Classic, 0.5), [2, 4]);
+        assert_eq!(spawns(MergeRule::Fibonacci, 0.5), [1, 2]);
+        assert_eq!(spawns(MergeRule::PowersOfThree, 0.5), [3, 9]);
+        assert_eq!(spawns(MergeRule::Threes, 0.), [1, 2]);
+        assert_eq!(spawns(MergeRule::Threes, 0.5), [1, 2, 3]);
+    }
+}
@@ -0,0 +1,660 @@
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, ErrorKind, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    board::{Board, BoardShiftDirection, BoardShiftedEvent, Caption},
+    game::{self, BoardGame, Game, GameMode, GameState, NewGameEvent, SoloBoard},
+    leaderboard::Leaderboards,
+    ui::toast::ToastEvent,
+    versus::{VersusCard, VersusOutcome},
+};
+
+/// Races another player over TCP in [`GameMode::Lan`].
+///
+/// The host waits for a guest on [`Lan::address`] and picks the seed of every game. Both players
+/// play their board locally and only send their moves, the board of the opponent is replayed
+/// from those on the same seed, so a score can't be made up.
+///
+/// `--host [ADDRESS]` or `--join [ADDRESS]` on the command line skip the menus, which makes it
+/// easy to try with two processes on localhost.
+pub struct NetPlugin;
+
+/// Listened on by the host and joined by the guest when no address is given.
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7878";
+/// The players share the rules of the host, fixed like the daily challenge.
+pub const BOARD_SIZE: u8 = 4;
+pub const SPAWN_FOUR_PROBABILITY: f32 = 0.1;
+
+/// A line of the protocol, written as RON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum NetMessage {
+    /// Sent by both sides once connected.
+    Hello { name: String },
+    /// Sent by the host to start a game.
+    Start { seed: u64 },
+    /// A move that changed the board of the sender, in the game of `seed`.
+    Move {
+        seed: u64,
+        direction: BoardShiftDirection,
+    },
+    /// Asks the host for a new game.
+    Rematch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Host,
+    Guest,
+}
+
+/// Sent by the buttons of the modes and lobby screens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LanCommand {
+    Host,
+    Join,
+    /// Closes the connection or stops waiting for one.
+    Leave,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LanStatus {
+    Idle,
+    Waiting,
+    Connecting,
+    Connected,
+    Closed,
+    Failed(String),
+}
+
+/// What the network threads report to the game, see [`run_link`].
+pub enum LinkEvent<M> {
+    Connected(TcpStream),
+    Message(M),
+    Closed,
+    Failed(String),
+}
+
+/// The connection to the other player.
+#[derive(Resource)]
+pub struct Lan {
+    /// Listened on by the host, connected to by the guest.
+    pub address: String,
+    pub role: Option<Role>,
+    pub status: LanStatus,
+    pub peer_name: String,
+    link: Option<LinkWriter<NetMessage>>,
+    events: Option<Mutex<mpsc::Receiver<LinkEvent<NetMessage>>>>,
+    /// Stops a host that is still waiting for a guest.
+    cancel: Arc<AtomicBool>,
+    /// Asked for on the command line, run at startup.
+    startup: Option<LanCommand>,
+    /// The seed of the game being played, sent by the host.
+    seed: Option<u64>,
+    /// Moves of the other player in the game of [`Lan::seed`], waiting for its board.
+    incoming: Vec<BoardShiftDirection>,
+}
+
+impl Default for Lan {
+    fn default() -> Self {
+        return Self {
+            address: DEFAULT_ADDRESS.to_string(),
+            role: None,
+            status: LanStatus::Idle,
+            peer_name: String::new(),
+            link: None,
+            events: None,
+            cancel: Arc::new(AtomicBool::new(false)),
+            startup: None,
+            seed: None,
+            incoming: Vec::new(),
+        };
+    }
+}
+
+impl Lan {
+    /// Reads `--host [ADDRESS]` and `--join [ADDRESS]`.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Self {
+        let mut lan = Lan::default();
+        let mut args = args.into_iter().peekable();
+
+        while let Some(arg) = args.next() {
+            let command = match arg.as_str() {
+                "--host" => LanCommand::Host,
+                "--join" => LanCommand::Join,
+                _ => continue,
+            };
+            lan.startup = Some(command);
+            if let Some(address) = args.next_if(|next| !next.starts_with("--")) {
+                lan.address = address;
+            }
+        }
+
+        return lan;
+    }
+
+    pub fn is_connected(&self) -> bool {
+        return self.link.is_some();
+    }
+
+    /// The line of the lobby screen.
+    pub fn describe(&self) -> String {
+        return match &self.status {
+            LanStatus::Idle => "Not connected".to_string(),
+            LanStatus::Waiting => format!("Waiting for a player on {}", self.address),
+            LanStatus::Connecting => format!("Connecting to {}", self.address),
+            LanStatus::Connected => format!("Connected to {}", self.peer_name),
+            LanStatus::Closed => "The other player left".to_string(),
+            LanStatus::Failed(err) => format!("Connection failed: {err}"),
+        };
+    }
+
+    pub fn host(&mut self) {
+        self.leave();
+        let (sender, receiver) = mpsc::channel();
+        let address = self.address.clone();
+        let cancel = self.cancel.clone();
+
+        thread::spawn(move || {
+            let listener = match TcpListener::bind(&address)
+                .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+            {
+                Ok(listener) => listener,
+                Err(err) => {
+                    let _ = sender.send(LinkEvent::Failed(err.to_string()));
+                    return;
+                }
+            };
+
+            // Polled, so leaving the lobby frees the port
+            while !cancel.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        run_link(stream, sender);
+                        return;
+                    }
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                        thread::sleep(Duration::from_millis(50));
+                    }
+                    Err(err) => {
+                        let _ = sender.send(LinkEvent::Failed(err.to_string()));
+                        return;
+                    }
+                }
+            }
+        });
+
+        self.role = Some(Role::Host);
+        self.status = LanStatus::Waiting;
+        self.events = Some(Mutex::new(receiver));
+    }
+
+    pub fn join(&mut self) {
+        self.leave();
+        let (sender, receiver) = mpsc::channel();
+        let address = self.address.clone();
+
+        thread::spawn(move || match TcpStream::connect(&address) {
+            Ok(stream) => run_link(stream, sender),
+            Err(err) => {
+                let _ = sender.send(LinkEvent::Failed(err.to_string()));
+            }
+        });
+
+        self.role = Some(Role::Guest);
+        self.status = LanStatus::Connecting;
+        self.events = Some(Mutex::new(receiver));
+    }
+
+    pub fn leave(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+        self.cancel = Arc::new(AtomicBool::new(false));
+        if let Some(link) = self.link.take() {
+            link.close();
+        }
+        self.events = None;
+        self.role = None;
+        self.status = LanStatus::Idle;
+        self.forget_game();
+    }
+
+    /// Drops the moves of the game being played, which no new move can join.
+    fn forget_game(&mut self) {
+        self.seed = None;
+        self.incoming.clear();
+    }
+
+    fn send(&mut self, message: &NetMessage) {
+        let Some(link) = self.link.as_ref() else {
+            return;
+        };
+        // A peer that stopped reading is cut off, the reader reports it like any closed stream
+        if link.send(message.clone()).is_err() {
+            link.close();
+        }
+    }
+}
+
+/// Hands a copy of the stream to the game and reads the messages until it closes.
+pub fn run_link<M: DeserializeOwned>(stream: TcpStream, sender: mpsc::Sender<LinkEvent<M>>) {
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_nodelay(true);
+    let reader = match stream.try_clone() {
+        Ok(reader) => reader,
+        Err(err) => {
+            let _ = sender.send(LinkEvent::Failed(err.to_string()));
+            return;
+        }
+    };
+    if sender.send(LinkEvent::Connected(stream)).is_err() {
+        return;
+    }
+
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        // Skips the lines of a newer version
+        let Ok(message) = ron::from_str(&line) else {
+            continue;
+        };
+        if sender.send(LinkEvent::Message(message)).is_err() {
+            return;
+        }
+    }
+
+    let _ = sender.send(LinkEvent::Closed);
+}
+
+/// Writes the messages of the game to a link on a thread of its own, so a peer that stops
+/// reading fills [`LinkWriter::CAPACITY`] messages instead of blocking the game.
+pub struct LinkWriter<M> {
+    stream: TcpStream,
+    sender: mpsc::SyncSender<M>,
+}
+
+impl<M: Serialize + Send + 'static> LinkWriter<M> {
+    pub const CAPACITY: usize = 1024;
+
+    pub fn new(stream: TcpStream) -> std::io::Result<Self> {
+        let mut writer = stream.try_clone()?;
+        let (sender, receiver) = mpsc::sync_channel::<M>(Self::CAPACITY);
+
+        // Ends once the writer is dropped or the stream fails
+        thread::spawn(move || {
+            for message in receiver {
+                if write_line(&mut writer, &message).is_err() {
+                    return;
+                }
+            }
+        });
+
+        return Ok(Self { stream, sender });
+    }
+
+    /// Queues a message, fails when the queue is full or the stream failed.
+    pub fn send(&self, message: M) -> Result<(), mpsc::TrySendError<M>> {
+        return self.sender.try_send(message);
+    }
+
+    /// Shuts the stream down, the reading side of the link reports it as closed.
+    pub fn close(&self) {
+        let _ = self.stream.shutdown(std::net::Shutdown::Both);
+    }
+}
+
+/// Writes a message as a line of RON.
+pub fn write_line<M: Serialize>(stream: &mut TcpStream, message: &M) -> std::io::Result<()> {
+    let line = ron::to_string(message).expect("Messages serialize");
+    return writeln!(stream, "{line}");
+}
+
+/// Replays the moves of the other player on its board entity.
+#[derive(Component, Debug, Clone, Default)]
+pub struct Remote {
+    /// Played one per frame by [`BoardShiftDirection::sys_handle_board_shift_on_keypress`].
+    pub moves: VecDeque<BoardShiftDirection>,
+}
+
+/// The line of the lobby screen, see [`Lan::describe`].
+#[derive(Component)]
+pub struct LanStatusText;
+
+impl NetPlugin {
+    fn on_startup(mut lan: ResMut<Lan>, mut command_writer: EventWriter<LanCommand>) {
+        if let Some(command) = lan.startup.take() {
+            command_writer.send(command);
+        }
+    }
+
+    fn sys_commands(
+        mut command_reader: EventReader<LanCommand>,
+        mut lan: ResMut<Lan>,
+        mut next_state: ResMut<NextState<GameState>>,
+    ) {
+        for command in command_reader.iter() {
+            match command {
+                LanCommand::Host => lan.host(),
+                LanCommand::Join => lan.join(),
+                LanCommand::Leave => {
+                    lan.leave();
+                    next_state.set(GameState::Modes);
+                    continue;
+                }
+            }
+            next_state.set(GameState::Lobby);
+        }
+    }
+
+    /// Handles what the network threads received.
+    fn sys_poll(
+        mut lan: ResMut<Lan>,
+        leaderboards: Res<Leaderboards>,
+        game: Res<Game>,
+        mut game_mode: ResMut<GameMode>,
+        mut remotes: Query<(&mut Remote, &mut BoardGame)>,
+        mut new_game_writer: EventWriter<NewGameEvent>,
+        mut toast_writer: EventWriter<ToastEvent>,
+    ) {
+        let events = match lan.events.as_ref() {
+            Some(events) => events
+                .lock()
+                .expect("Only read here")
+                .try_iter()
+                .collect::<Vec<_>>(),
+            None => return,
+        };
+
+        for event in events {
+            match event {
+                LinkEvent::Connected(stream) => {
+                    match LinkWriter::new(stream) {
+                        Ok(link) => lan.link = Some(link),
+                        Err(err) => {
+                            lan.status = LanStatus::Failed(err.to_string());
+                            continue;
+                        }
+                    }
+                    lan.status = LanStatus::Connected;
+                    let name = match leaderboards.last_name.as_str() {
+                        "" => "Player".to_string(),
+                        name => name.to_string(),
+                    };
+                    lan.send(&NetMessage::Hello { name });
+                }
+                LinkEvent::Message(NetMessage::Hello { name }) => {
+                    lan.peer_name = name;
+                    // The host starts the first game, see `sys_sync_new_games`
+                    if lan.role == Some(Role::Host) {
+                        *game_mode = GameMode::Lan;
+                        new_game_writer.send(NewGameEvent::default());
+                    }
+                }
+                LinkEvent::Message(NetMessage::Start { seed }) => {
+                    if lan.role == Some(Role::Guest) {
+                        lan.seed = Some(seed);
+                        lan.incoming.clear();
+                        *game_mode = GameMode::Lan;
+                        new_game_writer.send(NewGameEvent { seed: Some(seed) });
+                    }
+                }
+                // Moves of a previous game may still arrive after a rematch
+                LinkEvent::Message(NetMessage::Move { seed, direction }) => {
+                    if lan.seed == Some(seed) {
+                        lan.incoming.push(direction);
+                    }
+                }
+                LinkEvent::Message(NetMessage::Rematch) => {
+                    if lan.role == Some(Role::Host) && *game_mode == GameMode::Lan {
+                        new_game_writer.send(NewGameEvent::default());
+                    }
+                }
+                LinkEvent::Closed => {
+                    lan.link = None;
+                    lan.status = LanStatus::Closed;
+                    lan.forget_game();
+                    // Lets the game end once the player is stuck as well
+                    for (_, mut board_game) in remotes.iter_mut() {
+                        board_game.over = true;
+                    }
+                    toast_writer.send(ToastEvent {
+                        title: "Disconnected".to_string(),
+                        message: format!("{} left the game", lan.peer_name),
+                    });
+                }
+                LinkEvent::Failed(err) => {
+                    lan.link = None;
+                    lan.status = LanStatus::Failed(err);
+                    lan.forget_game();
+                }
+            }
+        }
+
+        // The board of a new game is only there once its seed is
+        if *game_mode != GameMode::Lan || remotes.is_empty() || lan.seed != Some(game.seed) {
+            return;
+        }
+        let moves = std::mem::take(&mut lan.incoming);
+        for (mut remote, _) in remotes.iter_mut() {
+            remote.moves.extend(moves.iter().copied());
+        }
+    }
+
+    /// Sends the seed of every new game of the host, or asks the host for one.
+    fn sys_sync_new_games(
+        mut new_game_reader: EventReader<NewGameEvent>,
+        mut lan: ResMut<Lan>,
+        game: Res<Game>,
+        game_mode: Res<GameMode>,
+    ) {
+        let Some(event) = new_game_reader.iter().last() else {
+            return;
+        };
+        if !lan.is_connected() {
+            return;
+        }
+        // Another mode was picked
+        if *game_mode != GameMode::Lan {
+            lan.leave();
+            return;
+        }
+
+        match lan.role {
+            Some(Role::Host) => {
+                lan.seed = Some(game.seed);
+                lan.incoming.clear();
+                lan.send(&NetMessage::Start { seed: game.seed });
+            }
+            // The guest waits in the lobby for the seed, see `game::reset`
+            Some(Role::Guest) if event.seed.is_none() => lan.send(&NetMessage::Rematch),
+            _ => {}
+        }
+    }
+
+    fn sys_send_moves(
+        mut shifted_reader: EventReader<BoardShiftedEvent>,
+        mut lan: ResMut<Lan>,
+        game: Res<Game>,
+        game_mode: Res<GameMode>,
+        players: Query<(), SoloBoard>,
+    ) {
+        for event in shifted_reader.iter() {
+            if *game_mode == GameMode::Lan && players.contains(event.board) {
+                lan.send(&NetMessage::Move {
+                    seed: game.seed,
+                    direction: event.direction,
+                });
+            }
+        }
+    }
+
+    /// Shows the biggest tile of the other player next to its score.
+    fn sys_update_captions(
+        game_mode: Res<GameMode>,
+        mut query_board: Query<(Entity, &mut Caption), With<Remote>>,
+        tiles: Query<(&crate::board::tile::Points, &Parent)>,
+    ) {
+        if *game_mode != GameMode::Lan {
+            return;
+        }
+        for (entity, mut caption) in query_board.iter_mut() {
+            let max_tile = tiles
+                .iter()
+                .filter(|(_, parent)| parent.get() == entity)
+                .map(|(points, _)| points.value)
+                .max()
+                .unwrap_or(0);
+            let note = format!("max tile {max_tile}");
+            if caption.note != note {
+                caption.note = note;
+            }
+        }
+    }
+
+    fn sys_status_text(lan: Res<Lan>, mut texts: Query<&mut Text, With<LanStatusText>>) {
+        if !lan.is_changed() {
+            return;
+        }
+        for mut text in texts.iter_mut() {
+            text.sections[0].value = lan.describe();
+        }
+    }
+
+    fn on_game_over(
+        mut card: ResMut<VersusCard>,
+        game_mode: Res<GameMode>,
+        query_board: Query<(&Board, &BoardGame, &Caption)>,
+    ) {
+        if *game_mode == GameMode::Lan {
+            card.0 = Some(VersusOutcome::by_score(query_board.iter().collect()));
+        }
+    }
+}
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Lan::from_args(std::env::args()))
+            .add_event::<LanCommand>()
+            .add_startup_system(NetPlugin::on_startup)
+            .add_systems((
+                NetPlugin::sys_commands,
+                NetPlugin::sys_poll.before(game::reset),
+                NetPlugin::sys_sync_new_games.after(game::reset),
+                NetPlugin::sys_status_text,
+            ))
+            .add_systems(
+                (
+                    NetPlugin::sys_send_moves
+                        .after(BoardShiftDirection::sys_handle_board_shift_on_keypress),
+                    NetPlugin::sys_update_captions.before(Board::sys_render_captions),
+                )
+                    .in_set(OnUpdate(GameState::Playing)),
+            )
+            .add_system(NetPlugin::on_game_over.in_schedule(OnEnter(GameState::GameOver)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Connects a host and a guest on a free port, each with its link and what it reads.
+    fn connect() -> [(
+        LinkWriter<NetMessage>,
+        mpsc::Receiver<LinkEvent<NetMessage>>,
+    ); 2] {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("A free port");
+        let address = listener.local_addr().expect("A bound address");
+
+        let (host_sender, host_receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().expect("The guest connects");
+            run_link(stream, host_sender);
+        });
+        let (guest_sender, guest_receiver) = mpsc::channel();
+        let stream = TcpStream::connect(address).expect("The host listens");
+        thread::spawn(move || run_link(stream, guest_sender));
+
+        return [host_receiver, guest_receiver].map(|receiver| {
+            let Ok(LinkEvent::Connected(stream)) = receiver.recv_timeout(TIMEOUT) else {
+                panic!("The link connects first");
+            };
+            (
+                LinkWriter::new(stream).expect("A copy of the stream"),
+                receiver,
+            )
+        });
+    }
+
+    fn receive(receiver: &mpsc::Receiver<LinkEvent<NetMessage>>) -> NetMessage {
+        return match receiver.recv_timeout(TIMEOUT) {
+            Ok(LinkEvent::Message(message)) => message,
+            _ => panic!("A message was sent"),
+        };
+    }
+
+    #[test]
+    fn host_and_guest_exchange_messages() {
+        let [(host, from_guest), (guest, from_host)] = connect();
+
+        let hello = |name: &str| NetMessage::Hello {
+            name: name.to_string(),
+        };
+        host.send(hello("Host")).expect("Room in the queue");
+        guest.send(hello("Guest")).expect("Room in the queue");
+        assert_eq!(receive(&from_host), hello("Host"));
+        assert_eq!(receive(&from_guest), hello("Guest"));
+
+        let moves = [
+            NetMessage::Start { seed: u64::MAX },
+            NetMessage::Move {
+                seed: u64::MAX,
+                direction: BoardShiftDirection::Left,
+            },
+        ];
+        for message in moves.iter() {
+            host.send(message.clone()).expect("Room in the queue");
+        }
+        for message in moves.iter() {
+            assert_eq!(&receive(&from_host), message);
+        }
+
+        guest.send(NetMessage::Rematch).expect("Room in the queue");
+        assert_eq!(receive(&from_guest), NetMessage::Rematch);
+
+        guest.close();
+        assert!(matches!(
+            from_guest.recv_timeout(TIMEOUT),
+            Ok(LinkEvent::Closed)
+        ));
+    }
+
+    #[test]
+    fn peer_that_stops_reading_doesnt_block_the_sender() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("A free port");
+        let stream = TcpStream::connect(listener.local_addr().expect("A bound address"))
+            .expect("The listener accepts");
+        // Accepted but never read
+        let _peer = listener.accept().expect("The stream connects");
+
+        let link = LinkWriter::new(stream).expect("A copy of the stream");
+        let name = "x".repeat(1024);
+        let full = (0..64 * LinkWriter::<NetMessage>::CAPACITY)
+            .any(|_| link.send(NetMessage::Hello { name: name.clone() }).is_err());
+
+        assert!(full, "The queue fills up instead of blocking");
+    }
+}
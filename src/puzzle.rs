@@ -0,0 +1,558 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    ecs::system::SystemParam,
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    assets::FontSpec,
+    board::{tile, Board, BoardShiftDirection, NewTileEvent, TileSpawnedEvent, TilesMergedEvent},
+    editor::Editor,
+    game::{BoardGame, GameMode, GameState, NewGameEvent, SoloBoard},
+    storage,
+};
+
+/// Plays the handcrafted [`Level`]s of [`GameMode::Puzzle`] and [`GameMode::Custom`] and saves
+/// the stars earned.
+pub struct PuzzlePlugin;
+
+/// A puzzle with a fixed starting board, loaded from `assets/levels/<name>.level.ron`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "268b3ded-017b-426c-bcbe-200f6366c650"]
+pub struct Level {
+    pub name: String,
+    /// The starting board, top row first, `0` for empty cells. Its height is the board size.
+    pub board: Vec<Vec<u32>>,
+    /// The `(column, row)` of the cells tiles can't enter, rows counted from the top like `board`.
+    #[serde(default)]
+    pub blockers: Vec<(u8, u8)>,
+    /// The tile spawned after each move, in order, on the first empty cell from the top left.
+    /// Nothing spawns once they run out.
+    #[serde(default)]
+    pub spawns: Vec<u32>,
+    pub goal: Goal,
+    pub max_moves: u32,
+    /// The most moves that still earn three stars.
+    pub three_stars: u32,
+    /// The most moves that still earn two stars.
+    pub two_stars: u32,
+}
+
+/// What completes a [`Level`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Goal {
+    /// Make a tile of this value.
+    Tile(u32),
+    /// Leave at most this many tiles on the board.
+    ClearTo(usize),
+    /// Score this many points.
+    Score(u32),
+}
+
+impl Goal {
+    pub fn description(&self) -> String {
+        return match self {
+            Goal::Tile(value) => format!("Make a {value}"),
+            Goal::ClearTo(1) => "Leave a single tile".to_string(),
+            Goal::ClearTo(count) => format!("Leave {count} tiles"),
+            Goal::Score(score) => format!("Score {score}"),
+        };
+    }
+
+    pub fn is_met(&self, score: u32, tiles: &[u32]) -> bool {
+        return match *self {
+            Goal::Tile(value) => tiles.iter().any(|&tile| tile >= value),
+            Goal::ClearTo(count) => tiles.len() <= count,
+            Goal::Score(target) => score >= target,
+        };
+    }
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        return Self {
+            name: "Custom".to_string(),
+            board: vec![vec![0; 4]; 4],
+            blockers: Vec::new(),
+            spawns: Vec::new(),
+            goal: Goal::Tile(16),
+            max_moves: 20,
+            three_stars: 10,
+            two_stars: 15,
+        };
+    }
+}
+
+impl Level {
+    /// The levels shipped in `assets/levels`, in the order they are played.
+    pub const BUILT_IN: [&str; 6] = [
+        "01_first_steps",
+        "02_corners",
+        "03_clean_sweep",
+        "04_conveyor",
+        "05_staircase",
+        "06_big_board",
+    ];
+
+    pub fn path(name: &str) -> String {
+        return format!("levels/{name}.level.ron");
+    }
+
+    pub fn board_size(&self) -> u8 {
+        return self.board.len() as u8;
+    }
+
+    /// The tiles of the starting board.
+    pub fn tiles(&self) -> Vec<(tile::Position, tile::Points)> {
+        let size = self.board_size();
+
+        return self
+            .board
+            .iter()
+            .enumerate()
+            .flat_map(|(row, values)| {
+                values.iter().enumerate().filter_map(move |(x, &value)| {
+                    let position = tile::Position {
+                        x: x as u8,
+                        y: size - 1 - row as u8,
+                        z: 0,
+                    };
+                    (value > 0).then_some((position, tile::Points::new(value)))
+                })
+            })
+            .collect();
+    }
+
+    pub fn blockers(&self) -> Vec<tile::Position> {
+        let size = self.board_size();
+
+        return self
+            .blockers
+            .iter()
+            .map(|&(x, row)| tile::Position {
+                x,
+                y: size - 1 - row,
+                z: 0,
+            })
+            .collect();
+    }
+
+    /// Where the scripted spawn number `index` goes, `None` when the script ran out or the
+    /// board is full.
+    pub fn spawn(
+        &self,
+        index: usize,
+        occupied: &[tile::Position],
+    ) -> Option<(tile::Position, tile::Points)> {
+        let &value = self.spawns.get(index)?;
+        let size = self.board_size();
+
+        // Reading order, from the top left corner
+        let position = (0..size)
+            .rev()
+            .flat_map(|y| (0..size).map(move |x| tile::Position { x, y, z: 0 }))
+            .find(|position| !occupied.contains(position))?;
+
+        return Some((position, tile::Points::new(value)));
+    }
+
+    /// Checks that the board is square and the blockers are on it, on empty cells.
+    pub fn check(&self) -> Result<(), String> {
+        let size = self.board.len();
+        if size == 0 {
+            return Err("the board has no rows".to_string());
+        }
+        if let Some((row, values)) = self
+            .board
+            .iter()
+            .enumerate()
+            .find(|(_, values)| values.len() != size)
+        {
+            return Err(format!(
+                "row {row} has {} cells instead of {size}",
+                values.len()
+            ));
+        }
+        if let Some((x, row)) = self
+            .blockers
+            .iter()
+            .find(|&&(x, row)| x as usize >= size || row as usize >= size)
+        {
+            return Err(format!("the blocker at ({x}, {row}) is off the board"));
+        }
+        if let Some((x, row)) = self
+            .blockers
+            .iter()
+            .find(|&&(x, row)| self.board[row as usize][x as usize] > 0)
+        {
+            return Err(format!("the blocker at ({x}, {row}) is on a tile"));
+        }
+
+        return Ok(());
+    }
+
+    /// The stars earned by completing the level in `moves` moves.
+    pub fn stars(&self, moves: u32) -> u8 {
+        return match moves {
+            _ if moves <= self.three_stars => 3,
+            _ if moves <= self.two_stars => 2,
+            _ => 1,
+        };
+    }
+}
+
+#[derive(Default)]
+struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        return Box::pin(async move {
+            let level: Level = ron::de::from_bytes(bytes)?;
+            level.check().map_err(bevy::asset::Error::msg)?;
+            load_context.set_default_asset(LoadedAsset::new(level));
+            Ok(())
+        });
+    }
+
+    fn extensions(&self) -> &[&str] {
+        return &["level.ron"];
+    }
+}
+
+/// The handles of the [`Level::BUILT_IN`] levels.
+#[derive(Resource)]
+pub struct LevelSpec {
+    pub levels: Vec<Handle<Level>>,
+}
+
+impl FromWorld for LevelSpec {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world
+            .get_resource_mut::<AssetServer>()
+            .expect("AssetServer to be initialised with the DefaultPlugins");
+
+        return LevelSpec {
+            levels: Level::BUILT_IN
+                .iter()
+                .map(|name| asset_server.load(Level::path(name)))
+                .collect(),
+        };
+    }
+}
+
+/// The levels that finished loading, by their index in [`Level::BUILT_IN`], and the one in the
+/// [`Editor`].
+#[derive(SystemParam)]
+pub struct Levels<'w> {
+    spec: Res<'w, LevelSpec>,
+    assets: Res<'w, Assets<Level>>,
+    editor: Res<'w, Editor>,
+}
+
+impl Levels<'_> {
+    /// The level played in `mode`, if it is one of the level modes.
+    pub fn of_mode(&self, mode: GameMode) -> Option<&Level> {
+        return match mode {
+            GameMode::Puzzle { level } => self.get(level),
+            GameMode::Custom => Some(&self.editor.level),
+            _ => None,
+        };
+    }
+
+    pub fn get(&self, index: u8) -> Option<&Level> {
+        return self
+            .spec
+            .levels
+            .get(index as usize)
+            .and_then(|handle| self.assets.get(handle));
+    }
+
+    pub fn len(&self) -> usize {
+        return self.spec.levels.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.spec.levels.is_empty();
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelResult {
+    /// The file name of the level, see [`Level::BUILT_IN`].
+    pub level: String,
+    pub stars: u8,
+    /// The fewest moves the level was completed in.
+    pub moves: u32,
+}
+
+/// The completed levels, stored in [`PuzzleResults::FILE`].
+#[derive(Resource, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PuzzleResults {
+    pub results: Vec<LevelResult>,
+}
+
+impl PuzzleResults {
+    pub const FILE: &str = "puzzles.ron";
+
+    pub fn load() -> Self {
+        return storage::load(PuzzleResults::FILE);
+    }
+
+    pub fn get(&self, level: &str) -> Option<&LevelResult> {
+        return self.results.iter().find(|result| result.level == level);
+    }
+
+    /// Keeps the best stars and moves of the level.
+    pub fn record(&mut self, level: &str, stars: u8, moves: u32) {
+        match self.results.iter_mut().find(|result| result.level == level) {
+            Some(result) => {
+                result.stars = result.stars.max(stars);
+                result.moves = result.moves.min(moves);
+            }
+            None => self.results.push(LevelResult {
+                level: level.to_string(),
+                stars,
+                moves,
+            }),
+        }
+    }
+}
+
+/// The end of a level, shown while in [`GameState::GameOver`].
+#[derive(Debug, Clone)]
+pub struct PuzzleOutcome {
+    pub mode: GameMode,
+    pub name: String,
+    pub goal: Goal,
+    /// `0` when the goal wasn't met.
+    pub stars: u8,
+    pub moves: u32,
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct PuzzleCard(pub Option<PuzzleOutcome>);
+
+/// How many scripted tiles were spawned in the current level.
+#[derive(Resource, Debug, Default)]
+struct SpawnCursor(usize);
+
+impl PuzzlePlugin {
+    /// Replaces the random spawns of the other modes with the script of the level.
+    fn sys_scripted_spawns(
+        mut commands: Commands,
+        mut new_game_reader: EventReader<NewGameEvent>,
+        mut new_tile_reader: EventReader<NewTileEvent>,
+        mut merged_reader: EventReader<TilesMergedEvent>,
+        mut cursor: ResMut<SpawnCursor>,
+        mut spawned_writer: EventWriter<TileSpawnedEvent>,
+        game_mode: Res<GameMode>,
+        levels: Levels,
+        query_board: Query<(Entity, &Board), SoloBoard>,
+        tiles: Query<(Entity, &tile::Position, &Parent)>,
+        font_spec: Res<FontSpec>,
+    ) {
+        if new_game_reader.iter().count() > 0 {
+            cursor.0 = 0;
+        }
+        // Despawned by the shift of this frame, but still in the query
+        let absorbed = merged_reader
+            .iter()
+            .flat_map(|event| event.absorbed.iter().map(|&(entity, _)| entity))
+            .collect::<Vec<_>>();
+
+        let Some(level) = levels.of_mode(*game_mode) else {
+            new_tile_reader.clear();
+            return;
+        };
+        // The levels are played alone
+        let Ok((board_entity, board)) = query_board.get_single() else {
+            new_tile_reader.clear();
+            return;
+        };
+
+        for _event in new_tile_reader.iter() {
+            let occupied = tiles
+                .iter()
+                .filter(|&(entity, _, parent)| {
+                    parent.get() == board_entity && !absorbed.contains(&entity)
+                })
+                .map(|(_, &position, _)| position)
+                .collect::<Vec<_>>();
+            let Some((position, points)) = level.spawn(cursor.0, &occupied) else {
+                continue;
+            };
+            cursor.0 += 1;
+
+            spawned_writer.send(TileSpawnedEvent {
+                entity: board.spawn_tile(&mut commands, board_entity, &font_spec, position, points),
+                board: board_entity,
+                position,
+                points,
+            });
+        }
+    }
+
+    /// Ends the level once its goal is met or its moves run out.
+    fn sys_check_goal(
+        game_mode: Res<GameMode>,
+        levels: Levels,
+        query_board: Query<&BoardGame, SoloBoard>,
+        tiles: Query<&tile::Points>,
+        mut game_state: ResMut<NextState<GameState>>,
+    ) {
+        let Some(level) = levels.of_mode(*game_mode) else {
+            return;
+        };
+        let Ok(board_game) = query_board.get_single() else {
+            return;
+        };
+
+        let values = tiles.iter().map(|points| points.value).collect::<Vec<_>>();
+        if level.goal.is_met(board_game.score, &values) || board_game.moves >= level.max_moves {
+            game_state.set(GameState::GameOver);
+        }
+    }
+
+    fn on_game_over(
+        mut results: ResMut<PuzzleResults>,
+        mut card: ResMut<PuzzleCard>,
+        game_mode: Res<GameMode>,
+        levels: Levels,
+        query_board: Query<&BoardGame, SoloBoard>,
+        tiles: Query<&tile::Points>,
+    ) {
+        let Some(level) = levels.of_mode(*game_mode) else {
+            return;
+        };
+        let Ok(board_game) = query_board.get_single() else {
+            return;
+        };
+
+        let values = tiles.iter().map(|points| points.value).collect::<Vec<_>>();
+        let completed = level.goal.is_met(board_game.score, &values);
+        let stars = if completed {
+            level.stars(board_game.moves)
+        } else {
+            0
+        };
+
+        // Play-tests of the edited level aren't recorded
+        if let (true, GameMode::Puzzle { level: index }) = (completed, *game_mode) {
+            results.record(Level::BUILT_IN[index as usize], stars, board_game.moves);
+            storage::save(PuzzleResults::FILE, results.as_ref());
+        }
+
+        card.0 = Some(PuzzleOutcome {
+            mode: *game_mode,
+            name: level.name.clone(),
+            goal: level.goal,
+            stars,
+            moves: board_game.moves,
+        });
+    }
+
+    fn on_exit_game_over(mut card: ResMut<PuzzleCard>) {
+        card.0 = None;
+    }
+}
+
+impl Plugin for PuzzlePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Level>()
+            .init_asset_loader::<LevelLoader>()
+            .init_resource::<LevelSpec>()
+            .insert_resource(PuzzleResults::load())
+            .init_resource::<PuzzleCard>()
+            .init_resource::<SpawnCursor>()
+            .add_system(
+                PuzzlePlugin::sys_scripted_spawns
+                    .after(BoardShiftDirection::sys_handle_board_shift_on_keypress),
+            )
+            .add_system(
+                PuzzlePlugin::sys_check_goal
+                    .before(BoardShiftDirection::sys_handle_board_shift_on_keypress)
+                    .in_set(OnUpdate(GameState::Playing)),
+            )
+            .add_system(PuzzlePlugin::on_game_over.in_schedule(OnEnter(GameState::GameOver)))
+            .add_system(PuzzlePlugin::on_exit_game_over.in_schedule(OnExit(GameState::GameOver)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_levels_are_valid() {
+        for name in Level::BUILT_IN {
+            let path = format!(
+                "{}/assets/{}",
+                env!("CARGO_MANIFEST_DIR"),
+                Level::path(name)
+            );
+            let level: Level = ron::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+            assert_eq!(level.check(), Ok(()), "{name}");
+        }
+    }
+
+    #[test]
+    fn check_rejects_uneven_boards() {
+        let mut level = Level::default();
+        level.board[2].pop();
+        assert_eq!(
+            level.check(),
+            Err("row 2 has 3 cells instead of 4".to_string())
+        );
+
+        let level = Level {
+            blockers: vec![(1, 4)],
+            ..default()
+        };
+        assert!(level.check().is_err());
+
+        let level = Level {
+            board: Vec::new(),
+            ..default()
+        };
+        assert!(level.check().is_err());
+    }
+
+    #[test]
+    fn check_rejects_blockers_on_tiles() {
+        let mut level = Level {
+            blockers: vec![(0, 0), (2, 1)],
+            ..default()
+        };
+        assert_eq!(level.check(), Ok(()));
+
+        level.board[1][2] = 8;
+        assert_eq!(
+            level.check(),
+            Err("the blocker at (2, 1) is on a tile".to_string())
+        );
+    }
+
+    #[test]
+    fn spawns_fill_the_first_empty_cell_from_the_top_left() {
+        let level = Level {
+            spawns: vec![2, 4],
+            ..default()
+        };
+        let top_left = tile::Position { x: 0, y: 3, z: 0 };
+
+        assert_eq!(level.spawn(0, &[]), Some((top_left, tile::Points::new(2))));
+        assert_eq!(
+            level.spawn(1, &[top_left]),
+            Some((tile::Position { x: 1, ..top_left }, tile::Points::new(4)))
+        );
+        assert_eq!(level.spawn(2, &[]), None);
+    }
+}
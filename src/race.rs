@@ -0,0 +1,62 @@
+use bevy::prelude::*;
+
+use crate::{
+    board::{Board, BoardShiftDirection, Caption},
+    bot::{Bot, BotPlugin},
+    game::{BoardGame, GameMode, GameState, SoloBoard},
+    versus::{VersusCard, VersusOutcome},
+};
+
+/// The player races a [`Bot`] on the same seed in [`GameMode::Race`].
+///
+/// Both play on until their board is stuck, the best score wins. The result goes on the
+/// [`VersusCard`] like a versus round.
+pub struct RacePlugin;
+
+/// The caption of the board of the player.
+pub const PLAYER_NAME: &str = "Player";
+
+impl RacePlugin {
+    /// Speeds the bot up once the player is stuck, so the race doesn't drag on.
+    fn sys_hurry_bots(
+        player: Query<&BoardGame, SoloBoard>,
+        mut bots: Query<&mut Bot>,
+        game_mode: Res<GameMode>,
+    ) {
+        if !matches!(*game_mode, GameMode::Race { .. }) {
+            return;
+        }
+        if !player.get_single().is_ok_and(|board_game| board_game.over) {
+            return;
+        }
+
+        for mut bot in bots.iter_mut() {
+            bot.hurry();
+        }
+    }
+
+    fn on_game_over(
+        mut card: ResMut<VersusCard>,
+        game_mode: Res<GameMode>,
+        query_board: Query<(&Board, &BoardGame, &Caption)>,
+    ) {
+        if !matches!(*game_mode, GameMode::Race { .. }) {
+            return;
+        }
+
+        card.0 = Some(VersusOutcome::by_score(query_board.iter().collect()));
+    }
+}
+
+impl Plugin for RacePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            RacePlugin::sys_hurry_bots
+                .before(BotPlugin::sys_think)
+                .before(BoardShiftDirection::sys_handle_board_shift_on_keypress)
+                .in_set(OnUpdate(GameState::Playing)),
+        )
+        // The versus card is cleared when leaving the game over screen
+        .add_system(RacePlugin::on_game_over.in_schedule(OnEnter(GameState::GameOver)));
+    }
+}
@@ -0,0 +1,244 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{audio::AudioSettings, board::BoardShiftDirection, storage, theme::Theme};
+
+/// The user settings, loaded from [`Settings::FILE`] at startup and saved whenever they change.
+///
+/// Everything applies live except the board size, which is used by the next game.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub board_size: u8,
+    /// Multiplier of the tile animation speed.
+    pub animation_speed: f32,
+    /// How many moves can be taken back, `0` disables undo.
+    pub undo_limit: u8,
+    /// The chance of a new tile being a 4 instead of a 2.
+    pub spawn_four_probability: f32,
+    /// Name of the theme file in `assets/themes`.
+    pub theme: String,
+    pub audio: AudioSettings,
+    pub key_bindings: KeyBindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        return Self {
+            board_size: 4,
+            animation_speed: 1.,
+            undo_limit: 3,
+            spawn_four_probability: 0.,
+            theme: Theme::BUILT_IN[0].to_string(),
+            audio: AudioSettings::default(),
+            key_bindings: KeyBindings::default(),
+        };
+    }
+}
+
+impl Settings {
+    pub const FILE: &str = "settings.ron";
+
+    pub const BOARD_SIZES: std::ops::RangeInclusive<u8> = 3..=8;
+
+    pub const MAX_UNDO_LIMIT: u8 = 20;
+
+    pub const ANIMATION_SPEEDS: std::ops::RangeInclusive<f32> = 0.25..=4.;
+
+    pub fn load() -> Self {
+        let mut settings: Settings = storage::load(Settings::FILE);
+        settings.clamp();
+        return settings;
+    }
+
+    /// Brings values edited by hand in the settings file back into the ranges of the settings screen.
+    fn clamp(&mut self) {
+        self.board_size = self
+            .board_size
+            .clamp(*Settings::BOARD_SIZES.start(), *Settings::BOARD_SIZES.end());
+        self.undo_limit = self.undo_limit.min(Settings::MAX_UNDO_LIMIT);
+        // The move animations are divided by it
+        self.animation_speed = if self.animation_speed.is_nan() {
+            1.
+        } else {
+            self.animation_speed.clamp(
+                *Settings::ANIMATION_SPEEDS.start(),
+                *Settings::ANIMATION_SPEEDS.end(),
+            )
+        };
+        for probability in [
+            &mut self.spawn_four_probability,
+            &mut self.audio.master_volume,
+            &mut self.audio.sfx_volume,
+            &mut self.audio.music_volume,
+        ] {
+            // `clamp` keeps a NaN as it is
+            *probability = if probability.is_nan() {
+                0.
+            } else {
+                probability.clamp(0., 1.)
+            };
+        }
+    }
+
+    pub fn sys_save_on_change(settings: Res<Settings>) {
+        if settings.is_changed() && !settings.is_added() {
+            storage::save(Settings::FILE, settings.as_ref());
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyBinding {
+    Left,
+    Right,
+    Up,
+    Down,
+    /// Through the layers of a cube, see [`crate::board::Grid::Cube`].
+    In,
+    Out,
+    Undo,
+    Pause,
+    Mute,
+}
+
+impl KeyBinding {
+    pub const ALL: [KeyBinding; 9] = [
+        KeyBinding::Left,
+        KeyBinding::Right,
+        KeyBinding::Up,
+        KeyBinding::Down,
+        KeyBinding::In,
+        KeyBinding::Out,
+        KeyBinding::Undo,
+        KeyBinding::Pause,
+        KeyBinding::Mute,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            KeyBinding::Left => "Left",
+            KeyBinding::Right => "Right",
+            KeyBinding::Up => "Up",
+            KeyBinding::Down => "Down",
+            KeyBinding::In => "In",
+            KeyBinding::Out => "Out",
+            KeyBinding::Undo => "Undo",
+            KeyBinding::Pause => "Pause",
+            KeyBinding::Mute => "Mute",
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub left: Vec<KeyCode>,
+    pub right: Vec<KeyCode>,
+    pub up: Vec<KeyCode>,
+    pub down: Vec<KeyCode>,
+    pub inward: Vec<KeyCode>,
+    pub outward: Vec<KeyCode>,
+    pub undo: Vec<KeyCode>,
+    pub pause: Vec<KeyCode>,
+    pub mute: Vec<KeyCode>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        return Self {
+            left: vec![KeyCode::Left, KeyCode::H, KeyCode::A],
+            right: vec![KeyCode::Right, KeyCode::L, KeyCode::D],
+            up: vec![KeyCode::Up, KeyCode::K, KeyCode::W],
+            down: vec![KeyCode::Down, KeyCode::J, KeyCode::S],
+            inward: vec![KeyCode::E, KeyCode::PageDown],
+            outward: vec![KeyCode::Q, KeyCode::PageUp],
+            undo: vec![KeyCode::U, KeyCode::Back],
+            pause: vec![KeyCode::Escape, KeyCode::P],
+            mute: vec![KeyCode::M],
+        };
+    }
+}
+
+impl KeyBindings {
+    pub fn keys(&self, binding: KeyBinding) -> &Vec<KeyCode> {
+        return match binding {
+            KeyBinding::Left => &self.left,
+            KeyBinding::Right => &self.right,
+            KeyBinding::Up => &self.up,
+            KeyBinding::Down => &self.down,
+            KeyBinding::In => &self.inward,
+            KeyBinding::Out => &self.outward,
+            KeyBinding::Undo => &self.undo,
+            KeyBinding::Pause => &self.pause,
+            KeyBinding::Mute => &self.mute,
+        };
+    }
+
+    pub fn keys_mut(&mut self, binding: KeyBinding) -> &mut Vec<KeyCode> {
+        return match binding {
+            KeyBinding::Left => &mut self.left,
+            KeyBinding::Right => &mut self.right,
+            KeyBinding::Up => &mut self.up,
+            KeyBinding::Down => &mut self.down,
+            KeyBinding::In => &mut self.inward,
+            KeyBinding::Out => &mut self.outward,
+            KeyBinding::Undo => &mut self.undo,
+            KeyBinding::Pause => &mut self.pause,
+            KeyBinding::Mute => &mut self.mute,
+        };
+    }
+
+    pub fn just_pressed(&self, binding: KeyBinding, input: &Input<KeyCode>) -> bool {
+        return input.any_just_pressed(self.keys(binding).iter().copied());
+    }
+
+    pub fn direction(&self, key_code: &KeyCode) -> Option<BoardShiftDirection> {
+        return [
+            (KeyBinding::Left, BoardShiftDirection::Left),
+            (KeyBinding::Right, BoardShiftDirection::Right),
+            (KeyBinding::Up, BoardShiftDirection::Up),
+            (KeyBinding::Down, BoardShiftDirection::Down),
+            (KeyBinding::In, BoardShiftDirection::In),
+            (KeyBinding::Out, BoardShiftDirection::Out),
+        ]
+        .into_iter()
+        .find(|(binding, _)| self.keys(*binding).contains(key_code))
+        .map(|(_, direction)| direction);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamp_brings_values_into_range() {
+        let mut settings = Settings {
+            board_size: 20,
+            animation_speed: 0.,
+            undo_limit: 200,
+            spawn_four_probability: f32::NAN,
+            ..default()
+        };
+        settings.audio.master_volume = -1.;
+        settings.audio.sfx_volume = 3.;
+        settings.clamp();
+
+        assert_eq!(settings.board_size, 8);
+        assert_eq!(settings.animation_speed, 0.25);
+        assert_eq!(settings.undo_limit, Settings::MAX_UNDO_LIMIT);
+        assert_eq!(settings.spawn_four_probability, 0.);
+        assert_eq!(settings.audio.master_volume, 0.);
+        assert_eq!(settings.audio.sfx_volume, 1.);
+
+        for (speed, clamped) in [(f32::NAN, 1.), (-2., 0.25), (10., 4.), (1.5, 1.5)] {
+            let mut settings = Settings {
+                animation_speed: speed,
+                ..default()
+            };
+            settings.clamp();
+            assert_eq!(settings.animation_speed, clamped);
+        }
+    }
+}
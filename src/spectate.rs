@@ -0,0 +1,442 @@
+use std::{
+    collections::VecDeque,
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, Mutex},
+    thread,
+};
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    assets::FontSpec,
+    board::{tile, Board, BoardShiftDirection, BoardShiftedEvent, Caption, TileSpawnedEvent},
+    game::{self, BoardGame, Game, GameMode, GameState, NewGameEvent, SoloBoard},
+    leaderboard::Leaderboards,
+    net::{self, LinkEvent, LinkWriter, Remote},
+    ui::toast::ToastEvent,
+};
+
+/// Shows the game of another instance, for demos.
+///
+/// `--broadcast [ADDRESS]` makes an instance send the board of its player to every spectator
+/// that connects. `--spectate [ADDRESS]` starts an instance in [`GameMode::Spectate`], which
+/// mirrors that board from the moves and spawns it receives and ignores the moves of the keys.
+pub struct SpectatePlugin;
+
+/// Listened on by the broadcaster and joined by the spectator when no address is given.
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7879";
+
+/// A line of the stream sent to the spectators, written as RON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SpectateMessage {
+    /// The whole board, sent to a new spectator and after a new game or an undo.
+    Snapshot {
+        name: String,
+        mode: GameMode,
+        size: u8,
+        blockers: Vec<tile::Position>,
+        tiles: Vec<(tile::Position, tile::Points)>,
+        score: u32,
+        moves: u32,
+    },
+    Move(BoardShiftDirection),
+    Spawn {
+        position: tile::Position,
+        points: tile::Points,
+    },
+}
+
+/// Sends the board of the player to the spectators.
+#[derive(Resource, Default)]
+pub struct Broadcast {
+    /// Listened on when broadcasting was asked for on the command line.
+    pub address: Option<String>,
+    /// Spectators accepted by the listening thread.
+    joined: Option<Mutex<mpsc::Receiver<TcpStream>>>,
+    spectators: Vec<LinkWriter<SpectateMessage>>,
+    /// Sends a snapshot instead of the events of this frame.
+    resync: bool,
+    undos: u32,
+}
+
+impl Broadcast {
+    /// Reads `--broadcast [ADDRESS]`.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Self {
+        return Self {
+            address: address_arg(args, "--broadcast"),
+            ..default()
+        };
+    }
+
+    fn send(&mut self, message: &SpectateMessage) {
+        // Spectators that left or stopped reading are dropped
+        self.spectators.retain(|link| {
+            let sent = link.send(message.clone()).is_ok();
+            if !sent {
+                link.close();
+            }
+            return sent;
+        });
+    }
+}
+
+/// The connection to the instance that is watched.
+#[derive(Resource, Default)]
+pub struct Spectator {
+    /// Joined when spectating was asked for on the command line.
+    pub address: Option<String>,
+    events: Option<Mutex<mpsc::Receiver<LinkEvent<SpectateMessage>>>>,
+    /// Received and not shown on the board yet.
+    queue: VecDeque<SpectateMessage>,
+}
+
+impl Spectator {
+    /// Reads `--spectate [ADDRESS]`.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Self {
+        return Self {
+            address: address_arg(args, "--spectate"),
+            ..default()
+        };
+    }
+
+    pub fn is_watching(&self) -> bool {
+        return self.events.is_some();
+    }
+}
+
+/// The address after `flag`, or the default one when it is followed by nothing or another flag.
+fn address_arg(args: impl IntoIterator<Item = String>, flag: &str) -> Option<String> {
+    let mut args = args.into_iter().peekable();
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            let address = args.next_if(|next| !next.starts_with("--"));
+            return Some(address.unwrap_or_else(|| DEFAULT_ADDRESS.to_string()));
+        }
+    }
+    return None;
+}
+
+impl SpectatePlugin {
+    fn on_startup(
+        mut broadcast: ResMut<Broadcast>,
+        mut spectator: ResMut<Spectator>,
+        mut game_mode: ResMut<GameMode>,
+    ) {
+        if let Some(address) = broadcast.address.clone() {
+            let (sender, receiver) = mpsc::channel();
+            thread::spawn(move || {
+                let listener = match TcpListener::bind(&address) {
+                    Ok(listener) => listener,
+                    Err(err) => {
+                        warn!("Can't broadcast on {address}: {err}");
+                        return;
+                    }
+                };
+                for stream in listener.incoming().flatten() {
+                    let _ = stream.set_nodelay(true);
+                    if sender.send(stream).is_err() {
+                        return;
+                    }
+                }
+            });
+            broadcast.joined = Some(Mutex::new(receiver));
+        }
+
+        if let Some(address) = spectator.address.clone() {
+            let (sender, receiver) = mpsc::channel();
+            thread::spawn(move || match TcpStream::connect(&address) {
+                Ok(stream) => net::run_link(stream, sender),
+                Err(err) => {
+                    let _ = sender.send(LinkEvent::Failed(err.to_string()));
+                }
+            });
+            spectator.events = Some(Mutex::new(receiver));
+            *game_mode = GameMode::Spectate { rule: default() };
+        }
+    }
+
+    /// Resends the board once a new game replaced it, like [`net::NetPlugin`] does with the seed.
+    fn sys_resync_new_games(
+        mut new_game_reader: EventReader<NewGameEvent>,
+        mut broadcast: ResMut<Broadcast>,
+    ) {
+        if new_game_reader.iter().last().is_some() {
+            broadcast.resync = true;
+        }
+    }
+
+    /// Sends what happened on the board of the player this frame.
+    fn sys_broadcast(
+        mut broadcast: ResMut<Broadcast>,
+        mut shifted_reader: EventReader<BoardShiftedEvent>,
+        mut spawned_reader: EventReader<TileSpawnedEvent>,
+        game: Res<Game>,
+        game_mode: Res<GameMode>,
+        leaderboards: Res<Leaderboards>,
+        query_board: Query<(Entity, &Board, &BoardGame), SoloBoard>,
+        tiles: Query<(&tile::Position, Option<&tile::Points>, &Parent)>,
+    ) {
+        let joined = match broadcast.joined.as_ref() {
+            Some(joined) => joined
+                .lock()
+                .expect("Only read here")
+                .try_iter()
+                .collect::<Vec<_>>(),
+            None => return,
+        };
+        if !joined.is_empty() {
+            broadcast.spectators.extend(
+                joined
+                    .into_iter()
+                    .filter_map(|stream| LinkWriter::new(stream).ok()),
+            );
+            broadcast.resync = true;
+        }
+        // The undone board isn't made of moves and spawns
+        if game.undos != broadcast.undos {
+            broadcast.undos = game.undos;
+            broadcast.resync = true;
+        }
+
+        let Ok((board_entity, board, board_game)) = query_board.get_single() else {
+            return;
+        };
+        if broadcast.spectators.is_empty() {
+            return;
+        }
+
+        if broadcast.resync {
+            shifted_reader.clear();
+            spawned_reader.clear();
+            broadcast.resync = false;
+
+            let on_board = tiles
+                .iter()
+                .filter(|(_, _, parent)| parent.get() == board_entity)
+                .map(|(&position, points, _)| (position, points.copied()))
+                .collect::<Vec<_>>();
+            let name = match leaderboards.last_name.as_str() {
+                "" => "Player".to_string(),
+                name => name.to_string(),
+            };
+            broadcast.send(&SpectateMessage::Snapshot {
+                name,
+                mode: *game_mode,
+                size: board.size,
+                blockers: on_board
+                    .iter()
+                    .filter(|(_, points)| points.is_none())
+                    .map(|&(position, _)| position)
+                    .collect(),
+                tiles: on_board
+                    .iter()
+                    .filter_map(|&(position, points)| Some((position, points?)))
+                    .collect(),
+                score: board_game.score,
+                moves: board_game.moves,
+            });
+            return;
+        }
+
+        // A move and then its spawn
+        for event in shifted_reader.iter() {
+            if event.board == board_entity {
+                broadcast.send(&SpectateMessage::Move(event.direction));
+            }
+        }
+        for event in spawned_reader.iter() {
+            if event.board == board_entity {
+                broadcast.send(&SpectateMessage::Spawn {
+                    position: event.position,
+                    points: event.points,
+                });
+            }
+        }
+    }
+
+    /// Replays the received messages on the board, a move at a time.
+    fn sys_follow(
+        mut commands: Commands,
+        mut spectator: ResMut<Spectator>,
+        mut query_board: Query<(Entity, &Board, Option<&mut Remote>, Option<&BoardGame>)>,
+        tiles: Query<(Entity, &tile::Position, &Parent)>,
+        font_spec: Res<FontSpec>,
+        windows: Query<&Window, With<PrimaryWindow>>,
+        mut game_mode: ResMut<GameMode>,
+        mut next_state: ResMut<NextState<GameState>>,
+        mut spawned_writer: EventWriter<TileSpawnedEvent>,
+        mut toast_writer: EventWriter<ToastEvent>,
+    ) {
+        let events = match spectator.events.as_ref() {
+            Some(events) => events
+                .lock()
+                .expect("Only read here")
+                .try_iter()
+                .collect::<Vec<_>>(),
+            None => return,
+        };
+        for event in events {
+            match event {
+                LinkEvent::Connected(_) => {}
+                LinkEvent::Message(message) => spectator.queue.push_back(message),
+                LinkEvent::Closed | LinkEvent::Failed(_) => {
+                    let message = match event {
+                        LinkEvent::Failed(err) => format!("Can't watch the game: {err}"),
+                        _ => "The watched game was closed".to_string(),
+                    };
+                    toast_writer.send(ToastEvent {
+                        title: "Disconnected".to_string(),
+                        message,
+                    });
+                }
+            }
+        }
+
+        // The shift plays the last move before its spawns can be placed
+        for (_, _, remote, board_game) in query_board.iter_mut() {
+            let Some(mut remote) = remote else {
+                continue;
+            };
+            if !remote.moves.is_empty() && !board_game.is_some_and(|board_game| board_game.over) {
+                return;
+            }
+            remote.moves.clear();
+        }
+
+        while let Some(message) = spectator.queue.pop_front() {
+            match message {
+                SpectateMessage::Snapshot {
+                    name,
+                    mode,
+                    size,
+                    blockers,
+                    tiles: snapshot_tiles,
+                    score,
+                    moves,
+                } => {
+                    let boards = query_board
+                        .iter()
+                        .map(|(entity, board, _, _)| (entity, board.clone()));
+                    let (board_entity, board) = Board::lay_out(
+                        &mut commands,
+                        boards,
+                        size,
+                        mode.grid(),
+                        1,
+                        windows.get_single().ok(),
+                    )
+                    .remove(0);
+
+                    for (entity, _, parent) in tiles.iter() {
+                        if parent.get() == board_entity {
+                            commands.entity(entity).despawn_recursive();
+                        }
+                    }
+                    for position in blockers {
+                        board.spawn_blocker(&mut commands, board_entity, position);
+                    }
+                    // A win before the snapshot doesn't play again
+                    let won = snapshot_tiles.iter().any(|(_, points)| {
+                        points.number() >= Some(mode.merge_rule().winning_value())
+                    });
+                    for (position, points) in snapshot_tiles {
+                        board.spawn_tile(&mut commands, board_entity, &font_spec, position, points);
+                    }
+
+                    commands.entity(board_entity).insert((
+                        BoardGame {
+                            score,
+                            moves,
+                            won,
+                            ..BoardGame::new(0)
+                        },
+                        Caption::new(format!("{name} ({})", mode.name())),
+                        Remote::default(),
+                    ));
+                    *game_mode = GameMode::Spectate {
+                        rule: mode.merge_rule(),
+                    };
+                    next_state.set(GameState::Playing);
+                    // The new board entities are there next frame
+                    return;
+                }
+                SpectateMessage::Move(direction) => {
+                    for (_, _, remote, _) in query_board.iter_mut() {
+                        if let Some(mut remote) = remote {
+                            remote.moves.push_back(direction);
+                        }
+                    }
+                    return;
+                }
+                SpectateMessage::Spawn { position, points } => {
+                    for (board_entity, board, remote, _) in query_board.iter() {
+                        if remote.is_none() {
+                            continue;
+                        }
+                        spawned_writer.send(TileSpawnedEvent {
+                            entity: board.spawn_tile(
+                                &mut commands,
+                                board_entity,
+                                &font_spec,
+                                position,
+                                points,
+                            ),
+                            board: board_entity,
+                            position,
+                            points,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Stops watching once another mode is picked.
+    fn sys_leave_on_new_game(
+        mut new_game_reader: EventReader<NewGameEvent>,
+        mut spectator: ResMut<Spectator>,
+        game_mode: Res<GameMode>,
+    ) {
+        if new_game_reader.iter().last().is_none() || !spectator.is_watching() {
+            return;
+        }
+        if !matches!(*game_mode, GameMode::Spectate { .. }) {
+            spectator.events = None;
+            spectator.queue.clear();
+        }
+    }
+
+    /// Shows the move number of the watched game next to its score.
+    fn sys_update_captions(
+        game_mode: Res<GameMode>,
+        mut query_board: Query<(&BoardGame, &mut Caption), With<Remote>>,
+    ) {
+        if !matches!(*game_mode, GameMode::Spectate { .. }) {
+            return;
+        }
+        for (board_game, mut caption) in query_board.iter_mut() {
+            let note = format!("move {}", board_game.moves);
+            if caption.note != note {
+                caption.note = note;
+            }
+        }
+    }
+}
+
+impl Plugin for SpectatePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Broadcast::from_args(std::env::args()))
+            .insert_resource(Spectator::from_args(std::env::args()))
+            .add_startup_system(SpectatePlugin::on_startup)
+            .add_systems((
+                SpectatePlugin::sys_resync_new_games.after(game::reset),
+                SpectatePlugin::sys_leave_on_new_game.after(game::reset),
+                SpectatePlugin::sys_follow
+                    .after(BoardShiftDirection::sys_handle_board_shift_on_keypress),
+                SpectatePlugin::sys_update_captions.before(Board::sys_render_captions),
+            ))
+            // Once the commands of the frame are applied, so a snapshot shows the new game
+            .add_system(SpectatePlugin::sys_broadcast.in_base_set(CoreSet::PostUpdate));
+    }
+}
@@ -0,0 +1,230 @@
+use std::{collections::BTreeMap, fmt::Write, fs, path::PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::{tile, TilesMergedEvent},
+    game::{BoardGame, Game, GameMode, GameState, SoloBoard},
+    storage,
+};
+
+/// Keeps the lifetime [`Statistics`] up to date and saves them after every game.
+pub struct StatisticsPlugin;
+
+/// Lifetime statistics, stored in [`Statistics::FILE`].
+#[derive(Resource, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Statistics {
+    pub games_played: u32,
+    pub wins: u32,
+    pub best_score: u32,
+    pub total_score: u64,
+    pub best_tile: u32,
+    pub total_moves: u64,
+    /// How many merges produced each tile value.
+    pub merges: BTreeMap<u32, u64>,
+    /// The longest game by time spent playing, in seconds.
+    pub longest_game_secs: f32,
+    /// Every finished game, oldest first.
+    pub history: Vec<GameRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameRecord {
+    /// Local date and time at the end of the game, formatted as `YYYY-MM-DD HH:MM`.
+    pub date: String,
+    pub mode: String,
+    pub seed: u64,
+    pub score: u32,
+    pub max_tile: u32,
+    pub moves: u32,
+    pub duration_secs: f32,
+    pub won: bool,
+}
+
+impl Statistics {
+    pub const FILE: &str = "statistics.ron";
+    pub const CSV_FILE: &str = "statistics.csv";
+
+    pub fn load() -> Self {
+        return storage::load(Statistics::FILE);
+    }
+
+    pub fn average_score(&self) -> f32 {
+        if self.games_played == 0 {
+            return 0.;
+        }
+        return self.total_score as f32 / self.games_played as f32;
+    }
+
+    pub fn record(&mut self, record: GameRecord) {
+        self.games_played += 1;
+        if record.won {
+            self.wins += 1;
+        }
+        self.best_score = self.best_score.max(record.score);
+        self.total_score += record.score as u64;
+        self.best_tile = self.best_tile.max(record.max_tile);
+        self.total_moves += record.moves as u64;
+        self.longest_game_secs = self.longest_game_secs.max(record.duration_secs);
+
+        self.history.push(record);
+    }
+
+    /// The game history as CSV, one row per game.
+    pub fn to_csv(&self) -> String {
+        let mut csv = "date,mode,seed,score,max_tile,moves,duration_secs,won\n".to_string();
+        for record in self.history.iter() {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{:.1},{}",
+                record.date,
+                record.mode,
+                record.seed,
+                record.score,
+                record.max_tile,
+                record.moves,
+                record.duration_secs,
+                record.won
+            );
+        }
+        return csv;
+    }
+
+    /// Writes [`Statistics::to_csv`] next to the statistics file.
+    pub fn export_csv(&self) -> Result<PathBuf, String> {
+        let dir = storage::data_dir();
+        let path = dir.join(Statistics::CSV_FILE);
+
+        fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+        fs::write(&path, self.to_csv()).map_err(|err| err.to_string())?;
+
+        return Ok(path);
+    }
+}
+
+impl StatisticsPlugin {
+    fn on_startup(statistics: Res<Statistics>, mut game: ResMut<Game>) {
+        for record in statistics.history.iter() {
+            game.record_score(&record.mode, record.score);
+        }
+    }
+
+    fn sys_count_merges(
+        mut merged_reader: EventReader<TilesMergedEvent>,
+        mut statistics: ResMut<Statistics>,
+        game_mode: Res<GameMode>,
+        solo_boards: Query<(), SoloBoard>,
+    ) {
+        // The play-tests of the editor aren't games of the player either
+        if *game_mode == GameMode::Custom {
+            merged_reader.clear();
+            return;
+        }
+        for event in merged_reader.iter() {
+            // The merges of the bots and the versus players aren't the player's
+            if !solo_boards.contains(event.board) {
+                continue;
+            }
+            if let Some(value) = event.points.number() {
+                *statistics.merges.entry(value).or_default() += 1;
+            }
+        }
+    }
+
+    fn on_game_over(
+        mut statistics: ResMut<Statistics>,
+        game: Res<Game>,
+        game_mode: Res<GameMode>,
+        query_board: Query<&BoardGame, SoloBoard>,
+        tiles: Query<&tile::Points>,
+    ) {
+        if *game_mode == GameMode::Custom {
+            return;
+        }
+        // The versus scores are on its result card, the bots keep no records
+        let Ok(board_game) = query_board.get_single() else {
+            return;
+        };
+
+        let max_tile = tiles
+            .iter()
+            .filter_map(|points| points.number())
+            .max()
+            .unwrap_or(0);
+
+        statistics.record(GameRecord {
+            date: chrono::Local::now().format("%Y-%m-%d %H:%M").to_string(),
+            mode: game_mode.name().to_string(),
+            seed: game.seed,
+            score: board_game.score,
+            max_tile,
+            moves: board_game.moves,
+            duration_secs: game.elapsed.as_secs_f32(),
+            won: max_tile >= game_mode.merge_rule().winning_value(),
+        });
+
+        storage::save(Statistics::FILE, statistics.as_ref());
+    }
+}
+
+impl Plugin for StatisticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Statistics::load())
+            .add_startup_system(StatisticsPlugin::on_startup)
+            .add_system(StatisticsPlugin::sys_count_merges)
+            .add_system(StatisticsPlugin::on_game_over.in_schedule(OnEnter(GameState::GameOver)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(mode: &str, score: u32, max_tile: u32, won: bool) -> GameRecord {
+        return GameRecord {
+            date: "2024-03-01 18:30".to_string(),
+            mode: mode.to_string(),
+            seed: 42,
+            score,
+            max_tile,
+            moves: 120,
+            duration_secs: 95.04,
+            won,
+        };
+    }
+
+    #[test]
+    fn record_adds_up_the_games() {
+        let mut statistics = Statistics::default();
+        statistics.record(record("Classic", 3000, 256, false));
+        statistics.record(record("Time 3 min", 25000, 2048, true));
+
+        assert_eq!(statistics.games_played, 2);
+        assert_eq!(statistics.wins, 1);
+        assert_eq!(statistics.best_score, 25000);
+        assert_eq!(statistics.best_tile, 2048);
+        assert_eq!(statistics.total_moves, 240);
+        assert_eq!(statistics.average_score(), 14000.);
+        assert_eq!(Statistics::default().average_score(), 0.);
+    }
+
+    #[test]
+    fn csv_has_a_row_per_game() {
+        let mut statistics = Statistics::default();
+        assert_eq!(
+            statistics.to_csv(),
+            "date,mode,seed,score,max_tile,moves,duration_secs,won\n"
+        );
+
+        statistics.record(record("Classic", 3000, 256, false));
+        statistics.record(record("Time 3 min", 25000, 2048, true));
+        assert_eq!(
+            statistics.to_csv(),
+            "date,mode,seed,score,max_tile,moves,duration_secs,won\n\
+             2024-03-01 18:30,Classic,42,3000,256,120,95.0,false\n\
+             2024-03-01 18:30,Time 3 min,42,25000,2048,120,95.0,true\n"
+        );
+    }
+}
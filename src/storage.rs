@@ -0,0 +1,52 @@
+//! Reading and writing the RON files kept in the user's data directory.
+use std::{fs, path::PathBuf};
+
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Overrides the data directory, e.g. to keep CI runs away from the real files.
+pub const DATA_DIR_VAR: &str = "BEVY_2048_DATA_DIR";
+
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os(DATA_DIR_VAR) {
+        return PathBuf::from(dir);
+    }
+
+    return dirs::data_dir()
+        .map(|dir| dir.join("bevy-2048"))
+        .unwrap_or_else(|| PathBuf::from("."));
+}
+
+/// Loads `file_name` from the [`data_dir`], a missing or invalid file gives the default value.
+pub fn load<T: DeserializeOwned + Default>(file_name: &str) -> T {
+    let path = data_dir().join(file_name);
+
+    let Ok(contents) = fs::read_to_string(&path) else {
+        return T::default();
+    };
+
+    return match ron::from_str(&contents) {
+        Ok(value) => value,
+        Err(err) => {
+            warn!("Ignoring invalid {}: {err}", path.display());
+            T::default()
+        }
+    };
+}
+
+pub fn save<T: Serialize>(file_name: &str, value: &T) {
+    let dir = data_dir();
+    let path = dir.join(file_name);
+
+    let result = fs::create_dir_all(&dir)
+        .map_err(|err| err.to_string())
+        .and_then(|_| {
+            ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+                .map_err(|err| err.to_string())
+        })
+        .and_then(|contents| fs::write(&path, contents).map_err(|err| err.to_string()));
+
+    if let Err(err) = result {
+        warn!("Couldn't save {}: {err}", path.display());
+    }
+}
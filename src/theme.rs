@@ -0,0 +1,350 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    utils::{BoxedFuture, HashMap, HashSet},
+};
+use serde::{de, Deserialize, Deserializer};
+
+use crate::{assets::FontSpec, board::tile, settings::Settings};
+
+/// Loads the theme picked in the [`Settings`] from `assets/themes/<name>.theme.ron` and
+/// reloads it whenever the file changes.
+pub struct ThemePlugin;
+
+/// The colors, font and corner radii of the game.
+///
+/// Missing fields fall back to the [`Default`] theme, which is the same as `dark.theme.ron`.
+#[derive(Debug, Clone, PartialEq, Deserialize, TypeUuid)]
+#[uuid = "27579c36-59cc-4fa5-a9b0-00e963b398c2"]
+#[serde(default)]
+pub struct Theme {
+    pub name: String,
+    pub background: HexColor,
+    pub board: HexColor,
+    pub empty_cell: HexColor,
+    /// The cells tiles can't enter in the blocker modes.
+    pub blocker: HexColor,
+    /// Tile styles ordered by value, a tile uses the last style with a value not above its own.
+    pub tiles: Vec<TileStyle>,
+    /// The tiles of the power-ups mode that aren't plain numbers.
+    pub special_tiles: SpecialTileColors,
+    /// Text drawn over the background and the menus.
+    pub text: HexColor,
+    /// The score boxes.
+    pub panel: HexColor,
+    /// Text drawn over the panels and buttons.
+    pub panel_text: HexColor,
+    /// Drawn behind the menu screens.
+    pub overlay: HexColor,
+    pub button: ButtonColors,
+    /// Path of the font in the assets folder.
+    pub font: String,
+    /// Corner radius of the board background, as a fraction of the tile size.
+    pub board_radius: f32,
+    /// Corner radius of the tiles and empty cells, as a fraction of the tile size.
+    pub tile_radius: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct TileStyle {
+    pub value: u32,
+    pub background: HexColor,
+    pub text: HexColor,
+}
+
+/// See [`tile::Kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct SpecialTileColors {
+    pub wildcard: HexColor,
+    pub bomb: HexColor,
+    pub multiplier: HexColor,
+    pub negative: HexColor,
+    /// Text drawn over all of them.
+    pub text: HexColor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct ButtonColors {
+    pub normal: HexColor,
+    pub hovered: HexColor,
+    pub pressed: HexColor,
+}
+
+/// A color written as a `"#rrggbb"` or `"#rrggbbaa"` string in the theme files.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HexColor(pub Color);
+
+impl<'de> Deserialize<'de> for HexColor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        return Color::hex(&hex)
+            .map(HexColor)
+            .map_err(|err| de::Error::custom(format!("invalid color {hex:?}: {err:?}")));
+    }
+}
+
+impl From<HexColor> for Color {
+    fn from(value: HexColor) -> Self {
+        return value.0;
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let hex = |hex: &str| HexColor(Color::hex(hex).expect("valid default theme color"));
+
+        return Self {
+            name: "Dark".into(),
+            background: hex("#1f2638"),
+            board: hex("#0f131f"),
+            empty_cell: hex("#a26fc1"),
+            blocker: hex("#0f131f"),
+            tiles: vec![TileStyle {
+                value: 0,
+                background: hex("#f7bfff"),
+                text: hex("#000000"),
+            }],
+            special_tiles: SpecialTileColors {
+                wildcard: hex("#b8860b"),
+                bomb: hex("#c1121f"),
+                multiplier: hex("#1b998b"),
+                negative: hex("#4a4e69"),
+                text: hex("#ffffff"),
+            },
+            text: hex("#ffffff"),
+            panel: hex("#a26fc1"),
+            panel_text: hex("#ffffff"),
+            overlay: hex("#000000d9"),
+            button: ButtonColors {
+                normal: hex("#00276d"),
+                hovered: hex("#5083d9"),
+                pressed: hex("#8cb8ff"),
+            },
+            font: "fonts/FiraSans-Bold.ttf".into(),
+            board_radius: 0.,
+            tile_radius: 0.,
+        };
+    }
+}
+
+impl Theme {
+    /// The themes shipped in `assets/themes`, cycled through by the theme setting.
+    pub const BUILT_IN: [&str; 3] = ["dark", "classic", "high_contrast"];
+
+    pub fn path(name: &str) -> String {
+        return format!("themes/{name}.theme.ron");
+    }
+
+    /// The style of a tile of any [`tile::Kind`].
+    pub fn points_style(&self, points: tile::Points) -> TileStyle {
+        let colors = &self.special_tiles;
+        let background = match points.kind {
+            tile::Kind::Number => return self.tile_style(points.value),
+            tile::Kind::Wildcard => colors.wildcard,
+            tile::Kind::Bomb => colors.bomb,
+            tile::Kind::Multiplier => colors.multiplier,
+            tile::Kind::Negative => colors.negative,
+        };
+        return TileStyle {
+            value: points.value,
+            background,
+            text: colors.text,
+        };
+    }
+
+    pub fn tile_style(&self, value: u32) -> TileStyle {
+        return self
+            .tiles
+            .iter()
+            .take_while(|style| style.value <= value)
+            .last()
+            .or(self.tiles.first())
+            .copied()
+            .unwrap_or(TileStyle {
+                value: 0,
+                background: self.empty_cell,
+                text: self.text,
+            });
+    }
+}
+
+#[derive(Default)]
+struct ThemeLoader;
+
+impl AssetLoader for ThemeLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        return Box::pin(async move {
+            let theme: Theme = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(theme));
+            Ok(())
+        });
+    }
+
+    fn extensions(&self) -> &[&str] {
+        return &["theme.ron"];
+    }
+}
+
+/// The theme in use, updated once the selected theme file is (re)loaded.
+#[derive(Resource, Default, Deref)]
+pub struct CurrentTheme(pub Theme);
+
+/// The handle of the theme file picked in the settings.
+#[derive(Resource, Default)]
+struct ThemeHandle(Handle<Theme>);
+
+/// White rounded rectangle textures used to tint sprites with rounded corners,
+/// cached by pixel size and radius.
+#[derive(Resource, Default)]
+pub struct RoundedRects {
+    textures: HashMap<(u32, u32, u32), Handle<Image>>,
+    /// The textures asked for since the last [`RoundedRects::evict_unused`].
+    used: HashSet<(u32, u32, u32)>,
+}
+
+impl RoundedRects {
+    pub fn get(&mut self, images: &mut Assets<Image>, size: Vec2, radius: f32) -> Handle<Image> {
+        let width = size.x.round().max(1.) as u32;
+        let height = size.y.round().max(1.) as u32;
+        let radius = radius.round().clamp(0., width.min(height) as f32 / 2.) as u32;
+
+        self.used.insert((width, height, radius));
+        return self
+            .textures
+            .entry((width, height, radius))
+            .or_insert_with(|| images.add(RoundedRects::image(width, height, radius as f32)))
+            .clone();
+    }
+
+    /// Forgets the textures that weren't asked for since the last call, like the sizes before
+    /// a window resize or the radii of the previous theme. Their images are freed once no
+    /// sprite uses them anymore.
+    pub fn evict_unused(&mut self) {
+        let used = std::mem::take(&mut self.used);
+        self.textures.retain(|key, _| used.contains(key));
+    }
+
+    fn image(width: u32, height: u32, radius: f32) -> Image {
+        let mut data = Vec::with_capacity((width * height * 4) as usize);
+
+        for y in 0..height {
+            for x in 0..width {
+                let point = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                // The closest point of the rectangle shrunk by the radius
+                let inner = point.clamp(
+                    Vec2::splat(radius),
+                    Vec2::new(width as f32 - radius, height as f32 - radius),
+                );
+                let distance = point.distance(inner);
+                let alpha = if distance == 0. {
+                    1.
+                } else {
+                    (radius - distance + 0.5).clamp(0., 1.)
+                };
+
+                data.extend([255, 255, 255, (alpha * 255.) as u8]);
+            }
+        }
+
+        return Image::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+        );
+    }
+}
+
+impl ThemePlugin {
+    fn sys_load_selected_theme(
+        settings: Res<Settings>,
+        asset_server: Res<AssetServer>,
+        mut handle: ResMut<ThemeHandle>,
+    ) {
+        if !settings.is_changed() {
+            return;
+        }
+
+        let new_handle = asset_server.load(Theme::path(&settings.theme));
+        if new_handle != handle.0 {
+            handle.0 = new_handle;
+        }
+    }
+
+    fn sys_update_current_theme(
+        handle: Res<ThemeHandle>,
+        mut events: EventReader<AssetEvent<Theme>>,
+        themes: Res<Assets<Theme>>,
+        mut current: ResMut<CurrentTheme>,
+    ) {
+        let was_modified = events.iter().any(|event| match event {
+            AssetEvent::Created { handle: changed } | AssetEvent::Modified { handle: changed } => {
+                *changed == handle.0
+            }
+            AssetEvent::Removed { .. } => false,
+        });
+
+        // A theme loaded before doesn't send a new event when picked again
+        if !was_modified && !handle.is_changed() {
+            return;
+        }
+
+        if let Some(theme) = themes.get(&handle.0) {
+            if current.0 != *theme {
+                current.0 = theme.clone();
+            }
+        }
+    }
+
+    fn sys_apply_theme(
+        theme: Res<CurrentTheme>,
+        asset_server: Res<AssetServer>,
+        mut clear_color: ResMut<ClearColor>,
+        mut font_spec: ResMut<FontSpec>,
+        mut texts: Query<&mut Text>,
+    ) {
+        if !theme.is_changed() {
+            return;
+        }
+
+        clear_color.0 = theme.background.into();
+
+        let font = asset_server.load(theme.font.as_str());
+        if font != font_spec.family {
+            for mut text in texts.iter_mut() {
+                for section in text.sections.iter_mut() {
+                    section.style.font = font.clone();
+                }
+            }
+            font_spec.family = font;
+        }
+    }
+}
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Theme>()
+            .init_asset_loader::<ThemeLoader>()
+            .init_resource::<CurrentTheme>()
+            .init_resource::<ThemeHandle>()
+            .init_resource::<RoundedRects>()
+            .add_systems(
+                (
+                    ThemePlugin::sys_load_selected_theme,
+                    ThemePlugin::sys_update_current_theme,
+                    ThemePlugin::sys_apply_theme,
+                )
+                    .chain(),
+            );
+    }
+}
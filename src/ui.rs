@@ -1,6 +1,16 @@
 use bevy::prelude::*;
 
-use crate::{assets::FontSpec, game::Game};
+use crate::{
+    assets::FontSpec,
+    board::{tile::TileText, Board},
+    game::{BoardGame, Game, GameMode, SoloBoard},
+    leaderboard::{Leaderboards, RuleSet},
+    puzzle::Levels,
+    theme::CurrentTheme,
+};
+
+mod menu;
+pub mod toast;
 
 pub struct UIPlugin;
 
@@ -38,13 +48,52 @@ impl UIPlugin {
                         ..default()
                     })
                     .with_children(|parent| {
+                        // mode box, hidden in the modes without a countdown
+                        parent
+                            .spawn((
+                                NodeBundle {
+                                    style: score_box::CONTAINER_STYLE,
+                                    ..default()
+                                },
+                                score_box::ScoreBox,
+                                ModeBox,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    TextBundle::from_section(
+                                        "<label>",
+                                        TextStyle {
+                                            font: font_spec.family.clone(),
+                                            font_size: 15.0,
+                                            color: Color::WHITE,
+                                        },
+                                    )
+                                    .with_text_alignment(TextAlignment::Center),
+                                    ModeLabel,
+                                ));
+                                parent.spawn((
+                                    TextBundle::from_section(
+                                        "<value>",
+                                        TextStyle {
+                                            font: font_spec.family.clone(),
+                                            font_size: 20.0,
+                                            color: Color::WHITE,
+                                        },
+                                    )
+                                    .with_text_alignment(TextAlignment::Center),
+                                    ModeDisplay,
+                                ));
+                            });
+                        // end mode box
                         // scorebox
                         parent
-                            .spawn(NodeBundle {
-                                style: score_box::CONTAINER_STYLE,
-                                background_color: BackgroundColor(score_box::BG_COLOR),
-                                ..default()
-                            })
+                            .spawn((
+                                NodeBundle {
+                                    style: score_box::CONTAINER_STYLE,
+                                    ..default()
+                                },
+                                score_box::ScoreBox,
+                            ))
                             .with_children(|parent| {
                                 parent.spawn(
                                     TextBundle::from_section(
@@ -73,11 +122,13 @@ impl UIPlugin {
                         // end scorebox
                         // best scorebox
                         parent
-                            .spawn(NodeBundle {
-                                style: score_box::CONTAINER_STYLE,
-                                background_color: BackgroundColor(score_box::BG_COLOR),
-                                ..default()
-                            })
+                            .spawn((
+                                NodeBundle {
+                                    style: score_box::CONTAINER_STYLE,
+                                    ..default()
+                                },
+                                score_box::ScoreBox,
+                            ))
                             .with_children(|parent| {
                                 parent.spawn(
                                     TextBundle::from_section(
@@ -106,53 +157,185 @@ impl UIPlugin {
                         // end best scorebox
                     });
 
-                parent
-                    .spawn(ButtonBundle {
-                        style: Style {
-                            size: Size::new(Val::Px(130.0), Val::Px(50.0)),
-                            justify_content: JustifyContent::Center,
-                            align_items: AlignItems::Center,
-                            ..default()
-                        },
-                        background_color: button::colors::NORMAL.into(),
-                        ..default()
-                    })
-                    .with_children(|parent| {
-                        parent.spawn(TextBundle {
-                            text: Text::from_section(
-                                "Button",
-                                TextStyle {
-                                    font: font_spec.family.clone(),
-                                    font_size: 20.0,
-                                    color: Color::rgb(0.9, 0.9, 0.9),
-                                },
-                            ),
-                            ..default()
-                        });
-                    });
+                button::spawn(
+                    parent,
+                    &font_spec,
+                    "Button",
+                    button::ButtonAction::EndOrNewGame,
+                )
+                .insert(button::HeaderButton);
             });
     }
 
+    /// Shows the score of the player, or the best one of the boards without one, next to the
+    /// best score of the mode, or its best time when it ranks by time.
     fn sys_score_board(
         game: Res<Game>,
+        game_mode: Res<GameMode>,
+        leaderboards: Res<Leaderboards>,
+        query_board: Query<&BoardGame>,
+        query_player: Query<(&Board, &BoardGame), SoloBoard>,
         mut query_score: Query<&mut Text, (With<ScoreDisplay>, Without<BestScoreDisplay>)>,
         mut query_best_score: Query<&mut Text, (With<BestScoreDisplay>, Without<ScoreDisplay>)>,
     ) {
         let mut text = query_score.single_mut();
-        text.sections[0].value = game.score.to_string();
+        let score = match query_player.get_single() {
+            Ok((_, board_game)) => board_game.score,
+            Err(_) => query_board
+                .iter()
+                .map(|board_game| board_game.score)
+                .max()
+                .unwrap_or(0),
+        };
+        text.sections[0].value = score.to_string();
 
         let mut text = query_best_score.single_mut();
-        text.sections[0].value = game.best_score.to_string();
+        text.sections[0].value = if game_mode.ranks_by_time() {
+            // The leaderboard only keeps the completed games of these
+            query_player
+                .get_single()
+                .ok()
+                .and_then(|(board, _)| {
+                    leaderboards
+                        .entries(RuleSet {
+                            board_size: board.size,
+                            mode: *game_mode,
+                        })
+                        .first()
+                })
+                .map_or("-".to_string(), |entry| {
+                    menu::MenuPlugin::format_duration(entry.duration_secs)
+                })
+        } else {
+            game.best_score(*game_mode).to_string()
+        };
+    }
+
+    /// Shows the time or moves left of the [`GameMode`] in the header.
+    fn sys_mode_box(
+        game: Res<Game>,
+        game_mode: Res<GameMode>,
+        levels: Levels,
+        query_board: Query<&BoardGame, SoloBoard>,
+        mut query_box: Query<&mut Style, With<ModeBox>>,
+        mut query_label: Query<&mut Text, (With<ModeLabel>, Without<ModeDisplay>)>,
+        mut query_value: Query<&mut Text, (With<ModeDisplay>, Without<ModeLabel>)>,
+    ) {
+        let moves_taken = query_board
+            .get_single()
+            .map_or(0, |board_game| board_game.moves);
+        let counter = match *game_mode {
+            GameMode::Classic
+            | GameMode::Daily
+            | GameMode::Blockers { .. }
+            | GameMode::Merge { .. }
+            | GameMode::Hex
+            | GameMode::Cube
+            | GameMode::Wrap
+            | GameMode::PowerUps
+            | GameMode::Gallery
+            | GameMode::Race { .. }
+            | GameMode::Lan
+            | GameMode::Spectate { .. } => None,
+            GameMode::TimeAttack { .. } | GameMode::Versus => {
+                let limit = game_mode.time_limit().unwrap_or_default();
+                Some((
+                    "Time left",
+                    limit.saturating_sub(game.elapsed).as_secs_f32().ceil(),
+                ))
+            }
+            GameMode::Sprint { .. } => Some(("Time", game.elapsed.as_secs_f32())),
+            GameMode::MoveLimit { moves } => {
+                Some(("Moves left", moves.saturating_sub(moves_taken) as f32))
+            }
+            GameMode::Puzzle { .. } | GameMode::Custom => levels.of_mode(*game_mode).map(|level| {
+                (
+                    "Moves left",
+                    level.max_moves.saturating_sub(moves_taken) as f32,
+                )
+            }),
+        };
+
+        let display = if counter.is_some() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        let mut style = query_box.single_mut();
+        if style.display != display {
+            style.display = display;
+        }
+
+        let Some((label, value)) = counter else {
+            return;
+        };
+        query_label.single_mut().sections[0].value = label.to_string();
+        query_value.single_mut().sections[0].value = match *game_mode {
+            GameMode::MoveLimit { .. } | GameMode::Puzzle { .. } | GameMode::Custom => {
+                value.to_string()
+            }
+            _ => menu::MenuPlugin::format_duration(value),
+        };
+    }
+}
+
+impl UIPlugin {
+    /// Colors the score boxes, menu overlays and texts with the [`CurrentTheme`].
+    ///
+    /// Runs in the `PostUpdate` set so nodes spawned this frame are themed before being drawn.
+    fn sys_apply_theme(
+        theme: Res<CurrentTheme>,
+        mut backgrounds: Query<
+            (&mut BackgroundColor, Option<&score_box::ScoreBox>),
+            Or<(With<score_box::ScoreBox>, With<menu::MenuScreen>)>,
+        >,
+        mut texts: Query<(&mut Text, Option<&Parent>), (With<Node>, Without<TileText>)>,
+        panels: Query<(), Or<(With<Button>, With<score_box::ScoreBox>)>>,
+    ) {
+        for (mut background, score_box) in backgrounds.iter_mut() {
+            let color = match score_box {
+                Some(_) => theme.panel.0,
+                None => theme.overlay.0,
+            };
+            if background.0 != color {
+                background.0 = color;
+            }
+        }
+
+        for (mut text, parent) in texts.iter_mut() {
+            let on_panel = parent.is_some_and(|parent| panels.contains(parent.get()));
+            let color = if on_panel {
+                theme.panel_text.0
+            } else {
+                theme.text.0
+            };
+
+            if text
+                .sections
+                .iter()
+                .any(|section| section.style.color != color)
+            {
+                for section in text.sections.iter_mut() {
+                    section.style.color = color;
+                }
+            }
+        }
     }
 }
 
 impl Plugin for UIPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(UIPlugin::on_startup).add_systems((
-            UIPlugin::sys_score_board,
-            button::interaction_system,
-            button::text_system,
-        ));
+        app.add_startup_system(UIPlugin::on_startup)
+            .add_systems((
+                UIPlugin::sys_score_board,
+                UIPlugin::sys_mode_box,
+                button::interaction_system,
+                button::action_system,
+                button::text_system,
+            ))
+            .add_system(UIPlugin::sys_apply_theme.in_base_set(CoreSet::PostUpdate))
+            .add_plugin(menu::MenuPlugin)
+            .add_plugin(toast::ToastPlugin);
     }
 }
 
@@ -162,6 +345,15 @@ pub struct ScoreDisplay;
 #[derive(Component)]
 pub struct BestScoreDisplay;
 
+#[derive(Component)]
+pub struct ModeBox;
+
+#[derive(Component)]
+pub struct ModeLabel;
+
+#[derive(Component)]
+pub struct ModeDisplay;
+
 mod score_box {
     use bevy::prelude::*;
 
@@ -177,74 +369,210 @@ mod score_box {
         ..Style::DEFAULT
     };
 
-    pub const BG_COLOR: Color = Color::Lcha {
-        lightness: 0.55,
-        chroma: 0.5,
-        hue: 315.0,
-        alpha: 1.0,
-    };
+    #[derive(Component)]
+    pub struct ScoreBox;
 }
 
 mod button {
-    use bevy::prelude::*;
+    use bevy::{app::AppExit, ecs::system::EntityCommands, prelude::*};
 
-    use crate::game::GameState;
+    use super::menu::{
+        KeyCapture, LeaderboardAxis, LeaderboardView, ReturnTo, SettingKind, StatusText,
+    };
+    use crate::{
+        assets::FontSpec,
+        editor::{Editor, EditorField},
+        game::{GameMode, GameState, NewGameEvent},
+        net::LanCommand,
+        settings::{KeyBinding, Settings},
+        stats::Statistics,
+        theme::CurrentTheme,
+    };
 
-    pub(super) mod colors {
-        use bevy::prelude::*;
+    /// What happens when a button is clicked.
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    pub enum ButtonAction {
+        /// Ends the running game, or starts a new one when it is over.
+        EndOrNewGame,
+        NewGame,
+        Continue,
+        Resume,
+        MainMenu,
+        Modes,
+        Settings,
+        Statistics,
+        /// Writes the game history to a CSV file.
+        ExportStatistics,
+        Leaderboard,
+        /// Shows the leaderboard of the previous or next board size or mode.
+        CycleLeaderboard(LeaderboardAxis, i8),
+        Achievements,
+        DailyResults,
+        /// The level select screen of the puzzles.
+        Levels,
+        Editor,
+        AdjustEditor(EditorField, i8),
+        /// Saves the edited level to [`Editor::FILE`].
+        SaveLevel,
+        /// Empties the board of the edited level.
+        ClearLevel,
+        /// Leaves the current menu screen.
+        Back,
+        Quit,
+        SelectMode(GameMode),
+        AdjustSetting(SettingKind, i8),
+        /// Waits for a key press to add it to the binding.
+        CaptureKey(KeyBinding),
+        Lan(LanCommand),
+    }
 
-        pub const NORMAL: Color = Color::Lcha {
-            lightness: 0.15,
-            chroma: 0.5,
-            hue: 281.0,
-            alpha: 1.0,
-        };
-        pub const HOVERED: Color = Color::Lcha {
-            lightness: 0.55,
-            chroma: 0.5,
-            hue: 281.0,
-            alpha: 1.0,
-        };
-        pub const PRESSED: Color = Color::Lcha {
-            lightness: 0.75,
-            chroma: 0.5,
-            hue: 281.0,
-            alpha: 1.0,
-        };
+    /// The button in the header, its label follows the [`GameState`].
+    #[derive(Component)]
+    pub struct HeaderButton;
+
+    pub fn spawn<'w, 's, 'a>(
+        parent: &'a mut ChildBuilder<'w, 's, '_>,
+        font_spec: &FontSpec,
+        label: &str,
+        action: ButtonAction,
+    ) -> EntityCommands<'w, 's, 'a> {
+        let mut button = parent.spawn((
+            ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Px(130.0), Val::Px(50.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            action,
+        ));
+        button.with_children(|parent| {
+            parent.spawn(TextBundle {
+                text: Text::from_section(
+                    label,
+                    TextStyle {
+                        font: font_spec.family.clone(),
+                        font_size: 20.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                ),
+                ..default()
+            });
+        });
+
+        return button;
     }
 
+    /// Colors the buttons with the [`CurrentTheme`], it runs every frame to pick up new buttons
+    /// and theme changes.
     pub fn interaction_system(
-        mut interaction_query: Query<
-            (&Interaction, &mut BackgroundColor),
-            (Changed<Interaction>, With<Button>),
-        >,
+        mut interaction_query: Query<(&Interaction, &mut BackgroundColor), With<Button>>,
+        theme: Res<CurrentTheme>,
+    ) {
+        for (&interaction, mut bg_color) in interaction_query.iter_mut() {
+            let color = match interaction {
+                Interaction::Clicked => theme.button.pressed,
+                Interaction::Hovered => theme.button.hovered,
+                Interaction::None => theme.button.normal,
+            };
+
+            if bg_color.0 != color.0 {
+                bg_color.0 = color.0;
+            }
+        }
+    }
+
+    pub fn action_system(
+        interaction_query: Query<(&Interaction, &ButtonAction), Changed<Interaction>>,
         run_state: Res<State<GameState>>,
         mut next_state: ResMut<NextState<GameState>>,
+        mut new_game_writer: EventWriter<NewGameEvent>,
+        mut exit_writer: EventWriter<AppExit>,
+        mut return_to: ResMut<ReturnTo>,
+        mut game_mode: ResMut<GameMode>,
+        mut settings: ResMut<Settings>,
+        mut capture: ResMut<KeyCapture>,
+        statistics: Res<Statistics>,
+        mut status_query: Query<&mut Text, With<StatusText>>,
+        mut leaderboard_view: ResMut<LeaderboardView>,
+        mut editor: ResMut<Editor>,
+        mut lan_writer: EventWriter<LanCommand>,
     ) {
-        for (&interaction, mut bg_color) in interaction_query.iter_mut() {
-            match interaction {
-                Interaction::Clicked => {
-                    *bg_color = colors::PRESSED.into();
-                    match run_state.0 {
-                        GameState::Playing => {
-                            next_state.set(GameState::GameOver);
-                        }
-                        GameState::GameOver => {
-                            next_state.set(GameState::Playing);
-                        }
+        for (&interaction, &action) in interaction_query.iter() {
+            if interaction != Interaction::Clicked {
+                continue;
+            }
+
+            match action {
+                ButtonAction::EndOrNewGame => match run_state.0 {
+                    GameState::Playing | GameState::Paused => {
+                        next_state.set(GameState::GameOver);
+                    }
+                    GameState::GameOver => new_game_writer.send(NewGameEvent::default()),
+                    GameState::MainMenu
+                    | GameState::Modes
+                    | GameState::Settings
+                    | GameState::Statistics
+                    | GameState::Leaderboard
+                    | GameState::Achievements
+                    | GameState::DailyResults
+                    | GameState::Levels
+                    | GameState::Editor
+                    | GameState::Lobby => {}
+                },
+                ButtonAction::NewGame => new_game_writer.send(NewGameEvent::default()),
+                ButtonAction::Continue | ButtonAction::Resume => {
+                    next_state.set(GameState::Playing);
+                }
+                ButtonAction::MainMenu => next_state.set(GameState::MainMenu),
+                ButtonAction::Modes => next_state.set(GameState::Modes),
+                ButtonAction::Settings => {
+                    return_to.0 = run_state.0.clone();
+                    next_state.set(GameState::Settings);
+                }
+                ButtonAction::Statistics => next_state.set(GameState::Statistics),
+                ButtonAction::ExportStatistics => {
+                    let message = match statistics.export_csv() {
+                        Ok(path) => format!("Exported to {}", path.display()),
+                        Err(err) => format!("Export failed: {err}"),
                     };
+                    for mut text in status_query.iter_mut() {
+                        text.sections[0].value = message.clone();
+                    }
                 }
-                Interaction::Hovered => {
-                    *bg_color = colors::HOVERED.into();
-                    // text.sections[0].value = "Restart".into();
+                ButtonAction::Leaderboard => next_state.set(GameState::Leaderboard),
+                ButtonAction::CycleLeaderboard(axis, step) => leaderboard_view.cycle(axis, step),
+                ButtonAction::Achievements => next_state.set(GameState::Achievements),
+                ButtonAction::DailyResults => next_state.set(GameState::DailyResults),
+                ButtonAction::Levels => next_state.set(GameState::Levels),
+                ButtonAction::Editor => next_state.set(GameState::Editor),
+                ButtonAction::AdjustEditor(field, step) => field.adjust(&mut editor, step),
+                ButtonAction::SaveLevel => {
+                    let path = editor.save();
+                    for mut text in status_query.iter_mut() {
+                        text.sections[0].value = format!("Saved to {}", path.display());
+                    }
                 }
-                Interaction::None => *bg_color = colors::NORMAL.into(),
-            };
+                ButtonAction::ClearLevel => editor.clear(),
+                ButtonAction::Back => next_state.set(return_to.0.clone()),
+                ButtonAction::Quit => exit_writer.send(AppExit),
+                ButtonAction::SelectMode(mode) => {
+                    *game_mode = mode;
+                    new_game_writer.send(NewGameEvent::default());
+                }
+                ButtonAction::AdjustSetting(kind, step) => {
+                    kind.adjust(&mut settings, &mut capture, step)
+                }
+                ButtonAction::CaptureKey(binding) => capture.0 = Some(binding),
+                ButtonAction::Lan(command) => lan_writer.send(command),
+            }
         }
     }
 
     pub fn text_system(
-        button_query: Query<&Children, With<Button>>,
+        button_query: Query<&Children, With<HeaderButton>>,
         mut text_query: Query<&mut Text>,
         run_state: Res<State<GameState>>,
     ) {
@@ -255,8 +583,8 @@ mod button {
         let mut text = text_query.get_mut(*first_child_entity).unwrap();
 
         match run_state.0 {
-            GameState::Playing => text.sections[0].value = "End Game".to_string(),
             GameState::GameOver => text.sections[0].value = "New Game".to_string(),
+            _ => text.sections[0].value = "End Game".to_string(),
         }
     }
 }
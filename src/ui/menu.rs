@@ -0,0 +1,1430 @@
+use bevy::prelude::*;
+
+use super::button::{self, ButtonAction};
+use crate::{
+    achievements::{Achievements, ACHIEVEMENTS},
+    assets::FontSpec,
+    bot::Difficulty,
+    daily::{DailyCard, DailyResults},
+    editor::{Editor, EditorField},
+    game::{Game, GameMode, GameState},
+    leaderboard::{Leaderboards, PendingEntry, RuleSet},
+    net::{Lan, LanCommand, LanStatusText},
+    puzzle::{Level, Levels, PuzzleCard, PuzzleResults},
+    settings::{KeyBinding, Settings},
+    stats::Statistics,
+    storage,
+    theme::{CurrentTheme, Theme},
+    versus::VersusCard,
+};
+
+/// The main menu, modes, settings, statistics, leaderboard, achievements, level select, lobby and
+/// pause screens drawn over the board, the level editor panel, the name prompt of a new high score and
+/// the daily challenge, puzzle, versus and race result cards.
+pub struct MenuPlugin;
+
+/// The root node of a menu screen, despawned when leaving its state.
+#[derive(Component)]
+pub struct MenuScreen;
+
+/// The state the settings screen goes back to.
+#[derive(Resource)]
+pub struct ReturnTo(pub GameState);
+
+impl Default for ReturnTo {
+    fn default() -> Self {
+        return Self(GameState::MainMenu);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingKind {
+    BoardSize,
+    AnimationSpeed,
+    UndoLimit,
+    SpawnFourProbability,
+    Theme,
+    MasterVolume,
+    SfxVolume,
+    MusicVolume,
+    Music,
+    Mute,
+    Key(KeyBinding),
+}
+
+impl SettingKind {
+    pub const ALL: [SettingKind; 19] = [
+        SettingKind::BoardSize,
+        SettingKind::AnimationSpeed,
+        SettingKind::UndoLimit,
+        SettingKind::SpawnFourProbability,
+        SettingKind::Theme,
+        SettingKind::MasterVolume,
+        SettingKind::SfxVolume,
+        SettingKind::MusicVolume,
+        SettingKind::Music,
+        SettingKind::Mute,
+        SettingKind::Key(KeyBinding::Left),
+        SettingKind::Key(KeyBinding::Right),
+        SettingKind::Key(KeyBinding::Up),
+        SettingKind::Key(KeyBinding::Down),
+        SettingKind::Key(KeyBinding::In),
+        SettingKind::Key(KeyBinding::Out),
+        SettingKind::Key(KeyBinding::Undo),
+        SettingKind::Key(KeyBinding::Pause),
+        SettingKind::Key(KeyBinding::Mute),
+    ];
+
+    const VOLUME_STEP: f32 = 0.1;
+    const ANIMATION_SPEED_STEP: f32 = 0.25;
+    const PROBABILITY_STEP: f32 = 0.05;
+
+    fn label(&self) -> String {
+        return match self {
+            SettingKind::BoardSize => "Board size".to_string(),
+            SettingKind::AnimationSpeed => "Animation speed".to_string(),
+            SettingKind::UndoLimit => "Undo limit".to_string(),
+            SettingKind::SpawnFourProbability => "Chance of a 4".to_string(),
+            SettingKind::Theme => "Theme".to_string(),
+            SettingKind::MasterVolume => "Master volume".to_string(),
+            SettingKind::SfxVolume => "Effects volume".to_string(),
+            SettingKind::MusicVolume => "Music volume".to_string(),
+            SettingKind::Music => "Music".to_string(),
+            SettingKind::Mute => "Mute".to_string(),
+            SettingKind::Key(binding) => format!("{} keys", binding.name()),
+        };
+    }
+
+    fn display(&self, settings: &Settings) -> String {
+        let percent = |value: f32| format!("{}%", (value * 100.).round());
+        let toggle = |enabled: bool| if enabled { "On" } else { "Off" }.to_string();
+
+        return match self {
+            SettingKind::BoardSize => format!("{0}x{0}", settings.board_size),
+            SettingKind::AnimationSpeed => format!("x{:.2}", settings.animation_speed),
+            SettingKind::UndoLimit => settings.undo_limit.to_string(),
+            SettingKind::SpawnFourProbability => percent(settings.spawn_four_probability),
+            SettingKind::Theme => settings.theme.clone(),
+            SettingKind::MasterVolume => percent(settings.audio.master_volume),
+            SettingKind::SfxVolume => percent(settings.audio.sfx_volume),
+            SettingKind::MusicVolume => percent(settings.audio.music_volume),
+            SettingKind::Music => toggle(settings.audio.music),
+            SettingKind::Mute => toggle(settings.audio.muted),
+            SettingKind::Key(binding) => settings
+                .key_bindings
+                .keys(*binding)
+                .iter()
+                .map(|key_code| format!("{key_code:?}"))
+                .collect::<Vec<_>>()
+                .join(", "),
+        };
+    }
+
+    /// Steps the setting up or down, toggles ignore the sign of `step`.
+    ///
+    /// Key bindings remove their last key here, stepping up waits for a key in [`KeyCapture`].
+    pub fn adjust(&self, settings: &mut Settings, capture: &mut KeyCapture, step: i8) {
+        let step_by = |value: f32, size: f32, max: f32| (value + step as f32 * size).clamp(0., max);
+
+        match self {
+            SettingKind::BoardSize => {
+                settings.board_size = settings
+                    .board_size
+                    .saturating_add_signed(step)
+                    .clamp(*Settings::BOARD_SIZES.start(), *Settings::BOARD_SIZES.end());
+            }
+            SettingKind::AnimationSpeed => {
+                settings.animation_speed = step_by(
+                    settings.animation_speed,
+                    Self::ANIMATION_SPEED_STEP,
+                    *Settings::ANIMATION_SPEEDS.end(),
+                )
+                .max(*Settings::ANIMATION_SPEEDS.start());
+            }
+            SettingKind::UndoLimit => {
+                settings.undo_limit = settings
+                    .undo_limit
+                    .saturating_add_signed(step)
+                    .min(Settings::MAX_UNDO_LIMIT);
+            }
+            SettingKind::SpawnFourProbability => {
+                settings.spawn_four_probability =
+                    step_by(settings.spawn_four_probability, Self::PROBABILITY_STEP, 1.);
+            }
+            SettingKind::Theme => {
+                // A custom theme set in the settings file cycles back to the built-in ones
+                let count = Theme::BUILT_IN.len() as isize;
+                let index = Theme::BUILT_IN
+                    .iter()
+                    .position(|&name| name == settings.theme)
+                    .map_or(0, |index| index as isize + step as isize);
+                settings.theme = Theme::BUILT_IN[index.rem_euclid(count) as usize].to_string();
+            }
+            SettingKind::MasterVolume => {
+                settings.audio.master_volume =
+                    step_by(settings.audio.master_volume, Self::VOLUME_STEP, 1.);
+            }
+            SettingKind::SfxVolume => {
+                settings.audio.sfx_volume =
+                    step_by(settings.audio.sfx_volume, Self::VOLUME_STEP, 1.);
+            }
+            SettingKind::MusicVolume => {
+                settings.audio.music_volume =
+                    step_by(settings.audio.music_volume, Self::VOLUME_STEP, 1.);
+            }
+            SettingKind::Music => settings.audio.music = !settings.audio.music,
+            SettingKind::Mute => settings.audio.muted = !settings.audio.muted,
+            SettingKind::Key(binding) => {
+                let keys = settings.key_bindings.keys_mut(*binding);
+                if step > 0 {
+                    capture.0 = Some(*binding);
+                } else if keys.len() > 1 {
+                    // Every action keeps at least one key
+                    keys.pop();
+                }
+            }
+        }
+    }
+}
+
+/// The binding waiting for the next key press to be added to it.
+#[derive(Resource, Default)]
+pub struct KeyCapture(pub Option<KeyBinding>);
+
+/// The text showing the current value of a setting.
+#[derive(Component)]
+struct SettingValue(SettingKind);
+
+/// The value of a line of the editor panel.
+#[derive(Component)]
+struct EditorValue(EditorField);
+
+/// A line of text reporting the outcome of a button, like the CSV export.
+#[derive(Component)]
+pub struct StatusText;
+
+/// The rule set shown on the leaderboard screen.
+#[derive(Resource)]
+pub struct LeaderboardView(pub RuleSet);
+
+impl Default for LeaderboardView {
+    fn default() -> Self {
+        return Self(RuleSet {
+            board_size: 4,
+            mode: GameMode::Classic,
+        });
+    }
+}
+
+/// The part of the [`LeaderboardView`] a pair of arrows steps through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaderboardAxis {
+    BoardSize,
+    Mode,
+}
+
+impl LeaderboardView {
+    pub fn cycle(&mut self, axis: LeaderboardAxis, step: i8) {
+        match axis {
+            LeaderboardAxis::BoardSize => {
+                let sizes = Settings::BOARD_SIZES.collect::<Vec<_>>();
+                self.0.board_size = MenuPlugin::step_through(&sizes, self.0.board_size, step);
+            }
+            LeaderboardAxis::Mode => {
+                let modes = GameMode::ALL
+                    .into_iter()
+                    .filter(GameMode::has_leaderboard)
+                    .collect::<Vec<_>>();
+                self.0.mode = MenuPlugin::step_through(&modes, self.0.mode, step);
+            }
+        }
+    }
+
+    fn title(&self, axis: LeaderboardAxis) -> String {
+        return match axis {
+            LeaderboardAxis::BoardSize => format!("{0}x{0}", self.0.board_size),
+            LeaderboardAxis::Mode => self.0.mode.name(),
+        };
+    }
+}
+
+/// The rows of the leaderboard screen, rebuilt when the [`LeaderboardView`] changes.
+#[derive(Component)]
+struct LeaderboardTable;
+
+#[derive(Component)]
+struct LeaderboardTitle(LeaderboardAxis);
+
+/// The overlay asking for the name of a new high score.
+#[derive(Component)]
+struct NamePrompt;
+
+#[derive(Component)]
+struct NameText;
+
+#[derive(Component)]
+struct DailyCardScreen;
+
+#[derive(Component)]
+struct PuzzleCardScreen;
+
+#[derive(Component)]
+struct VersusCardScreen;
+
+impl MenuPlugin {
+    /// How many games of the history the statistics screen lists.
+    const RECENT_GAMES: usize = 8;
+
+    /// Formats seconds as `m:ss`.
+    pub fn format_duration(secs: f32) -> String {
+        let secs = secs as u32;
+        return format!("{}:{:02}", secs / 60, secs % 60);
+    }
+
+    /// The item `step` places away from `current`, wrapping around.
+    fn step_through<T: Copy + PartialEq>(items: &[T], current: T, step: i8) -> T {
+        let index = items
+            .iter()
+            .position(|&item| item == current)
+            .map_or(0, |index| index as isize + step as isize);
+        return items[index.rem_euclid(items.len() as isize) as usize];
+    }
+
+    fn spawn_screen(
+        commands: &mut Commands,
+        font_spec: &FontSpec,
+        title: &str,
+        content: impl FnOnce(&mut ChildBuilder),
+    ) -> Entity {
+        return commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                        position_type: PositionType::Absolute,
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+                        gap: Size::all(Val::Px(8.0)),
+                        ..default()
+                    },
+                    z_index: ZIndex::Global(10),
+                    ..default()
+                },
+                MenuScreen,
+            ))
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    title,
+                    TextStyle {
+                        font: font_spec.family.clone(),
+                        font_size: 60.0,
+                        color: Color::WHITE,
+                    },
+                ));
+
+                content(parent);
+            })
+            .id();
+    }
+
+    fn despawn_screen(mut commands: Commands, screens: Query<Entity, With<MenuScreen>>) {
+        for entity in screens.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    fn on_enter_main_menu(mut commands: Commands, font_spec: Res<FontSpec>, game: Res<Game>) {
+        MenuPlugin::spawn_screen(&mut commands, &font_spec, "2048", |parent| {
+            button::spawn(parent, &font_spec, "New Game", ButtonAction::NewGame);
+            if game.in_progress {
+                button::spawn(parent, &font_spec, "Continue", ButtonAction::Continue);
+            }
+            button::spawn(parent, &font_spec, "Modes", ButtonAction::Modes);
+            button::spawn(parent, &font_spec, "Settings", ButtonAction::Settings);
+            button::spawn(parent, &font_spec, "Statistics", ButtonAction::Statistics);
+            button::spawn(parent, &font_spec, "Leaderboard", ButtonAction::Leaderboard);
+            button::spawn(
+                parent,
+                &font_spec,
+                "Achievements",
+                ButtonAction::Achievements,
+            );
+            button::spawn(parent, &font_spec, "Editor", ButtonAction::Editor);
+            button::spawn(parent, &font_spec, "Quit", ButtonAction::Quit);
+        });
+    }
+
+    fn on_enter_paused(mut commands: Commands, font_spec: Res<FontSpec>) {
+        MenuPlugin::spawn_screen(&mut commands, &font_spec, "Paused", |parent| {
+            button::spawn(parent, &font_spec, "Resume", ButtonAction::Resume);
+            button::spawn(parent, &font_spec, "New Game", ButtonAction::NewGame);
+            button::spawn(parent, &font_spec, "Settings", ButtonAction::Settings);
+            button::spawn(parent, &font_spec, "Main Menu", ButtonAction::MainMenu);
+        });
+    }
+
+    fn on_enter_modes(
+        mut commands: Commands,
+        font_spec: Res<FontSpec>,
+        mut return_to: ResMut<ReturnTo>,
+    ) {
+        return_to.0 = GameState::MainMenu;
+
+        MenuPlugin::spawn_screen(&mut commands, &font_spec, "Modes", |parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::width(Val::Px(420.0)),
+                        flex_wrap: FlexWrap::Wrap,
+                        justify_content: JustifyContent::Center,
+                        gap: Size::all(Val::Px(8.0)),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for mode in GameMode::ALL {
+                        button::spawn(
+                            parent,
+                            &font_spec,
+                            &mode.name(),
+                            ButtonAction::SelectMode(mode),
+                        );
+                    }
+                    for difficulty in Difficulty::ALL {
+                        let mode = GameMode::Race { difficulty };
+                        button::spawn(
+                            parent,
+                            &font_spec,
+                            &mode.name(),
+                            ButtonAction::SelectMode(mode),
+                        );
+                    }
+                });
+            button::spawn(
+                parent,
+                &font_spec,
+                "Daily Results",
+                ButtonAction::DailyResults,
+            );
+            button::spawn(parent, &font_spec, "Puzzles", ButtonAction::Levels);
+            button::spawn(
+                parent,
+                &font_spec,
+                "Versus",
+                ButtonAction::SelectMode(GameMode::Versus),
+            );
+            button::spawn(
+                parent,
+                &font_spec,
+                &GameMode::Gallery.name(),
+                ButtonAction::SelectMode(GameMode::Gallery),
+            );
+            button::spawn(
+                parent,
+                &font_spec,
+                "Host LAN Game",
+                ButtonAction::Lan(LanCommand::Host),
+            );
+            button::spawn(
+                parent,
+                &font_spec,
+                "Join LAN Game",
+                ButtonAction::Lan(LanCommand::Join),
+            );
+            button::spawn(parent, &font_spec, "Back", ButtonAction::Back);
+        });
+    }
+
+    /// Shows the state of the connection until the host starts the game.
+    fn on_enter_lobby(mut commands: Commands, font_spec: Res<FontSpec>, lan: Res<Lan>) {
+        let text_style = TextStyle {
+            font: font_spec.family.clone(),
+            font_size: 20.0,
+            color: Color::WHITE,
+        };
+
+        MenuPlugin::spawn_screen(&mut commands, &font_spec, "LAN Game", |parent| {
+            parent.spawn((
+                TextBundle::from_section(lan.describe(), text_style.clone()),
+                LanStatusText,
+            ));
+            button::spawn(
+                parent,
+                &font_spec,
+                "Back",
+                ButtonAction::Lan(LanCommand::Leave),
+            );
+        });
+    }
+
+    fn on_enter_settings(
+        mut commands: Commands,
+        font_spec: Res<FontSpec>,
+        mut capture: ResMut<KeyCapture>,
+    ) {
+        capture.0 = None;
+
+        let text_style = TextStyle {
+            font: font_spec.family.clone(),
+            font_size: 20.0,
+            color: Color::WHITE,
+        };
+
+        MenuPlugin::spawn_screen(&mut commands, &font_spec, "Settings", |parent| {
+            for kind in SettingKind::ALL {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            gap: Size::all(Val::Px(10.0)),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle {
+                            style: Style {
+                                size: Size::width(Val::Px(200.0)),
+                                ..default()
+                            },
+                            ..TextBundle::from_section(kind.label(), text_style.clone())
+                        });
+                        button::spawn(
+                            parent,
+                            &font_spec,
+                            "-",
+                            ButtonAction::AdjustSetting(kind, -1),
+                        );
+                        parent.spawn((
+                            TextBundle {
+                                style: Style {
+                                    size: Size::width(Val::Px(260.0)),
+                                    ..default()
+                                },
+                                ..TextBundle::from_section("", text_style.clone())
+                                    .with_text_alignment(TextAlignment::Center)
+                            },
+                            SettingValue(kind),
+                        ));
+                        button::spawn(
+                            parent,
+                            &font_spec,
+                            "+",
+                            ButtonAction::AdjustSetting(kind, 1),
+                        );
+                        if let SettingKind::Key(binding) = kind {
+                            button::spawn(
+                                parent,
+                                &font_spec,
+                                "Bind...",
+                                ButtonAction::CaptureKey(binding),
+                            );
+                        }
+                    });
+            }
+            button::spawn(parent, &font_spec, "Back", ButtonAction::Back);
+        });
+    }
+
+    fn on_enter_statistics(
+        mut commands: Commands,
+        font_spec: Res<FontSpec>,
+        statistics: Res<Statistics>,
+        mut return_to: ResMut<ReturnTo>,
+    ) {
+        return_to.0 = GameState::MainMenu;
+
+        let text_style = TextStyle {
+            font: font_spec.family.clone(),
+            font_size: 20.0,
+            color: Color::WHITE,
+        };
+        let win_rate = if statistics.games_played == 0 {
+            0.
+        } else {
+            statistics.wins as f32 / statistics.games_played as f32 * 100.
+        };
+        let merges = statistics
+            .merges
+            .iter()
+            .rev()
+            .take(6)
+            .map(|(value, count)| format!("{value}: {count}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        let summary = [
+            ("Games played", statistics.games_played.to_string()),
+            ("Wins", format!("{} ({win_rate:.0}%)", statistics.wins)),
+            ("Best score", statistics.best_score.to_string()),
+            (
+                "Average score",
+                format!("{:.0}", statistics.average_score()),
+            ),
+            ("Best tile", statistics.best_tile.to_string()),
+            ("Total moves", statistics.total_moves.to_string()),
+            (
+                "Longest game",
+                MenuPlugin::format_duration(statistics.longest_game_secs),
+            ),
+            ("Merges", merges),
+        ];
+
+        MenuPlugin::spawn_screen(&mut commands, &font_spec, "Statistics", |parent| {
+            for (label, value) in summary {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            gap: Size::all(Val::Px(10.0)),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle {
+                            style: Style {
+                                size: Size::width(Val::Px(200.0)),
+                                ..default()
+                            },
+                            ..TextBundle::from_section(label, text_style.clone())
+                        });
+                        parent.spawn(TextBundle {
+                            style: Style {
+                                size: Size::width(Val::Px(300.0)),
+                                ..default()
+                            },
+                            ..TextBundle::from_section(value, text_style.clone())
+                        });
+                    });
+            }
+
+            parent.spawn(TextBundle::from_section("Recent games", text_style.clone()));
+            for record in statistics
+                .history
+                .iter()
+                .rev()
+                .take(MenuPlugin::RECENT_GAMES)
+            {
+                parent.spawn(TextBundle::from_section(
+                    format!(
+                        "{}  {}  score {}  tile {}  {} moves{}",
+                        record.date,
+                        record.mode,
+                        record.score,
+                        record.max_tile,
+                        record.moves,
+                        if record.won { "  won" } else { "" }
+                    ),
+                    TextStyle {
+                        font_size: 16.0,
+                        ..text_style.clone()
+                    },
+                ));
+            }
+
+            parent.spawn((TextBundle::from_section("", text_style.clone()), StatusText));
+            button::spawn(
+                parent,
+                &font_spec,
+                "Export CSV",
+                ButtonAction::ExportStatistics,
+            );
+            button::spawn(parent, &font_spec, "Back", ButtonAction::Back);
+        });
+    }
+
+    fn on_enter_leaderboard(
+        mut commands: Commands,
+        font_spec: Res<FontSpec>,
+        settings: Res<Settings>,
+        game_mode: Res<GameMode>,
+        mut view: ResMut<LeaderboardView>,
+        mut return_to: ResMut<ReturnTo>,
+    ) {
+        return_to.0 = GameState::MainMenu;
+        view.0 = RuleSet {
+            board_size: settings.board_size,
+            mode: if game_mode.has_leaderboard() {
+                *game_mode
+            } else {
+                GameMode::Classic
+            },
+        };
+
+        let text_style = TextStyle {
+            font: font_spec.family.clone(),
+            font_size: 20.0,
+            color: Color::WHITE,
+        };
+
+        MenuPlugin::spawn_screen(&mut commands, &font_spec, "Leaderboard", |parent| {
+            for axis in [LeaderboardAxis::Mode, LeaderboardAxis::BoardSize] {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            gap: Size::all(Val::Px(10.0)),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        button::spawn(
+                            parent,
+                            &font_spec,
+                            "<",
+                            ButtonAction::CycleLeaderboard(axis, -1),
+                        );
+                        parent.spawn((
+                            TextBundle {
+                                style: Style {
+                                    size: Size::width(Val::Px(200.0)),
+                                    ..default()
+                                },
+                                ..TextBundle::from_section("", text_style.clone())
+                                    .with_text_alignment(TextAlignment::Center)
+                            },
+                            LeaderboardTitle(axis),
+                        ));
+                        button::spawn(
+                            parent,
+                            &font_spec,
+                            ">",
+                            ButtonAction::CycleLeaderboard(axis, 1),
+                        );
+                    });
+            }
+
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        gap: Size::all(Val::Px(4.0)),
+                        ..default()
+                    },
+                    ..default()
+                },
+                LeaderboardTable,
+            ));
+
+            button::spawn(parent, &font_spec, "Back", ButtonAction::Back);
+        });
+    }
+
+    fn on_enter_achievements(
+        mut commands: Commands,
+        font_spec: Res<FontSpec>,
+        achievements: Res<Achievements>,
+        mut return_to: ResMut<ReturnTo>,
+    ) {
+        return_to.0 = GameState::MainMenu;
+
+        let text_style = TextStyle {
+            font: font_spec.family.clone(),
+            font_size: 18.0,
+            color: Color::WHITE,
+        };
+        let title = format!(
+            "Achievements {}/{}",
+            achievements.unlocked.len(),
+            ACHIEVEMENTS.len()
+        );
+
+        MenuPlugin::spawn_screen(&mut commands, &font_spec, &title, |parent| {
+            for achievement in ACHIEVEMENTS.iter() {
+                let status = achievements
+                    .get(achievement.id)
+                    .map_or("Locked".to_string(), |unlocked| unlocked.date.clone());
+                let columns = [
+                    (180.0, achievement.name.to_string()),
+                    (460.0, achievement.description.to_string()),
+                    (110.0, status),
+                ];
+
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            gap: Size::all(Val::Px(10.0)),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        for (width, value) in columns {
+                            parent.spawn(TextBundle {
+                                style: Style {
+                                    size: Size::width(Val::Px(width)),
+                                    ..default()
+                                },
+                                ..TextBundle::from_section(value, text_style.clone())
+                            });
+                        }
+                    });
+            }
+            button::spawn(parent, &font_spec, "Back", ButtonAction::Back);
+        });
+    }
+
+    fn sys_leaderboard_table(
+        mut commands: Commands,
+        font_spec: Res<FontSpec>,
+        view: Res<LeaderboardView>,
+        leaderboards: Res<Leaderboards>,
+        tables: Query<(Entity, Ref<LeaderboardTable>)>,
+        mut titles: Query<(&mut Text, &LeaderboardTitle)>,
+    ) {
+        let Ok((entity, table)) = tables.get_single() else {
+            return;
+        };
+        if !view.is_changed() && !leaderboards.is_changed() && !table.is_added() {
+            return;
+        }
+
+        for (mut text, title) in titles.iter_mut() {
+            text.sections[0].value = view.title(title.0);
+        }
+
+        let text_style = TextStyle {
+            font: font_spec.family.clone(),
+            font_size: 18.0,
+            color: Color::WHITE,
+        };
+        let entries = leaderboards.entries(view.0);
+
+        commands.entity(entity).despawn_descendants();
+        commands.entity(entity).with_children(|parent| {
+            if entries.is_empty() {
+                parent.spawn(TextBundle::from_section(
+                    "No scores yet",
+                    text_style.clone(),
+                ));
+            }
+
+            for (rank, entry) in entries.iter().enumerate() {
+                let columns = [
+                    (40.0, format!("{}.", rank + 1)),
+                    (160.0, entry.name.clone()),
+                    (90.0, entry.score.to_string()),
+                    (70.0, entry.max_tile.to_string()),
+                    (100.0, format!("{} moves", entry.moves)),
+                    (70.0, MenuPlugin::format_duration(entry.duration_secs)),
+                    (120.0, entry.date.clone()),
+                ];
+
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            gap: Size::all(Val::Px(10.0)),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        for (width, value) in columns {
+                            parent.spawn(TextBundle {
+                                style: Style {
+                                    size: Size::width(Val::Px(width)),
+                                    ..default()
+                                },
+                                ..TextBundle::from_section(value, text_style.clone())
+                            });
+                        }
+                    });
+            }
+        });
+    }
+
+    fn on_enter_daily_results(
+        mut commands: Commands,
+        font_spec: Res<FontSpec>,
+        results: Res<DailyResults>,
+        mut return_to: ResMut<ReturnTo>,
+    ) {
+        return_to.0 = GameState::Modes;
+
+        let text_style = TextStyle {
+            font: font_spec.family.clone(),
+            font_size: 18.0,
+            color: Color::WHITE,
+        };
+
+        MenuPlugin::spawn_screen(&mut commands, &font_spec, "Daily Results", |parent| {
+            if results.results.is_empty() {
+                parent.spawn(TextBundle::from_section(
+                    "No daily challenge played yet",
+                    text_style.clone(),
+                ));
+            }
+
+            for result in results.results.iter().rev().take(MenuPlugin::RECENT_GAMES) {
+                let columns = [
+                    (120.0, result.date.clone()),
+                    (120.0, format!("score {}", result.score)),
+                    (100.0, format!("tile {}", result.max_tile)),
+                    (100.0, format!("{} moves", result.moves)),
+                ];
+
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            gap: Size::all(Val::Px(10.0)),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        for (width, value) in columns {
+                            parent.spawn(TextBundle {
+                                style: Style {
+                                    size: Size::width(Val::Px(width)),
+                                    ..default()
+                                },
+                                ..TextBundle::from_section(value, text_style.clone())
+                            });
+                        }
+                    });
+            }
+            button::spawn(parent, &font_spec, "Back", ButtonAction::Back);
+        });
+    }
+
+    fn on_enter_levels(
+        mut commands: Commands,
+        font_spec: Res<FontSpec>,
+        levels: Levels,
+        results: Res<PuzzleResults>,
+        mut return_to: ResMut<ReturnTo>,
+    ) {
+        return_to.0 = GameState::Modes;
+
+        let text_style = TextStyle {
+            font: font_spec.family.clone(),
+            font_size: 18.0,
+            color: Color::WHITE,
+        };
+
+        MenuPlugin::spawn_screen(&mut commands, &font_spec, "Puzzles", |parent| {
+            for (index, name) in Level::BUILT_IN.iter().enumerate() {
+                // Levels still loading are left out
+                let Some(level) = levels.get(index as u8) else {
+                    continue;
+                };
+                let stars = results.get(name).map_or("-".to_string(), |result| {
+                    format!("{}/3 stars", result.stars)
+                });
+                let columns = [
+                    (200.0, format!("{}. {}", index + 1, level.name)),
+                    (180.0, level.goal.description()),
+                    (110.0, format!("{} moves", level.max_moves)),
+                    (90.0, stars),
+                ];
+
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            gap: Size::all(Val::Px(10.0)),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        for (width, value) in columns {
+                            parent.spawn(TextBundle {
+                                style: Style {
+                                    size: Size::width(Val::Px(width)),
+                                    ..default()
+                                },
+                                ..TextBundle::from_section(value, text_style.clone())
+                            });
+                        }
+                        button::spawn(
+                            parent,
+                            &font_spec,
+                            "Play",
+                            ButtonAction::SelectMode(GameMode::Puzzle { level: index as u8 }),
+                        );
+                    });
+            }
+            button::spawn(parent, &font_spec, "Back", ButtonAction::Back);
+        });
+    }
+
+    /// A panel left of the board, which stays clickable for placing tiles.
+    fn on_enter_editor(
+        mut commands: Commands,
+        font_spec: Res<FontSpec>,
+        mut return_to: ResMut<ReturnTo>,
+    ) {
+        return_to.0 = GameState::MainMenu;
+
+        let text_style = TextStyle {
+            font: font_spec.family.clone(),
+            font_size: 16.0,
+            color: Color::WHITE,
+        };
+        let small_button = Style {
+            size: Size::new(Val::Px(30.0), Val::Px(26.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        };
+
+        commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            left: Val::Px(10.0),
+                            top: Val::Px(60.0),
+                            ..default()
+                        },
+                        flex_direction: FlexDirection::Column,
+                        gap: Size::all(Val::Px(6.0)),
+                        ..default()
+                    },
+                    z_index: ZIndex::Global(10),
+                    ..default()
+                },
+                MenuScreen,
+            ))
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    "Left click: place, right click: clear",
+                    text_style.clone(),
+                ));
+                for field in EditorField::ALL {
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                align_items: AlignItems::Center,
+                                gap: Size::all(Val::Px(6.0)),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle {
+                                style: Style {
+                                    size: Size::width(Val::Px(90.0)),
+                                    ..default()
+                                },
+                                ..TextBundle::from_section(field.label(), text_style.clone())
+                            });
+                            button::spawn(
+                                parent,
+                                &font_spec,
+                                "-",
+                                ButtonAction::AdjustEditor(field, -1),
+                            )
+                            .insert(small_button.clone());
+                            parent.spawn((
+                                TextBundle {
+                                    style: Style {
+                                        size: Size::width(Val::Px(110.0)),
+                                        ..default()
+                                    },
+                                    ..TextBundle::from_section("", text_style.clone())
+                                        .with_text_alignment(TextAlignment::Center)
+                                },
+                                EditorValue(field),
+                            ));
+                            button::spawn(
+                                parent,
+                                &font_spec,
+                                "+",
+                                ButtonAction::AdjustEditor(field, 1),
+                            )
+                            .insert(small_button.clone());
+                        });
+                }
+                button::spawn(
+                    parent,
+                    &font_spec,
+                    "Play",
+                    ButtonAction::SelectMode(GameMode::Custom),
+                );
+                button::spawn(parent, &font_spec, "Save", ButtonAction::SaveLevel);
+                button::spawn(parent, &font_spec, "Clear", ButtonAction::ClearLevel);
+                button::spawn(parent, &font_spec, "Back", ButtonAction::Back);
+                parent.spawn((TextBundle::from_section("", text_style), StatusText));
+            });
+    }
+
+    fn sys_editor_values(editor: Res<Editor>, mut values: Query<(&mut Text, &EditorValue)>) {
+        for (mut text, value) in values.iter_mut() {
+            text.sections[0].value = value.0.display(&editor);
+        }
+    }
+
+    /// Shows the result card of a level while in [`GameState::GameOver`].
+    fn sys_puzzle_card(
+        mut commands: Commands,
+        font_spec: Res<FontSpec>,
+        card: Res<PuzzleCard>,
+        levels: Levels,
+        screens: Query<Entity, With<PuzzleCardScreen>>,
+    ) {
+        if !card.is_changed() {
+            return;
+        }
+        for entity in screens.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        let Some(outcome) = card.0.as_ref() else {
+            return;
+        };
+
+        let text_style = TextStyle {
+            font: font_spec.family.clone(),
+            font_size: 20.0,
+            color: Color::WHITE,
+        };
+        let title = if outcome.stars > 0 {
+            "Level complete"
+        } else {
+            "Level failed"
+        };
+
+        let screen = MenuPlugin::spawn_screen(&mut commands, &font_spec, title, |parent| {
+            parent.spawn(TextBundle::from_section(
+                format!("{}: {}", outcome.name, outcome.goal.description()),
+                text_style.clone(),
+            ));
+            parent.spawn(TextBundle::from_section(
+                if outcome.stars > 0 {
+                    format!("{} moves, {}/3 stars", outcome.moves, outcome.stars)
+                } else {
+                    format!("{} moves", outcome.moves)
+                },
+                text_style,
+            ));
+
+            match outcome.mode {
+                GameMode::Puzzle { level } => {
+                    let next = level + 1;
+                    if outcome.stars > 0 && levels.get(next).is_some() {
+                        button::spawn(
+                            parent,
+                            &font_spec,
+                            "Next Level",
+                            ButtonAction::SelectMode(GameMode::Puzzle { level: next }),
+                        );
+                    }
+                    button::spawn(parent, &font_spec, "Retry", ButtonAction::NewGame);
+                    button::spawn(parent, &font_spec, "Levels", ButtonAction::Levels);
+                }
+                _ => {
+                    button::spawn(parent, &font_spec, "Retry", ButtonAction::NewGame);
+                    button::spawn(parent, &font_spec, "Edit", ButtonAction::Editor);
+                }
+            }
+            button::spawn(parent, &font_spec, "Main Menu", ButtonAction::MainMenu);
+        });
+        commands.entity(screen).insert(PuzzleCardScreen);
+    }
+
+    /// Shows the winner of a versus round or a race while in [`GameState::GameOver`].
+    fn sys_versus_card(
+        mut commands: Commands,
+        font_spec: Res<FontSpec>,
+        card: Res<VersusCard>,
+        screens: Query<Entity, With<VersusCardScreen>>,
+    ) {
+        if !card.is_changed() {
+            return;
+        }
+        for entity in screens.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        let Some(outcome) = card.0.as_ref() else {
+            return;
+        };
+
+        let text_style = TextStyle {
+            font: font_spec.family.clone(),
+            font_size: 20.0,
+            color: Color::WHITE,
+        };
+        let title = match outcome.winner {
+            Some(winner) => format!("{} wins", outcome.players[winner].name),
+            None => "Draw".to_string(),
+        };
+
+        let screen = MenuPlugin::spawn_screen(&mut commands, &font_spec, &title, |parent| {
+            for player in outcome.players.iter() {
+                let mut line = format!(
+                    "{}: {} points in {} moves",
+                    player.name, player.score, player.moves
+                );
+                if player.stuck {
+                    line.push_str(", stuck");
+                }
+                parent.spawn(TextBundle::from_section(line, text_style.clone()));
+            }
+
+            button::spawn(parent, &font_spec, "Rematch", ButtonAction::NewGame);
+            button::spawn(parent, &font_spec, "Main Menu", ButtonAction::MainMenu);
+        });
+        commands.entity(screen).insert(VersusCardScreen);
+    }
+
+    /// Shows the result card of a daily challenge while in [`GameState::GameOver`].
+    fn sys_daily_card(
+        mut commands: Commands,
+        font_spec: Res<FontSpec>,
+        theme: Res<CurrentTheme>,
+        card: Res<DailyCard>,
+        screens: Query<Entity, With<DailyCardScreen>>,
+    ) {
+        if !card.is_changed() {
+            return;
+        }
+        for entity in screens.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        let Some((result, attempt)) = card.0.as_ref() else {
+            return;
+        };
+
+        let text_style = TextStyle {
+            font: font_spec.family.clone(),
+            font_size: 20.0,
+            color: Color::WHITE,
+        };
+        let title = if attempt.ranked {
+            "Daily Challenge"
+        } else {
+            "Daily Practice"
+        };
+
+        let screen = MenuPlugin::spawn_screen(&mut commands, &font_spec, title, |parent| {
+            parent.spawn(TextBundle::from_section(
+                format!(
+                    "{}  score {}  tile {}  {} moves",
+                    result.date, result.score, result.max_tile, result.moves
+                ),
+                text_style.clone(),
+            ));
+
+            // The final board, drawn with the colors of the current theme
+            for row in result.board.iter() {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            gap: Size::all(Val::Px(4.0)),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        for &value in row {
+                            let color = match value {
+                                0 => theme.empty_cell.0,
+                                value => theme.tile_style(value).background.0,
+                            };
+                            parent.spawn(NodeBundle {
+                                style: Style {
+                                    size: Size::all(Val::Px(24.0)),
+                                    ..default()
+                                },
+                                background_color: color.into(),
+                                ..default()
+                            });
+                        }
+                    });
+            }
+
+            let note = if attempt.ranked {
+                format!(
+                    "Share text saved to {}",
+                    storage::data_dir().join(DailyResults::SHARE_FILE).display()
+                )
+            } else {
+                "Only the first attempt of the day is scored".to_string()
+            };
+            parent.spawn(TextBundle::from_section(note, text_style));
+            button::spawn(parent, &font_spec, "Results", ButtonAction::DailyResults);
+            button::spawn(parent, &font_spec, "Main Menu", ButtonAction::MainMenu);
+        });
+        commands.entity(screen).insert(DailyCardScreen);
+    }
+
+    /// Shows the [`NamePrompt`] while a [`PendingEntry`] waits for its name.
+    fn sys_name_prompt(
+        mut commands: Commands,
+        font_spec: Res<FontSpec>,
+        pending: Res<PendingEntry>,
+        leaderboards: Res<Leaderboards>,
+        prompts: Query<Entity, With<NamePrompt>>,
+        mut names: Query<&mut Text, With<NameText>>,
+    ) {
+        let Some((rules, entry)) = pending.0.as_ref() else {
+            for entity in prompts.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+            return;
+        };
+
+        if prompts.is_empty() {
+            let text_style = TextStyle {
+                font: font_spec.family.clone(),
+                font_size: 20.0,
+                color: Color::WHITE,
+            };
+            let rank = leaderboards.rank(*rules, entry);
+
+            let prompt =
+                MenuPlugin::spawn_screen(&mut commands, &font_spec, "New high score!", |parent| {
+                    parent.spawn(TextBundle::from_section(
+                        if rules.mode.ranks_by_time() {
+                            format!(
+                                "#{} on {} in {}",
+                                rank + 1,
+                                rules.name(),
+                                MenuPlugin::format_duration(entry.duration_secs)
+                            )
+                        } else {
+                            format!("#{} on {} with {}", rank + 1, rules.name(), entry.score)
+                        },
+                        text_style.clone(),
+                    ));
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font_size: 40.0,
+                                ..text_style.clone()
+                            },
+                        ),
+                        NameText,
+                    ));
+                    parent.spawn(TextBundle::from_section(
+                        "Type your name, enter to save",
+                        text_style,
+                    ));
+                });
+            commands.entity(prompt).insert(NamePrompt);
+        }
+
+        for mut text in names.iter_mut() {
+            text.sections[0].value = format!("{}_", entry.name);
+        }
+    }
+
+    fn sys_setting_values(
+        settings: Res<Settings>,
+        capture: Res<KeyCapture>,
+        mut values: Query<(&mut Text, &SettingValue)>,
+    ) {
+        for (mut text, value) in values.iter_mut() {
+            text.sections[0].value = match value.0 {
+                SettingKind::Key(binding) if capture.0 == Some(binding) => "Press a key...".into(),
+                kind => kind.display(&settings),
+            };
+        }
+    }
+
+    /// Adds the next pressed key to the binding waiting in [`KeyCapture`], escape cancels.
+    fn sys_capture_key(
+        input: Res<Input<KeyCode>>,
+        mut capture: ResMut<KeyCapture>,
+        mut settings: ResMut<Settings>,
+    ) {
+        let Some(binding) = capture.0 else {
+            return;
+        };
+        let Some(&key_code) = input.get_just_pressed().next() else {
+            return;
+        };
+
+        capture.0 = None;
+        if key_code == KeyCode::Escape {
+            return;
+        }
+
+        let keys = settings.key_bindings.keys_mut(binding);
+        if !keys.contains(&key_code) {
+            keys.push(key_code);
+        }
+    }
+
+    fn sys_back_on_escape(
+        input: Res<Input<KeyCode>>,
+        capture: Res<KeyCapture>,
+        run_state: Res<State<GameState>>,
+        return_to: Res<ReturnTo>,
+        mut next_state: ResMut<NextState<GameState>>,
+    ) {
+        if !input.just_pressed(KeyCode::Escape) || capture.0.is_some() {
+            return;
+        }
+
+        match run_state.0 {
+            GameState::Modes
+            | GameState::Settings
+            | GameState::Statistics
+            | GameState::Leaderboard
+            | GameState::Achievements
+            | GameState::DailyResults
+            | GameState::Levels
+            | GameState::Editor => {
+                next_state.set(return_to.0.clone());
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReturnTo>()
+            .init_resource::<KeyCapture>()
+            .init_resource::<LeaderboardView>()
+            .add_system(MenuPlugin::sys_setting_values)
+            .add_system(MenuPlugin::sys_editor_values.in_set(OnUpdate(GameState::Editor)))
+            .add_system(MenuPlugin::sys_leaderboard_table)
+            .add_system(MenuPlugin::sys_name_prompt)
+            .add_system(MenuPlugin::sys_daily_card)
+            .add_system(MenuPlugin::sys_puzzle_card)
+            .add_system(MenuPlugin::sys_versus_card)
+            .add_systems((MenuPlugin::sys_back_on_escape, MenuPlugin::sys_capture_key).chain());
+
+        for state in [
+            GameState::MainMenu,
+            GameState::Modes,
+            GameState::Settings,
+            GameState::Statistics,
+            GameState::Leaderboard,
+            GameState::Achievements,
+            GameState::DailyResults,
+            GameState::Levels,
+            GameState::Editor,
+            GameState::Lobby,
+            GameState::Paused,
+        ] {
+            app.add_system(MenuPlugin::despawn_screen.in_schedule(OnExit(state)));
+        }
+
+        app.add_system(MenuPlugin::on_enter_main_menu.in_schedule(OnEnter(GameState::MainMenu)))
+            .add_system(MenuPlugin::on_enter_modes.in_schedule(OnEnter(GameState::Modes)))
+            .add_system(MenuPlugin::on_enter_settings.in_schedule(OnEnter(GameState::Settings)))
+            .add_system(MenuPlugin::on_enter_statistics.in_schedule(OnEnter(GameState::Statistics)))
+            .add_system(
+                MenuPlugin::on_enter_leaderboard.in_schedule(OnEnter(GameState::Leaderboard)),
+            )
+            .add_system(
+                MenuPlugin::on_enter_achievements.in_schedule(OnEnter(GameState::Achievements)),
+            )
+            .add_system(
+                MenuPlugin::on_enter_daily_results.in_schedule(OnEnter(GameState::DailyResults)),
+            )
+            .add_system(MenuPlugin::on_enter_levels.in_schedule(OnEnter(GameState::Levels)))
+            .add_system(MenuPlugin::on_enter_editor.in_schedule(OnEnter(GameState::Editor)))
+            .add_system(MenuPlugin::on_enter_lobby.in_schedule(OnEnter(GameState::Lobby)))
+            .add_system(MenuPlugin::on_enter_paused.in_schedule(OnEnter(GameState::Paused)));
+    }
+}
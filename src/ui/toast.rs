@@ -0,0 +1,132 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use super::score_box::ScoreBox;
+use crate::{achievements::AchievementUnlockedEvent, assets::FontSpec};
+
+/// Short notifications stacked in the bottom right corner.
+pub struct ToastPlugin;
+
+/// How long a toast stays on screen.
+const TOAST_DURATION: Duration = Duration::from_secs(4);
+
+/// The node the toasts are stacked in.
+#[derive(Component)]
+struct ToastStack;
+
+#[derive(Component)]
+struct Toast(Timer);
+
+/// Shows a toast with a title line and a message line.
+pub struct ToastEvent {
+    pub title: String,
+    pub message: String,
+}
+
+impl ToastPlugin {
+    fn on_startup(mut commands: Commands) {
+        commands.spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        right: Val::Px(20.0),
+                        bottom: Val::Px(20.0),
+                        ..default()
+                    },
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::FlexEnd,
+                    gap: Size::all(Val::Px(10.0)),
+                    ..default()
+                },
+                z_index: ZIndex::Global(20),
+                ..default()
+            },
+            ToastStack,
+        ));
+    }
+
+    fn sys_achievement_toasts(
+        mut unlocked_reader: EventReader<AchievementUnlockedEvent>,
+        mut toast_writer: EventWriter<ToastEvent>,
+    ) {
+        for AchievementUnlockedEvent(achievement) in unlocked_reader.iter() {
+            toast_writer.send(ToastEvent {
+                title: format!("Achievement unlocked: {}", achievement.name),
+                message: achievement.description.to_string(),
+            });
+        }
+    }
+
+    fn sys_spawn_toasts(
+        mut commands: Commands,
+        mut toast_reader: EventReader<ToastEvent>,
+        font_spec: Res<FontSpec>,
+        stacks: Query<Entity, With<ToastStack>>,
+    ) {
+        let Ok(stack) = stacks.get_single() else {
+            return;
+        };
+
+        for event in toast_reader.iter() {
+            commands.entity(stack).with_children(|parent| {
+                parent
+                    .spawn((
+                        NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Column,
+                                padding: UiRect::all(Val::Px(10.0)),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        ScoreBox,
+                        Toast(Timer::new(TOAST_DURATION, TimerMode::Once)),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            event.title.clone(),
+                            TextStyle {
+                                font: font_spec.family.clone(),
+                                font_size: 20.0,
+                                color: Color::WHITE,
+                            },
+                        ));
+                        parent.spawn(TextBundle::from_section(
+                            event.message.clone(),
+                            TextStyle {
+                                font: font_spec.family.clone(),
+                                font_size: 15.0,
+                                color: Color::WHITE,
+                            },
+                        ));
+                    });
+            });
+        }
+    }
+
+    fn sys_expire_toasts(
+        mut commands: Commands,
+        time: Res<Time>,
+        mut toasts: Query<(Entity, &mut Toast)>,
+    ) {
+        for (entity, mut toast) in toasts.iter_mut() {
+            if toast.0.tick(time.delta()).finished() {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+impl Plugin for ToastPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ToastEvent>()
+            .add_startup_system(ToastPlugin::on_startup)
+            .add_systems((
+                ToastPlugin::sys_achievement_toasts,
+                ToastPlugin::sys_spawn_toasts,
+                ToastPlugin::sys_expire_toasts,
+            ));
+    }
+}
@@ -0,0 +1,337 @@
+use std::time::Duration;
+
+use bevy::{ecs::event::ManualEventReader, prelude::*};
+use itertools::Itertools;
+use rand::seq::IteratorRandom;
+
+use crate::{
+    assets::FontSpec,
+    board::{
+        tile, Board, BoardShiftDirection, Caption, NewTileEvent, TileSpawnedEvent, TilesMergedEvent,
+    },
+    game::{self, BoardGame, Game, GameState},
+};
+
+/// Two players share the keyboard in [`game::GameMode::Versus`], each on a board with a [`Contender`].
+///
+/// Both boards get the same spawns, big merges send [`Attack`]s to the other board. The round
+/// ends when a board is stuck or the time is up, the survivor or the best score wins.
+pub struct VersusPlugin;
+
+/// How long a round lasts when both players keep moving.
+pub const ROUND_DURATION: Duration = Duration::from_secs(3 * 60);
+
+/// The keys of a player, each controls a board of its own.
+///
+/// They are fixed to keep the players on their own half of the keyboard, whatever the
+/// direction keys of [`crate::settings::KeyBindings`] are. Each caption names them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Controls {
+    Wasd,
+    Arrows,
+}
+
+impl Controls {
+    pub const ALL: [Controls; 2] = [Controls::Wasd, Controls::Arrows];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Controls::Wasd => "WASD",
+            Controls::Arrows => "Arrows",
+        };
+    }
+
+    pub fn direction(&self, key_code: &KeyCode) -> Option<BoardShiftDirection> {
+        return match (self, key_code) {
+            (Controls::Wasd, KeyCode::A) | (Controls::Arrows, KeyCode::Left) => {
+                Some(BoardShiftDirection::Left)
+            }
+            (Controls::Wasd, KeyCode::D) | (Controls::Arrows, KeyCode::Right) => {
+                Some(BoardShiftDirection::Right)
+            }
+            (Controls::Wasd, KeyCode::W) | (Controls::Arrows, KeyCode::Up) => {
+                Some(BoardShiftDirection::Up)
+            }
+            (Controls::Wasd, KeyCode::S) | (Controls::Arrows, KeyCode::Down) => {
+                Some(BoardShiftDirection::Down)
+            }
+            _ => None,
+        };
+    }
+}
+
+/// Sent to the other board by a big merge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attack {
+    /// A tile of [`Attack::JUNK_VALUE`], which only merges with another junk tile.
+    Junk,
+    /// A cell tiles can't enter for the rest of the round.
+    Blocker,
+}
+
+impl Attack {
+    pub const JUNK_VALUE: u32 = 1;
+    /// Merging a tile of at least this value sends junk.
+    pub const JUNK_MERGE: u32 = 32;
+    /// Merging a tile of at least this value sends a blocker.
+    pub const BLOCKER_MERGE: u32 = 256;
+    /// Further blockers land as junk, so a board never gets walled off.
+    pub const MAX_BLOCKERS: usize = 2;
+
+    pub fn of_merge(value: u32) -> Option<Attack> {
+        if value >= Attack::BLOCKER_MERGE {
+            return Some(Attack::Blocker);
+        }
+        if value >= Attack::JUNK_MERGE {
+            return Some(Attack::Junk);
+        }
+        return None;
+    }
+}
+
+/// A player of a versus round, on its board entity next to its [`BoardGame`].
+#[derive(Component, Debug, Clone)]
+pub struct Contender {
+    /// The column of the board, from `0`.
+    pub player: usize,
+    pub controls: Controls,
+    /// Attacks of the other player, landing after the next move.
+    pub incoming: Vec<Attack>,
+}
+
+impl Contender {
+    pub fn new(player: usize) -> Self {
+        return Self {
+            player,
+            controls: Controls::ALL[player % Controls::ALL.len()],
+            incoming: Vec::new(),
+        };
+    }
+
+    pub fn name(&self) -> String {
+        return format!("Player {}", self.player + 1);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ContenderResult {
+    pub name: String,
+    pub score: u32,
+    pub moves: u32,
+    pub stuck: bool,
+}
+
+/// The result of a versus round or a [`crate::race`], shown on the result card.
+#[derive(Resource, Debug, Default)]
+pub struct VersusCard(pub Option<VersusOutcome>);
+
+#[derive(Debug, Clone)]
+pub struct VersusOutcome {
+    /// By player.
+    pub players: Vec<ContenderResult>,
+    /// The index of the winner in `players`, `None` for a draw.
+    pub winner: Option<usize>,
+}
+
+impl VersusOutcome {
+    /// Players that got stuck lose to the ones still moving, then the best score wins.
+    pub fn new(players: Vec<ContenderResult>) -> Self {
+        let rank = |player: &ContenderResult| (!player.stuck, player.score);
+        let best = players.iter().map(rank).max();
+        let leaders = players
+            .iter()
+            .positions(|player| Some(rank(player)) == best)
+            .collect::<Vec<_>>();
+
+        return Self {
+            winner: (leaders.len() == 1).then(|| leaders[0]),
+            players,
+        };
+    }
+
+    /// Ranks boards that all play on until they are stuck by their score.
+    pub fn by_score(mut boards: Vec<(&Board, &BoardGame, &Caption)>) -> Self {
+        boards.sort_by_key(|(board, _, _)| board.column);
+
+        return Self::new(
+            boards
+                .into_iter()
+                .map(|(_, board_game, caption)| ContenderResult {
+                    name: caption.name.clone(),
+                    score: board_game.score,
+                    moves: board_game.moves,
+                    // Being stuck first doesn't lose
+                    stuck: false,
+                })
+                .collect(),
+        );
+    }
+}
+
+impl VersusPlugin {
+    /// Queues an attack on every other board for each big merge.
+    fn sys_send_attacks(
+        mut merged_reader: EventReader<TilesMergedEvent>,
+        mut contenders: Query<(Entity, &mut Contender)>,
+    ) {
+        for event in merged_reader.iter() {
+            let Some(attack) = event.points.number().and_then(Attack::of_merge) else {
+                continue;
+            };
+
+            for (entity, mut contender) in contenders.iter_mut() {
+                if entity != event.board {
+                    contender.incoming.push(attack);
+                }
+            }
+        }
+    }
+
+    /// Lands the queued attacks on the free cells left after the new tile of a move.
+    fn sys_land_attacks(
+        mut commands: Commands,
+        mut new_tile_reader: EventReader<NewTileEvent>,
+        mut spawned_reader: Local<ManualEventReader<TileSpawnedEvent>>,
+        mut spawned_events: ResMut<Events<TileSpawnedEvent>>,
+        mut query_board: Query<(&Board, &mut BoardGame, &mut Contender)>,
+        tiles: Query<(&tile::Position, &Parent)>,
+        blockers: Query<&Parent, With<tile::Blocker>>,
+        font_spec: Res<FontSpec>,
+    ) {
+        let spawned = spawned_reader
+            .iter(&spawned_events)
+            .copied()
+            .collect::<Vec<_>>();
+
+        for event in new_tile_reader.iter() {
+            let Ok((board, mut board_game, mut contender)) = query_board.get_mut(event.board)
+            else {
+                continue;
+            };
+            if contender.incoming.is_empty() {
+                continue;
+            }
+
+            // The tile of this move is not spawned yet
+            let mut occupied = Board::tiles_of(event.board, &tiles);
+            occupied.extend(
+                spawned
+                    .iter()
+                    .filter(|spawned| spawned.board == event.board)
+                    .map(|spawned| spawned.position),
+            );
+            let mut blocker_count = blockers
+                .iter()
+                .filter(|parent| parent.get() == event.board)
+                .count();
+
+            while let Some(attack) = contender.incoming.pop() {
+                // With the seeded spawns, so a round replays the same from its seed and moves
+                let Some(position) = board
+                    .grid
+                    .cells(board.size)
+                    .filter(|position| !occupied.contains(position))
+                    .choose(&mut board_game.rng)
+                else {
+                    // Waits for a free cell
+                    contender.incoming.push(attack);
+                    break;
+                };
+                occupied.push(position);
+
+                if attack == Attack::Blocker && blocker_count < Attack::MAX_BLOCKERS {
+                    board.spawn_blocker(&mut commands, event.board, position);
+                    blocker_count += 1;
+                    continue;
+                }
+
+                let points = tile::Points::new(Attack::JUNK_VALUE);
+                spawned_events.send(TileSpawnedEvent {
+                    entity: board.spawn_tile(
+                        &mut commands,
+                        event.board,
+                        &font_spec,
+                        position,
+                        points,
+                    ),
+                    board: event.board,
+                    position,
+                    points,
+                });
+            }
+        }
+    }
+
+    /// Ends the round once a board is stuck or the time is up.
+    fn sys_check_round_end(
+        game: Res<Game>,
+        query_board: Query<&BoardGame, With<Contender>>,
+        mut game_state: ResMut<NextState<GameState>>,
+    ) {
+        if query_board.is_empty() {
+            return;
+        }
+
+        if game.elapsed >= ROUND_DURATION || query_board.iter().any(|board_game| board_game.over) {
+            game_state.set(GameState::GameOver);
+        }
+    }
+
+    fn on_game_over(mut card: ResMut<VersusCard>, contenders: Query<(&Contender, &BoardGame)>) {
+        let mut players = contenders.iter().collect::<Vec<_>>();
+        if players.is_empty() {
+            return;
+        }
+        players.sort_by_key(|(contender, _)| contender.player);
+
+        card.0 = Some(VersusOutcome::new(
+            players
+                .into_iter()
+                .map(|(contender, board_game)| ContenderResult {
+                    name: contender.name(),
+                    score: board_game.score,
+                    moves: board_game.moves,
+                    stuck: board_game.over,
+                })
+                .collect(),
+        ));
+    }
+
+    fn on_exit_game_over(mut card: ResMut<VersusCard>) {
+        card.0 = None;
+    }
+
+    /// Shows the incoming attacks next to the score of each player.
+    fn sys_update_captions(mut query_board: Query<(&Contender, &mut Caption)>) {
+        for (contender, mut caption) in query_board.iter_mut() {
+            let note = match contender.incoming.len() {
+                0 => String::new(),
+                count => format!("{count} incoming"),
+            };
+            if caption.note != note {
+                caption.note = note;
+            }
+        }
+    }
+}
+
+impl Plugin for VersusPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VersusCard>()
+            .add_system(VersusPlugin::sys_update_captions.before(Board::sys_render_captions))
+            .add_systems(
+                (
+                    VersusPlugin::sys_check_round_end
+                        .after(game::tick_timer)
+                        .after(game::check_game_over)
+                        .before(BoardShiftDirection::sys_handle_board_shift_on_keypress),
+                    VersusPlugin::sys_send_attacks
+                        .after(BoardShiftDirection::sys_handle_board_shift_on_keypress),
+                    VersusPlugin::sys_land_attacks.after(Board::on_new_tile_handler),
+                )
+                    .in_set(OnUpdate(GameState::Playing)),
+            )
+            .add_system(VersusPlugin::on_game_over.in_schedule(OnEnter(GameState::GameOver)))
+            .add_system(VersusPlugin::on_exit_game_over.in_schedule(OnExit(GameState::GameOver)));
+    }
+}